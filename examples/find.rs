@@ -15,7 +15,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     if args.len() != 2 {
         eprintln!("Usage: {} <window name>", args[0]);
-        return Err("No window provided")?;
+        Err("No window provided")?;
     }
 
     let window_name = &args[1];
@@ -0,0 +1,192 @@
+//! BMP decoder
+//!
+//! Reads uncompressed Windows bitmaps (`BITMAPINFOHEADER` and later headers):
+//! - palette images (1, 4 and 8 bits per pixel),
+//! - 16, 24 and 32 bits per pixel images, with the default layout or channel masks (`BI_BITFIELDS`).
+//!
+//! Rows are stored bottom-up unless the height is negative.
+
+use std::error::Error;
+
+use super::Bitmap;
+
+/// Uncompressed pixels
+const BI_RGB: u32 = 0;
+/// Uncompressed pixels with channel masks
+const BI_BITFIELDS: u32 = 3;
+/// Uncompressed pixels with channel masks (alpha included)
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// Reads a BMP image
+///
+/// # Arguments
+///
+/// * `data` - The content of the image file
+///
+/// # Returns
+///
+/// The decoded image
+///
+/// # Errors
+///
+/// Returns an error if the image is malformed, compressed or uses an unsupported header.
+pub fn read_bmp(data: &[u8]) -> Result<Bitmap, Box<dyn Error>> {
+    let u16_at = |offset: usize| -> Result<u16, Box<dyn Error>> {
+        let bytes = data.get(offset..offset + 2).ok_or("BMP header is too short")?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let u32_at = |offset: usize| -> Result<u32, Box<dyn Error>> {
+        let bytes = data.get(offset..offset + 4).ok_or("BMP header is too short")?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    if data.get(..2) != Some(b"BM") {
+        Err("Not a BMP image")?;
+    }
+
+    let offset = u32_at(10)? as usize;
+    let header_size = u32_at(14)? as usize;
+    if header_size < 40 {
+        Err("Unsupported BMP header")?;
+    }
+
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bits = u16_at(28)?;
+    let compression = u32_at(30)?;
+
+    let width = u16::try_from(width).map_err(|_| "Invalid BMP width")?;
+    let top_down = height < 0;
+    let height = u16::try_from(height.unsigned_abs()).map_err(|_| "Invalid BMP height")?;
+
+    // Channel masks (red, green, blue, alpha), stored after the 40 bytes header
+    let masks = match (compression, bits) {
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            let alpha = match header_size >= 56 || compression == BI_ALPHABITFIELDS {
+                true => u32_at(66)?,
+                false => 0,
+            };
+            [u32_at(54)?, u32_at(58)?, u32_at(62)?, alpha]
+        }
+        (BI_RGB, 16) => [0x7C00, 0x03E0, 0x001F, 0],
+        (BI_RGB, 24 | 32) => [0xFF0000, 0x00FF00, 0x0000FF, 0],
+        (BI_RGB, 1 | 4 | 8) => [0; 4],
+        (BI_RGB, _) => Err("Unsupported BMP bits per pixel")?,
+        _ => Err("Compressed BMP images are not supported")?,
+    };
+
+    // The palette follows the header (blue, green, red, reserved)
+    let palette: Vec<[u8; 4]> = match bits {
+        1 | 4 | 8 => {
+            let count = match u32_at(46)? {
+                0 => 1 << bits,
+                count => count as usize,
+            };
+            let start = 14 + header_size;
+            let entries = data.get(start..start + count * 4).ok_or("BMP palette is too short")?;
+            entries.chunks(4).map(|entry| [entry[2], entry[1], entry[0], 0xFF]).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    // Rows are padded to 4 bytes
+    let stride = (width as usize * bits as usize).div_ceil(32) * 4;
+    let rows = data
+        .get(offset..offset + stride * height as usize)
+        .ok_or("BMP data is too short")?;
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height as usize {
+        let row = match top_down {
+            true => &rows[y * stride..][..stride],
+            false => &rows[(height as usize - 1 - y) * stride..][..stride],
+        };
+
+        for x in 0..width as usize {
+            let pixel = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let index = (row[bit / 8] >> (8 - bits as usize - bit % 8)) & ((1 << bits) - 1) as u8;
+                    *palette.get(index as usize).ok_or("BMP palette index out of range")?
+                }
+                _ => {
+                    let bytes = &row[x * bits as usize / 8..][..bits as usize / 8];
+                    let value = bytes.iter().rev().fold(0u32, |value, byte| (value << 8) | *byte as u32);
+                    let channel = |mask: u32| match mask {
+                        0 => 0xFF,
+                        mask => {
+                            let max = (mask >> mask.trailing_zeros()) as u64;
+                            (((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max) as u8
+                        }
+                    };
+                    [channel(masks[0]), channel(masks[1]), channel(masks[2]), channel(masks[3])]
+                }
+            };
+            pixels.push(pixel);
+        }
+    }
+
+    Bitmap::new(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a BMP file with a 40 bytes header
+    fn bmp(width: i32, height: i32, bits: u16, compression: u32, extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let offset = 14 + 40 + extra.len() as u32;
+        let mut file = Vec::new();
+        file.extend_from_slice(b"BM");
+        file.extend_from_slice(&(offset + data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&offset.to_le_bytes());
+        file.extend_from_slice(&40u32.to_le_bytes());
+        file.extend_from_slice(&width.to_le_bytes());
+        file.extend_from_slice(&height.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&bits.to_le_bytes());
+        file.extend_from_slice(&compression.to_le_bytes());
+        file.extend_from_slice(&[0; 20]);
+        file.extend_from_slice(extra);
+        file.extend_from_slice(data);
+        file
+    }
+
+    #[test]
+    fn test_read_24() {
+        // Bottom-up rows padded to 4 bytes, pixels are stored as blue, green, red
+        let data = [1, 2, 3, 0, 4, 5, 6, 0];
+        let bitmap = read_bmp(&bmp(1, 2, 24, BI_RGB, &[], &data)).unwrap();
+        assert_eq!(bitmap.pixels(), &[[6, 5, 4, 0xFF], [3, 2, 1, 0xFF]]);
+
+        let bitmap = read_bmp(&bmp(1, -2, 24, BI_RGB, &[], &data)).unwrap();
+        assert_eq!(bitmap.pixels(), &[[3, 2, 1, 0xFF], [6, 5, 4, 0xFF]]);
+    }
+
+    #[test]
+    fn test_read_32_bitfields() {
+        let masks: Vec<u8> = [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000]
+            .iter()
+            .flat_map(|mask| mask.to_le_bytes())
+            .collect();
+        let bitmap = read_bmp(&bmp(1, 1, 32, BI_ALPHABITFIELDS, &masks, &[1, 2, 3, 0x80])).unwrap();
+        assert_eq!(bitmap.pixels(), &[[3, 2, 1, 0x80]]);
+    }
+
+    #[test]
+    fn test_read_palette() {
+        let mut file = bmp(3, 1, 1, BI_RGB, &[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0], &[0b1010_0000, 0, 0, 0]);
+        // Two colors in the palette
+        file[46] = 2;
+        let bitmap = read_bmp(&file).unwrap();
+        assert_eq!(bitmap.pixels(), &[[0xFF, 0xFF, 0xFF, 0xFF], [0, 0, 0, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read_bmp(b"BM").is_err());
+        assert!(read_bmp(&bmp(2, 2, 24, BI_RGB, &[], &[0; 4])).is_err());
+        assert!(read_bmp(&bmp(1, 1, 8, 1, &[], &[0; 4])).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Codec module
+//!
+//! This module is used to convert images from and to other representations:
+//! - the X11 image format (`GetImage` replies and `PutImage` requests),
+//! - the Netpbm formats (PPM for color images, PGM for gray images, PBM for masks),
+//! - the BMP format (read only),
+//! - the PNG format (behind the `png` feature).
+
+use std::{error::Error, path::Path};
+
+pub mod bmp;
+pub mod ppm;
+pub mod ximage;
+
+#[cfg(feature = "png")]
+pub mod png;
+
+/// Represents a decoded RGBA image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    /// The width of the image (in pixels)
+    width: u16,
+    /// The height of the image (in pixels)
+    height: u16,
+    /// The RGBA pixels (row major, not premultiplied)
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Bitmap {
+    /// Creates a new bitmap from RGBA pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the image
+    /// * `height` - The height of the image
+    /// * `pixels` - The RGBA pixels (row major, not premultiplied)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixel count does not match the size.
+    pub fn new(width: u16, height: u16, pixels: Vec<[u8; 4]>) -> Result<Self, Box<dyn Error>> {
+        if pixels.len() != width as usize * height as usize {
+            Err("Pixel count does not match the image size")?;
+        }
+
+        Ok(Self { width, height, pixels })
+    }
+
+    /// Decodes an image, the format is detected from its signature
+    ///
+    /// Supported formats are binary PPM and PGM, BMP, and PNG (with the `png` feature).
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The content of the image file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the format is not supported or the image is malformed.
+    pub fn decode(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        match data {
+            [b'P', b'5' | b'6', ..] => ppm::read_pnm(data),
+            [b'B', b'M', ..] => bmp::read_bmp(data),
+            #[cfg(feature = "png")]
+            [0x89, b'P', b'N', b'G', ..] => png::read_png(data),
+            #[cfg(not(feature = "png"))]
+            [0x89, b'P', b'N', b'G', ..] => Err("PNG images need the `png` feature")?,
+            _ => Err("Unsupported image format")?,
+        }
+    }
+
+    /// Reads and decodes an image file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the image file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or decoded.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    /// Returns the width of the image
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height of the image
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the RGBA pixels of the image (row major)
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    /// Returns the image resized to the given size (nearest neighbour)
+    pub fn scale(&self, width: u16, height: u16) -> Self {
+        if (width, height) == (self.width, self.height) {
+            return self.clone();
+        }
+        if self.pixels.is_empty() {
+            // Nothing to sample, the image is transparent
+            return Self { width, height, pixels: vec![[0; 4]; width as usize * height as usize] };
+        }
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height as usize {
+            // Sample at the center of the destination pixel
+            let source_y = (2 * y + 1) * self.height as usize / (2 * height as usize);
+            let row = &self.pixels[source_y * self.width as usize..][..self.width as usize];
+            for x in 0..width as usize {
+                pixels.push(row[(2 * x + 1) * self.width as usize / (2 * width as usize)]);
+            }
+        }
+
+        Self { width, height, pixels }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_signature() {
+        assert!(Bitmap::decode(b"P6\n1 1\n255\n\x01\x02\x03").is_ok());
+        assert!(Bitmap::decode(b"GIF89a").is_err());
+        assert!(Bitmap::decode(b"").is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let bitmap = Bitmap::new(2, 1, vec![[1, 1, 1, 255], [2, 2, 2, 255]]).unwrap();
+
+        let scaled = bitmap.scale(4, 2);
+        assert_eq!(scaled.pixels().len(), 8);
+        assert_eq!(scaled.pixels()[1], [1, 1, 1, 255]);
+        assert_eq!(scaled.pixels()[6], [2, 2, 2, 255]);
+
+        assert_eq!(bitmap.scale(1, 1).pixels(), &[[2, 2, 2, 255]]);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        assert!(Bitmap::new(2, 2, vec![[0; 4]]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! PNG codec (behind the `png` feature)
+//!
+//! Writes RGBA images, the shape mask is stored in the alpha channel.
+//! Reads every PNG color type (palettes, gray and 16 bits images are converted to 8 bits RGBA).
+
+use std::{error::Error, io::Write};
+
+use super::Bitmap;
+
+/// Writes an RGBA image as a PNG
+///
+/// # Arguments
+///
+/// * `writer` - The destination
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `pixels` - The RGBA pixels (row major)
+///
+/// # Errors
+///
+/// Returns an error if the pixel count does not match the size, or if the encoding fails.
+pub fn write_png<W: Write>(writer: W, width: u16, height: u16, pixels: &[[u8; 4]]) -> Result<(), Box<dyn Error>> {
+    if pixels.len() != width as usize * height as usize {
+        Err("Pixel count does not match the image size")?;
+    }
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = pixels.iter().flatten().copied().collect();
+    encoder.write_header()?.write_image_data(&data)?;
+
+    Ok(())
+}
+
+/// Reads a PNG image
+///
+/// Only the first frame of animated images is read.
+///
+/// # Arguments
+///
+/// * `data` - The content of the image file
+///
+/// # Returns
+///
+/// The decoded image
+///
+/// # Errors
+///
+/// Returns an error if the decoding fails or the image is too large.
+pub fn read_png(data: &[u8]) -> Result<Bitmap, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let width = u16::try_from(info.width).map_err(|_| "Image is too wide")?;
+    let height = u16::try_from(info.height).map_err(|_| "Image is too high")?;
+
+    let samples = &buffer[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => samples.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+        png::ColorType::Rgb => samples.chunks(3).map(|p| [p[0], p[1], p[2], 0xFF]).collect(),
+        png::ColorType::GrayscaleAlpha => samples.chunks(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => samples.iter().map(|g| [*g, *g, *g, 0xFF]).collect(),
+        png::ColorType::Indexed => Err("Palette was not expanded")?,
+    };
+
+    Bitmap::new(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_png() {
+        let mut data = Vec::new();
+        write_png(&mut data, 2, 1, &[[1, 2, 3, 4], [5, 6, 7, 8]]).unwrap();
+
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_read_png() {
+        let pixels = [[1, 2, 3, 4], [5, 6, 7, 8]];
+        let mut data = Vec::new();
+        write_png(&mut data, 2, 1, &pixels).unwrap();
+
+        let bitmap = read_png(&data).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 1));
+        assert_eq!(bitmap.pixels(), &pixels);
+
+        assert!(read_png(b"\x89PNG\r\n\x1a\n").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Netpbm codec
+//!
+//! Writes color images as binary PPM (P6) and masks as binary PBM (P4).
+//! Both formats are trivial to produce and readable by most image tools.
+//!
+//! Reads binary PPM (P6) and PGM (P5) images, with 8 or 16 bits per channel.
+
+use std::{error::Error, io::Write};
+
+use super::Bitmap;
+
+/// Writes an RGBA image as a binary PPM (the alpha channel is dropped)
+///
+/// # Arguments
+///
+/// * `writer` - The destination
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `pixels` - The RGBA pixels (row major)
+///
+/// # Errors
+///
+/// Returns an error if the pixel count does not match the size, or if the writer fails.
+pub fn write_ppm<W: Write>(mut writer: W, width: u16, height: u16, pixels: &[[u8; 4]]) -> Result<(), Box<dyn Error>> {
+    if pixels.len() != width as usize * height as usize {
+        Err("Pixel count does not match the image size")?;
+    }
+
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+
+    let data: Vec<u8> = pixels.iter().flat_map(|[r, g, b, _]| [*r, *g, *b]).collect();
+    writer.write_all(&data)?;
+
+    Ok(())
+}
+
+/// Writes a mask as a binary PBM (set pixels are black, as in the PBM convention)
+///
+/// # Arguments
+///
+/// * `writer` - The destination
+/// * `width` - The width of the mask
+/// * `height` - The height of the mask
+/// * `mask` - The mask bits (row major)
+///
+/// # Errors
+///
+/// Returns an error if the bit count does not match the size, or if the writer fails.
+pub fn write_pbm<W: Write>(mut writer: W, width: u16, height: u16, mask: &[bool]) -> Result<(), Box<dyn Error>> {
+    if mask.len() != width as usize * height as usize {
+        Err("Bit count does not match the mask size")?;
+    }
+
+    write!(writer, "P4\n{} {}\n", width, height)?;
+
+    // Rows are padded to a full byte, most significant bit first
+    let stride = (width as usize).div_ceil(8);
+    let mut data = vec![0u8; stride * height as usize];
+    for (index, _) in mask.iter().enumerate().filter(|(_, bit)| **bit) {
+        let (x, y) = (index % width as usize, index / width as usize);
+        data[y * stride + x / 8] |= 0x80 >> (x % 8);
+    }
+    writer.write_all(&data)?;
+
+    Ok(())
+}
+
+/// Reads a binary PPM (P6) or PGM (P5) image
+///
+/// # Arguments
+///
+/// * `data` - The content of the image file
+///
+/// # Returns
+///
+/// The decoded image (fully opaque)
+///
+/// # Errors
+///
+/// Returns an error if the header is malformed or the data is too short.
+pub fn read_pnm(data: &[u8]) -> Result<Bitmap, Box<dyn Error>> {
+    let channels = match data.get(..2) {
+        Some(b"P6") => 3,
+        Some(b"P5") => 1,
+        _ => Err("Not a binary PPM or PGM image")?,
+    };
+
+    let mut index = 2;
+    let width = header_value(data, &mut index)?;
+    let height = header_value(data, &mut index)?;
+    let max = header_value(data, &mut index)?;
+
+    let width = u16::try_from(width).map_err(|_| "Image is too wide")?;
+    let height = u16::try_from(height).map_err(|_| "Image is too high")?;
+    if max == 0 || max > u16::MAX as u32 {
+        Err("Invalid maximum value")?;
+    }
+
+    // A single whitespace separates the header from the data
+    index += 1;
+
+    let bytes = if max > 0xFF { 2 } else { 1 };
+    let count = width as usize * height as usize;
+    let samples = data.get(index..).unwrap_or_default();
+    if samples.len() < count * channels * bytes {
+        Err("Image data is too short")?;
+    }
+
+    // Samples are big endian, scaled from `0..=max` to `0..=255`
+    let sample = |offset: usize| {
+        let value = match bytes {
+            2 => u16::from_be_bytes([samples[offset * 2], samples[offset * 2 + 1]]) as u32,
+            _ => samples[offset] as u32,
+        };
+        ((value.min(max) * 255 + max / 2) / max) as u8
+    };
+
+    let pixels = (0..count)
+        .map(|pixel| match channels {
+            3 => [sample(pixel * 3), sample(pixel * 3 + 1), sample(pixel * 3 + 2), 0xFF],
+            _ => {
+                let gray = sample(pixel);
+                [gray, gray, gray, 0xFF]
+            }
+        })
+        .collect();
+
+    Bitmap::new(width, height, pixels)
+}
+
+/// Reads a decimal value of a Netpbm header (whitespaces and comments are skipped)
+fn header_value(data: &[u8], index: &mut usize) -> Result<u32, Box<dyn Error>> {
+    loop {
+        match data.get(*index) {
+            Some(b'#') => {
+                // Comments run until the end of the line
+                while !matches!(data.get(*index), Some(b'\n') | None) {
+                    *index += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => *index += 1,
+            _ => break,
+        }
+    }
+
+    let start = *index;
+    while matches!(data.get(*index), Some(b'0'..=b'9')) {
+        *index += 1;
+    }
+    if start == *index {
+        Err("Malformed Netpbm header")?;
+    }
+
+    Ok(std::str::from_utf8(&data[start..*index])?.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_ppm() {
+        let mut data = Vec::new();
+        write_ppm(&mut data, 2, 1, &[[1, 2, 3, 4], [5, 6, 7, 8]]).unwrap();
+
+        assert_eq!(data, b"P6\n2 1\n255\n\x01\x02\x03\x05\x06\x07");
+    }
+
+    #[test]
+    fn test_write_pbm() {
+        let mut data = Vec::new();
+        let mut mask = vec![false; 10 * 2];
+        mask[0] = true;
+        mask[9] = true;
+        mask[10 + 8] = true;
+        write_pbm(&mut data, 10, 2, &mask).unwrap();
+
+        assert_eq!(data, b"P4\n10 2\n\x80\x40\x00\x80");
+    }
+
+    #[test]
+    fn test_read_ppm() {
+        let mut data = Vec::new();
+        write_ppm(&mut data, 2, 1, &[[1, 2, 3, 4], [5, 6, 7, 8]]).unwrap();
+
+        let bitmap = read_pnm(&data).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 1));
+        assert_eq!(bitmap.pixels(), &[[1, 2, 3, 0xFF], [5, 6, 7, 0xFF]]);
+    }
+
+    #[test]
+    fn test_read_pgm() {
+        // Comments in the header, 16 bits samples
+        let bitmap = read_pnm(b"P5 # gray\n2 1 # size\n65535\n\xFF\xFF\x80\x00").unwrap();
+        assert_eq!(bitmap.pixels(), &[[0xFF, 0xFF, 0xFF, 0xFF], [0x80, 0x80, 0x80, 0xFF]]);
+
+        assert!(read_pnm(b"P5 2 1 255\n\x00").is_err());
+        assert!(read_pnm(b"P5 2 1\n").is_err());
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        assert!(write_ppm(Vec::new(), 2, 2, &[[0; 4]]).is_err());
+        assert!(write_pbm(Vec::new(), 2, 2, &[true]).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! X11 image codec
+//!
+//! Decodes the pixel data of `GetImage` replies and encodes the pixel data of `PutImage` requests
+//! (`ZPixmap` format).
+//! The layout of the data depends on the server: bits per pixel and scanline padding come from the
+//! pixmap formats of the setup, byte and bit order from the setup as well.
+
+use std::error::Error;
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{ConnectionExt, Drawable as XDrawable, Gcontext, ImageFormat, ImageOrder, Setup},
+};
+
+use crate::drawable::Drawable;
+
+/// Describes how the pixels of an image are laid out in memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelFormat {
+    /// Number of bits used by a pixel
+    pub bits_per_pixel: u8,
+    /// Rows are padded to a multiple of this number of bits
+    pub scanline_pad: u8,
+    /// Order of the bytes of a pixel
+    pub byte_order: ImageOrder,
+    /// Order of the bits in a byte (1 bit per pixel only)
+    pub bit_order: ImageOrder,
+}
+
+impl PixelFormat {
+    /// Returns the pixel format used by the server for the given depth
+    ///
+    /// # Arguments
+    ///
+    /// * `setup` - The setup of the X11 connection
+    /// * `depth` - The depth of the image
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server does not support the depth.
+    pub fn from_setup(setup: &Setup, depth: u8) -> Result<Self, Box<dyn Error>> {
+        let format = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == depth)
+            .ok_or("Unsupported depth")?;
+
+        Ok(Self {
+            bits_per_pixel: format.bits_per_pixel,
+            scanline_pad: format.scanline_pad,
+            byte_order: setup.image_byte_order,
+            bit_order: setup.bitmap_format_bit_order,
+        })
+    }
+
+    /// Returns the number of bytes of a row of the given width
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scanline pad is not a multiple of 8 bits (or is zero).
+    pub fn stride(&self, width: u16) -> Result<usize, Box<dyn Error>> {
+        let pad = self.scanline_pad as usize;
+        if pad == 0 || !pad.is_multiple_of(8) {
+            Err("Unsupported scanline pad")?;
+        }
+        let bits = width as usize * self.bits_per_pixel as usize;
+        Ok(bits.div_ceil(pad) * pad / 8)
+    }
+
+    /// Decodes the pixel values of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The image data
+    /// * `width` - The width of the image
+    /// * `height` - The height of the image
+    ///
+    /// # Returns
+    ///
+    /// The raw pixel values (row major)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is too short or the bits per pixel or the scanline pad are not supported.
+    pub fn decode(&self, data: &[u8], width: u16, height: u16) -> Result<Vec<u32>, Box<dyn Error>> {
+        let stride = self.stride(width)?;
+        if data.len() < stride * height as usize {
+            Err("Image data is too short")?;
+        }
+
+        let bytes = match self.bits_per_pixel {
+            1 => 0,
+            8 => 1,
+            16 => 2,
+            24 => 3,
+            32 => 4,
+            _ => Err("Unsupported bits per pixel")?,
+        };
+
+        if width == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for row in data.chunks(stride).take(height as usize) {
+            for x in 0..width as usize {
+                let value = if bytes == 0 {
+                    let shift = match self.bit_order {
+                        ImageOrder::LSB_FIRST => x % 8,
+                        _ => 7 - x % 8,
+                    };
+                    ((row[x / 8] >> shift) & 1) as u32
+                } else {
+                    let pixel = &row[x * bytes..(x + 1) * bytes];
+                    let fold = |value: u32, byte: &u8| (value << 8) | *byte as u32;
+                    match self.byte_order {
+                        ImageOrder::LSB_FIRST => pixel.iter().rev().fold(0, fold),
+                        _ => pixel.iter().fold(0, fold),
+                    }
+                };
+                pixels.push(value);
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Encodes pixel values (the inverse of `decode`)
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - The raw pixel values (row major)
+    /// * `width` - The width of the image
+    /// * `height` - The height of the image
+    ///
+    /// # Returns
+    ///
+    /// The image data, rows padded to the scanline pad
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixel count does not match the size or the bits per pixel or the scanline pad are not supported.
+    pub fn encode(&self, pixels: &[u32], width: u16, height: u16) -> Result<Vec<u8>, Box<dyn Error>> {
+        if pixels.len() != width as usize * height as usize {
+            Err("Pixel count does not match the image size")?;
+        }
+
+        let bytes = match self.bits_per_pixel {
+            1 => 0,
+            8 => 1,
+            16 => 2,
+            24 => 3,
+            32 => 4,
+            _ => Err("Unsupported bits per pixel")?,
+        };
+
+        let stride = self.stride(width)?;
+        let mut data = vec![0u8; stride * height as usize];
+        if width == 0 {
+            return Ok(data);
+        }
+
+        for (row, values) in data.chunks_mut(stride).zip(pixels.chunks(width as usize)) {
+            for (x, value) in values.iter().enumerate() {
+                if bytes == 0 {
+                    let shift = match self.bit_order {
+                        ImageOrder::LSB_FIRST => x % 8,
+                        _ => 7 - x % 8,
+                    };
+                    row[x / 8] |= ((value & 1) as u8) << shift;
+                } else {
+                    let pixel = &mut row[x * bytes..(x + 1) * bytes];
+                    for (index, byte) in pixel.iter_mut().enumerate() {
+                        let shift = match self.byte_order {
+                            ImageOrder::LSB_FIRST => index,
+                            _ => bytes - 1 - index,
+                        };
+                        *byte = (value >> (shift * 8)) as u8;
+                    }
+                }
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+/// Fetches the pixel values of a drawable
+///
+/// # Arguments
+///
+/// * `conn` - The X11 connection
+/// * `drawable` - The drawable to read (usually a pixmap)
+///
+/// # Returns
+///
+/// The raw pixel values (row major), at the depth of the drawable
+///
+/// # Errors
+///
+/// Returns an error if the image could not be fetched or decoded.
+pub fn get_image<C: Connection>(conn: &C, drawable: &dyn Drawable) -> Result<Vec<u32>, Box<dyn Error>> {
+    get_area(conn, drawable.id(), (0, 0), drawable.width(), drawable.height())
+}
+
+/// Fetches the pixel values of an area of a drawable
+///
+/// # Arguments
+///
+/// * `conn` - The X11 connection
+/// * `drawable` - The drawable to read
+/// * `origin` - The top left corner of the area (inside of the drawable)
+/// * `width` - The width of the area
+/// * `height` - The height of the area
+///
+/// # Returns
+///
+/// The raw pixel values (row major), at the depth of the drawable
+///
+/// # Errors
+///
+/// Returns an error if the area is outside of the drawable or the image could not be decoded.
+pub fn get_area<C: Connection>(
+    conn: &C,
+    drawable: XDrawable,
+    origin: (i16, i16),
+    width: u16,
+    height: u16,
+) -> Result<Vec<u32>, Box<dyn Error>> {
+    let reply = conn
+        .get_image(ImageFormat::Z_PIXMAP, drawable, origin.0, origin.1, width, height, !0)?
+        .reply()?;
+
+    let format = PixelFormat::from_setup(conn.setup(), reply.depth)?;
+    format.decode(&reply.data, width, height)
+}
+
+/// Uploads pixel values to a drawable (split in several requests if needed)
+///
+/// # Arguments
+///
+/// * `conn` - The X11 connection
+/// * `drawable` - The destination drawable
+/// * `gc` - The graphics context used for the upload
+/// * `depth` - The depth of the drawable
+/// * `origin` - The destination of the top left corner of the pixels
+/// * `width` - The width of the pixel block
+/// * `values` - The raw pixel values at the depth of the drawable (row major)
+///
+/// # Errors
+///
+/// Returns an error if the server does not support the depth or the requests could not be sent.
+pub fn put_image<C: Connection>(
+    conn: &C,
+    drawable: XDrawable,
+    gc: Gcontext,
+    depth: u8,
+    origin: (i16, i16),
+    width: u16,
+    values: &[u32],
+) -> Result<(), Box<dyn Error>> {
+    if width == 0 {
+        return Ok(());
+    }
+    let format = PixelFormat::from_setup(conn.setup(), depth)?;
+
+    // Keep some room for the request header (the height of a request is 16 bits)
+    let stride = format.stride(width)?.max(1);
+    let rows = (conn.maximum_request_bytes().saturating_sub(64) / stride).clamp(1, u16::MAX as usize);
+
+    for (index, block) in values.chunks(width as usize * rows).enumerate() {
+        // The rows past the coordinate range are outside of every drawable
+        let Some(y) = row_offset(origin.1, index * rows) else {
+            break;
+        };
+        let height = (block.len() / width as usize) as u16;
+        let data = format.encode(block, width, height)?;
+        conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            drawable,
+            gc,
+            width,
+            height,
+            origin.0,
+            y,
+            0,
+            depth,
+            &data,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns the coordinate of a row below the origin (None if it does not fit in the coordinate range)
+fn row_offset(origin: i16, row: usize) -> Option<i16> {
+    i16::try_from(row).ok().and_then(|row| origin.checked_add(row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(bits_per_pixel: u8, byte_order: ImageOrder, bit_order: ImageOrder) -> PixelFormat {
+        PixelFormat {
+            bits_per_pixel,
+            scanline_pad: 32,
+            byte_order,
+            bit_order,
+        }
+    }
+
+    #[test]
+    fn test_stride() {
+        let format = format(1, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST);
+        assert_eq!(format.stride(1).unwrap(), 4);
+        assert_eq!(format.stride(33).unwrap(), 8);
+        assert_eq!(PixelFormat { bits_per_pixel: 24, scanline_pad: 8, ..format }.stride(3).unwrap(), 9);
+
+        // The scanline pad is a number of bytes
+        let zero = PixelFormat { scanline_pad: 0, ..format };
+        assert!(zero.stride(1).is_err());
+        assert!(zero.decode(&[0; 4], 1, 1).is_err());
+        assert!(zero.encode(&[0], 1, 1).is_err());
+        assert!(PixelFormat { scanline_pad: 12, ..format }.stride(1).is_err());
+    }
+
+    #[test]
+    fn test_row_offset() {
+        assert_eq!(row_offset(10, 20), Some(30));
+        assert_eq!(row_offset(-100, 100), Some(0));
+        assert_eq!(row_offset(i16::MAX - 1, 1), Some(i16::MAX));
+        assert_eq!(row_offset(i16::MAX, 1), None);
+        assert_eq!(row_offset(0, 40_000), None);
+    }
+
+    #[test]
+    fn test_decode_32() {
+        let data = [0x2A, 0x2A, 0xA5, 0xFF, 0x01, 0x02, 0x03, 0x04];
+
+        let lsb = format(32, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST);
+        assert_eq!(lsb.decode(&data, 2, 1).unwrap(), vec![0xFFA52A2A, 0x04030201]);
+
+        let msb = format(32, ImageOrder::MSB_FIRST, ImageOrder::LSB_FIRST);
+        assert_eq!(msb.decode(&data, 2, 1).unwrap(), vec![0x2A2AA5FF, 0x01020304]);
+    }
+
+    #[test]
+    fn test_decode_1() {
+        // Two rows of 4 bytes (padded to 32 bits)
+        let data = [0b0000_0101, 0, 0, 0, 0b1000_0000, 0, 0, 0];
+
+        let lsb = format(1, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST);
+        assert_eq!(lsb.decode(&data, 3, 2).unwrap(), vec![1, 0, 1, 0, 0, 0]);
+
+        let msb = format(1, ImageOrder::LSB_FIRST, ImageOrder::MSB_FIRST);
+        assert_eq!(msb.decode(&data, 3, 2).unwrap(), vec![0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_encode() {
+        let pixels = [0xFFA52A2A, 0x04030201];
+        for format in [
+            format(32, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST),
+            format(32, ImageOrder::MSB_FIRST, ImageOrder::LSB_FIRST),
+            PixelFormat { scanline_pad: 8, ..format(24, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST) },
+        ] {
+            let data = format.encode(&pixels, 2, 1).unwrap();
+            let expected: Vec<u32> = pixels.iter().map(|pixel| pixel & (u32::MAX >> (32 - format.bits_per_pixel))).collect();
+            assert_eq!(format.decode(&data, 2, 1).unwrap(), expected);
+        }
+
+        let msb = format(1, ImageOrder::LSB_FIRST, ImageOrder::MSB_FIRST);
+        assert_eq!(msb.encode(&[0, 0, 0, 1, 0, 0], 3, 2).unwrap(), [0, 0, 0, 0, 0b1000_0000, 0, 0, 0]);
+        assert!(msb.encode(&[0], 3, 2).is_err());
+    }
+
+    #[test]
+    fn test_decode_too_short() {
+        let format = format(32, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST);
+        assert!(format.decode(&[0; 4], 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_decode_empty() {
+        let format = format(32, ImageOrder::LSB_FIRST, ImageOrder::LSB_FIRST);
+        assert_eq!(format.decode(&[], 0, 3).unwrap(), Vec::<u32>::new());
+        assert_eq!(format.decode(&[], 2, 0).unwrap(), Vec::<u32>::new());
+    }
+}
@@ -3,7 +3,7 @@
 //! This module is used to define the color for the shapes.
 //! It defines the Color enum which contains the predefined colors and the RGB and RGBA colors.
 //! 
-//! - RGBA may not be handled correctly by the X11 server (Depends off the presence of the Composite extension).
+//! - RGBA colors are only translucent on an ARGB window (requires a compositing manager), otherwise the alpha channel is ignored.
 //! The transparent color will use Shape extension to fake transparency.
 //! 
 //! 
@@ -277,6 +277,8 @@ impl Color {
     /// Get the value of the color at the specified depth
     /// 
     /// This method will return the value of the color at the specified depth.
+    /// At depth 32 (ARGB visual), the color channels are premultiplied by the alpha channel.
+    /// At the other depths, `Color::RGB` and `Color::RGBA` are the raw packed channels.
     /// 
     /// # Arguments
     /// 
@@ -301,10 +303,19 @@ impl Color {
             Color::PINK => for_depth(0xFFC0CB, depth),
             Color::PURPLE => for_depth(0x800080, depth),
             Color::RED => for_depth(0xFF0000, depth),
-            Color::RGB(r, g, b) => ((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32),
-            Color::RGBA(r, g, b, a) => {
-                ((*a as u32) << 24) | ((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32)
-            }
+            Color::RGB(r, g, b) => match depth {
+                // ARGB visuals need an opaque alpha channel
+                Depth::D32 => for_depth(((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32), depth),
+                _ => ((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32),
+            },
+            Color::RGBA(r, g, b, a) => match depth {
+                // ARGB visuals expect premultiplied colors
+                Depth::D32 => {
+                    let premultiply = |c: u8| (c as u32 * *a as u32 + 127) / 255;
+                    ((*a as u32) << 24) | (premultiply(*r) << 16) | (premultiply(*g) << 8) | premultiply(*b)
+                }
+                _ => ((*a as u32) << 24) | ((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32),
+            },
             Color::SILVER => for_depth(0xC0C0C0, depth),
             Color::TRANSPARENT => 0,
             Color::WHITE => for_depth(0xFFFFFF, depth),
@@ -332,11 +343,72 @@ impl Color {
 
         Color::new_rgba(r as u8, g as u8, b as u8, alpha)
     }
+
+    /// Create a color from a pixel value of the given depth
+    /// 
+    /// This is the inverse of `value`, used to read back pixels from the X server.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `value` - The pixel value
+    /// * `depth` - The depth of the pixel value
+    /// 
+    /// # Returns
+    /// 
+    /// The function returns the color of the pixel (WHITE or BLACK at depth 1).
+    /// 
+    pub fn from_value(value: u32, depth: &Depth) -> Self {
+        match depth {
+            Depth::D32 => {
+                // Undo the premultiplication of the color
+                let a = (value >> 24) as u8;
+                let unpremultiply = |shift: u32| match a {
+                    0 => 0,
+                    a => ((((value >> shift) & 0xFF) * 255 + a as u32 / 2) / a as u32).min(0xFF) as u8,
+                };
+                Color::RGBA(unpremultiply(16), unpremultiply(8), unpremultiply(0), a)
+            }
+            Depth::D24 => Color::RGB((value >> 16) as u8, (value >> 8) as u8, value as u8),
+            Depth::D16 => {
+                // Expand the 5 bit channels to 8 bit
+                let channel = |shift: u32| {
+                    let c = ((value >> shift) & 0x1F) as u8;
+                    (c << 3) | (c >> 2)
+                };
+                Color::RGB(channel(10), channel(5), channel(0))
+            }
+            Depth::D8 => Color::RGB(value as u8, value as u8, value as u8),
+            Depth::D1 => match value & 1 {
+                0 => Color::BLACK,
+                _ => Color::WHITE,
+            },
+        }
+    }
+
+    /// Get the red, green, blue and alpha components of the color
+    /// 
+    /// Opaque colors have an alpha of 0xFF, the transparent color is all zeros.
+    /// 
+    /// # Returns
+    /// 
+    /// The function returns the `[r, g, b, a]` components of the color.
+    /// 
+    pub fn rgba(&self) -> [u8; 4] {
+        match self {
+            Color::RGBA(r, g, b, a) => [*r, *g, *b, *a],
+            Color::TRANSPARENT => [0, 0, 0, 0],
+            color => {
+                let raw = color.value(&Depth::D24);
+                [(raw >> 16) as u8, (raw >> 8) as u8, raw as u8, 0xFF]
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     #[cfg(test)]
+    #[allow(clippy::module_inception)]
     mod tests {
         use super::super::*;
 
@@ -415,6 +487,57 @@ mod tests {
             assert_eq!(Color::BROWN.value(&Depth::D16), 0x50A5);
             assert_eq!(Color::CYAN.value(&Depth::D8), 0xAA);
             assert_eq!(Color::GRAY.value(&Depth::D1), 0x1);
+            assert_eq!(Color::RGB(0xA5, 0x2A, 0x2A).value(&Depth::D32), 0xFFA52A2A);
+            assert_eq!(Color::RGBA(0xFF, 0x80, 0x00, 0x80).value(&Depth::D32), 0x80804000);
+
+            // Only the ARGB visuals change the raw values
+            assert_eq!(Color::RGB(0xA5, 0x2A, 0x2A).value(&Depth::D16), 0xA52A2A);
+            assert_eq!(Color::RGB(0xA5, 0x2A, 0x2A).value(&Depth::D1), 0xA52A2A);
+            assert_eq!(Color::RGBA(0xFF, 0x80, 0x00, 0x80).value(&Depth::D24), 0x80FF8000);
+        }
+
+        #[test]
+        fn test_color_from_value() {
+            assert_eq!(Color::from_value(0xFFA52A2A, &Depth::D32), Color::RGBA(0xA5, 0x2A, 0x2A, 0xFF));
+            assert_eq!(Color::from_value(0x80804000, &Depth::D32), Color::RGBA(0xFF, 0x80, 0x00, 0x80));
+            assert_eq!(Color::from_value(0, &Depth::D32), Color::RGBA(0, 0, 0, 0));
+            assert_eq!(Color::from_value(0xA52A2A, &Depth::D24), Color::RGB(0xA5, 0x2A, 0x2A));
+            assert_eq!(Color::from_value(0x7FFF, &Depth::D16), Color::RGB(0xFF, 0xFF, 0xFF));
+            assert_eq!(Color::from_value(0x80, &Depth::D8), Color::RGB(0x80, 0x80, 0x80));
+            assert_eq!(Color::from_value(1, &Depth::D1), Color::WHITE);
+            assert_eq!(Color::from_value(0, &Depth::D1), Color::BLACK);
+        }
+
+        #[test]
+        fn test_premultiply_round_trip() {
+            for c in 0..=0xFF {
+                // Opaque colors are not changed
+                let color = Color::RGBA(c, 0xFF - c, c / 2, 0xFF);
+                assert_eq!(Color::from_value(color.value(&Depth::D32), &Depth::D32), color);
+
+                // Translucent colors lose the precision of their alpha channel
+                for a in [0x80, 0x40] {
+                    let Color::RGBA(r, g, b, alpha) = Color::from_value(Color::RGBA(c, 0xFF - c, 0, a).value(&Depth::D32), &Depth::D32) else {
+                        panic!("Not an RGBA color");
+                    };
+                    let max_error = (0xFF / a as i32 + 1) / 2;
+                    assert_eq!(alpha, a);
+                    assert!((r as i32 - c as i32).abs() <= max_error);
+                    assert!((g as i32 - (0xFF - c) as i32).abs() <= max_error);
+                    assert_eq!(b, 0);
+                }
+            }
+
+            // The channels of transparent colors are lost
+            assert_eq!(Color::from_value(Color::RGBA(0xFF, 0x80, 0x00, 0).value(&Depth::D32), &Depth::D32), Color::RGBA(0, 0, 0, 0));
+        }
+
+        #[test]
+        fn test_color_rgba() {
+            assert_eq!(Color::BROWN.rgba(), [0xA5, 0x2A, 0x2A, 0xFF]);
+            assert_eq!(Color::RGB(1, 2, 3).rgba(), [1, 2, 3, 0xFF]);
+            assert_eq!(Color::RGBA(1, 2, 3, 4).rgba(), [1, 2, 3, 4]);
+            assert_eq!(Color::TRANSPARENT.rgba(), [0, 0, 0, 0]);
         }
 
         #[test]
@@ -0,0 +1,153 @@
+//! Damage tracking module
+//!
+//! This module is used to accumulate the regions of the overlay that need to be redrawn.
+//!
+//! Every time a shape changes, both its previous and its new bounding box are added to the damage.
+//! Overlapping regions are merged together so the overlay only repaints (and re-shapes)
+//! the union of the damaged regions.
+
+use crate::math::rect::Rect;
+
+/// Maximum number of disjoint regions kept before collapsing them into a single one.
+///
+/// Past this limit, sending more clip rectangles to the server costs more than it saves.
+const MAX_REGIONS: usize = 16;
+
+/// Represents the damaged regions of a drawable
+#[derive(Debug, Clone, Default)]
+pub struct Damage {
+    /// Disjoint damaged regions (in pixels)
+    rects: Vec<Rect>,
+}
+
+impl Damage {
+    /// Creates a new empty damage
+    pub fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    /// Adds a region to the damage
+    ///
+    /// The region is merged with every region it overlaps.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The damaged region
+    ///
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+
+        let mut merged = rect;
+        // Merge until no region overlaps the new one anymore
+        loop {
+            let before = self.rects.len();
+            self.rects.retain(|other| {
+                if other.intersects(&merged) {
+                    merged = merged.union(other);
+                    false
+                } else {
+                    true
+                }
+            });
+            if self.rects.len() == before {
+                break;
+            }
+        }
+        self.rects.push(merged);
+
+        if self.rects.len() > MAX_REGIONS {
+            let bounds = self.bounds();
+            self.rects.clear();
+            self.rects.push(bounds);
+        }
+    }
+
+    /// Restricts the damage to the given area (usually the drawable area)
+    pub fn clip(&mut self, area: &Rect) {
+        self.rects = self
+            .rects
+            .iter()
+            .filter_map(|rect| rect.intersection(area))
+            .collect();
+    }
+
+    /// Returns true if nothing needs to be redrawn
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Returns true if the given region overlaps the damage
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        self.rects.iter().any(|damaged| damaged.intersects(rect))
+    }
+
+    /// Returns the damaged regions
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    /// Returns the smallest rectangle containing every damaged region
+    pub fn bounds(&self) -> Rect {
+        self.rects
+            .iter()
+            .fold(Rect::default(), |acc, rect| acc.union(rect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_disjoint() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(20, 20, 10, 10));
+
+        assert_eq!(damage.rects().len(), 2);
+        assert_eq!(damage.bounds(), Rect::new(0, 0, 30, 30));
+    }
+
+    #[test]
+    fn test_add_overlapping() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(20, 0, 10, 10));
+        // Bridges both regions
+        damage.add(Rect::new(5, 0, 20, 5));
+
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 30, 10)]);
+    }
+
+    #[test]
+    fn test_add_empty() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 0, 10));
+
+        assert!(damage.is_empty());
+    }
+
+    #[test]
+    fn test_collapse() {
+        let mut damage = Damage::new();
+        for i in 0..=MAX_REGIONS as i32 {
+            damage.add(Rect::new(i * 10, 0, 5, 5));
+        }
+
+        assert_eq!(damage.rects().len(), 1);
+        assert_eq!(damage.bounds(), Rect::new(0, 0, MAX_REGIONS as i32 * 10 + 5, 5));
+    }
+
+    #[test]
+    fn test_clip() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(-5, -5, 10, 10));
+        damage.add(Rect::new(100, 100, 10, 10));
+        damage.clip(&Rect::new(0, 0, 50, 50));
+
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 5, 5)]);
+        assert!(damage.intersects(&Rect::new(2, 2, 1, 1)));
+    }
+}
@@ -0,0 +1,109 @@
+//! Back buffer of the overlay.
+//!
+//! The `BackBuffer` owns the off-screen resources used to render the overlay:
+//! - a color pixmap (same depth as the window) holding the rendered shapes,
+//! - a 1-bit mask pixmap holding the window shape,
+//! - a graphics context for each of them.
+//!
+//! Those resources are kept across frames and only reallocated when the window is resized.
+//! They are freed when the back buffer is dropped.
+
+use std::{error::Error, rc::Rc};
+
+use x11rb::connection::Connection;
+
+use super::{pixmap::Pixmap, window::Window, Drawable};
+
+use crate::{color::Depth, font::FontSet, math::vec::Vec2, shape::GcontextWrapperExt, Color};
+
+/// Represents the off-screen buffers of the overlay.
+pub struct BackBuffer<C>
+where
+    C: Connection,
+{
+    /// The X11 connection (used to free the pixmaps).
+    conn: Rc<C>,
+    /// The color pixmap.
+    color: Pixmap,
+    /// The 1-bit shape mask pixmap.
+    mask: Pixmap,
+    /// The graphics context used to draw on the color pixmap.
+    gc: GcontextWrapperExt<C>,
+    /// The graphics context used to draw on the mask pixmap.
+    mask_gc: GcontextWrapperExt<C>,
+}
+
+impl<C> BackBuffer<C>
+where
+    C: Connection,
+{
+    /// Creates the back buffer of a window.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `window` - The window that the buffers are associated with (size and depth).
+    /// * `font` - The fonts used by the graphics contexts.
+    ///
+    /// # Returns
+    ///
+    /// A new `BackBuffer` object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixmaps or the graphics contexts could not be created.
+    ///
+    pub fn new(conn: &Rc<C>, window: &Window, font: Option<FontSet>) -> Result<Self, Box<dyn Error>> {
+        let color = Pixmap::new(conn.as_ref(), window, None)?;
+        let mask = Pixmap::new(conn.as_ref(), window, Some(Depth::D1))?;
+
+        let gc = GcontextWrapperExt::init(conn, color.id(), None, None, font.clone())?;
+        let mask_gc = GcontextWrapperExt::init(
+            conn,
+            mask.id(),
+            Some(Color::BLACK.value(&mask.depth())),
+            None,
+            font,
+        )?;
+
+        Ok(Self {
+            conn: conn.clone(),
+            color,
+            mask,
+            gc,
+            mask_gc,
+        })
+    }
+
+    /// Returns the size of the buffers.
+    pub fn size(&self) -> Vec2<u16> {
+        self.color.size()
+    }
+
+    /// Returns the color pixmap and the mask pixmap.
+    pub fn pixmaps(&self) -> (&Pixmap, &Pixmap) {
+        (&self.color, &self.mask)
+    }
+
+    /// Returns the color pixmap and its graphics context.
+    pub fn color(&mut self) -> (&Pixmap, &mut GcontextWrapperExt<C>) {
+        (&self.color, &mut self.gc)
+    }
+
+    /// Returns the mask pixmap and its graphics context.
+    pub fn mask(&mut self) -> (&Pixmap, &mut GcontextWrapperExt<C>) {
+        (&self.mask, &mut self.mask_gc)
+    }
+}
+
+impl<C> Drop for BackBuffer<C>
+where
+    C: Connection,
+{
+    /// Frees the pixmaps (the graphics contexts free themselves).
+    fn drop(&mut self) {
+        // Errors are ignored, the connection may already be closed
+        let _ = self.color.free(self.conn.as_ref());
+        let _ = self.mask.free(self.conn.as_ref());
+    }
+}
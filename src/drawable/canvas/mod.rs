@@ -0,0 +1,831 @@
+//! Software canvas module
+//!
+//! The `Canvas` is an in-memory drawing surface that mimics what the overlay draws on the X server:
+//! - an RGBA color image (the content of the back buffer),
+//! - a 1-bit mask (the shape of the window).
+//!
+//! It does not need an X server, so it can be used to test the layout of an overlay
+//! or to take snapshots of it.
+//!
+//! The colors follow the rules of the overlay: transparent parts keep the previous color and are
+//! removed from the mask, black parts are drawn but removed from the mask (black is the "off" value
+//! of the 1-bit mask).
+//!
+//! Text is drawn with a built-in 5x7 bitmap font, its size may differ from the X font used on screen.
+
+use std::{cell::RefCell, error::Error, fs::File, io::{BufWriter, Write}, path::Path, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Arc as XArc, Rectangle as XRectangle},
+};
+
+use crate::{
+    codec::{ppm, Bitmap},
+    color::Depth,
+    math::{rect::Rect, vec::{Vec2, Vec2f}},
+    shape::{Fill, FillRule, LineStyle, Primitive, Shape},
+    Color,
+};
+
+pub mod glyphs;
+
+/// Represents an in-memory drawing surface (color image and shape mask)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    /// The size of the canvas (in pixels)
+    size: Vec2<u16>,
+    /// The RGBA pixels (row major)
+    pixels: Vec<[u8; 4]>,
+    /// The shape mask (row major, true if the pixel is part of the window shape)
+    mask: Vec<bool>,
+}
+
+impl Canvas {
+    /// Creates a new canvas
+    ///
+    /// Every pixel is transparent and outside of the mask.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the canvas (in pixels)
+    ///
+    pub fn new(size: Vec2<u16>) -> Self {
+        let len = size.x as usize * size.y as usize;
+        Self {
+            size,
+            pixels: vec![[0; 4]; len],
+            mask: vec![false; len],
+        }
+    }
+
+    /// Creates a canvas from its pixels and mask
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixel or bit count does not match the size.
+    pub(crate) fn from_parts(size: Vec2<u16>, pixels: Vec<[u8; 4]>, mask: Vec<bool>) -> Result<Self, Box<dyn Error>> {
+        let len = size.x as usize * size.y as usize;
+        if pixels.len() != len || mask.len() != len {
+            Err("Canvas data does not match its size")?;
+        }
+        Ok(Self { size, pixels, mask })
+    }
+
+    /// Returns the size of the canvas
+    pub fn size(&self) -> Vec2<u16> {
+        self.size
+    }
+
+    /// Returns the width of the canvas
+    pub fn width(&self) -> u16 {
+        self.size.x
+    }
+
+    /// Returns the height of the canvas
+    pub fn height(&self) -> u16 {
+        self.size.y
+    }
+
+    /// Returns the RGBA pixels of the canvas (row major)
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    /// Returns the shape mask of the canvas (row major)
+    pub fn mask(&self) -> &[bool] {
+        &self.mask
+    }
+
+    /// Returns the RGBA color of a pixel (None if outside of the canvas)
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 4]> {
+        self.index(x, y).map(|index| self.pixels[index])
+    }
+
+    /// Returns true if a pixel is part of the shape mask
+    pub fn is_masked(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).map(|index| self.mask[index]).unwrap_or(false)
+    }
+
+    /// Resets every pixel to transparent
+    pub fn clear(&mut self) {
+        self.pixels.fill([0; 4]);
+        self.mask.fill(false);
+    }
+
+    /// Writes the color image as a binary PPM
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn write_ppm<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        ppm::write_ppm(writer, self.width(), self.height(), &self.pixels)
+    }
+
+    /// Writes the shape mask as a binary PBM (pixels of the mask are black)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn write_pbm<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        ppm::write_pbm(writer, self.width(), self.height(), &self.mask)
+    }
+
+    /// Saves the color image to a PPM file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be written.
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_ppm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Saves the shape mask to a PBM file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be written.
+    pub fn save_pbm<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pbm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the canvas as a PNG
+    ///
+    /// The shape mask is stored in the alpha channel (pixels outside of the mask are fully transparent).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoding fails.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let pixels: Vec<[u8; 4]> = self
+            .pixels
+            .iter()
+            .zip(self.mask.iter())
+            .map(|([r, g, b, a], masked)| [*r, *g, *b, if *masked { *a } else { 0 }])
+            .collect();
+        crate::codec::png::write_png(writer, self.width(), self.height(), &pixels)
+    }
+
+    /// Saves the canvas to a PNG file (see `write_png`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be written.
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_png(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Renders shapes on the canvas (in order)
+    ///
+    /// Hidden shapes are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `shapes` - The shapes to render
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a shape could not be rasterized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Rectangle, Shape}, x11rb::rust_connection::RustConnection, Canvas, Color};
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// let rec = Rectangle::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(0.5, 0.5), Color::RED).unwrap();
+    /// let shapes: Vec<Rc<RefCell<dyn Shape<RustConnection>>>> = vec![rec];
+    ///
+    /// let mut canvas = Canvas::new((10, 10).into());
+    /// canvas.render(&shapes).unwrap();
+    ///
+    /// assert_eq!(canvas.pixel(2, 2), Some(Color::RED.rgba()));
+    /// assert!(!canvas.is_masked(7, 7));
+    /// ```
+    pub fn render<'a, C, I>(&mut self, shapes: I) -> Result<(), Box<dyn Error>>
+    where
+        C: Connection + 'a,
+        I: IntoIterator<Item = &'a Rc<RefCell<dyn Shape<C>>>>,
+    {
+        for shape in shapes {
+            let shape = shape.borrow();
+            if shape.is_visible() {
+                shape.rasterize(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Paints a single pixel
+    ///
+    /// Pixels outside of the canvas are ignored.
+    pub fn plot(&mut self, x: i32, y: i32, color: &Color) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+
+        // Transparent parts keep the previous color (they are masked anyway)
+        if color != &Color::TRANSPARENT {
+            self.pixels[index] = color.rgba();
+        }
+        self.mask[index] = color.value(&Depth::D1) != 0;
+    }
+
+    /// Draws an image
+    ///
+    /// Pixels whose alpha is above the threshold are copied and part of the mask (even black ones),
+    /// the others are left untouched. Pixels outside of the canvas are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The left of the image
+    /// * `y` - The top of the image
+    /// * `bitmap` - The image
+    /// * `threshold` - The alpha threshold
+    pub fn draw_image(&mut self, x: i32, y: i32, bitmap: &Bitmap, threshold: u8) {
+        let width = bitmap.width() as usize;
+        for (offset, pixel) in bitmap.pixels().iter().enumerate().filter(|(_, pixel)| pixel[3] > threshold) {
+            let (px, py) = (x + (offset % width) as i32, y + (offset / width) as i32);
+            if let Some(index) = self.index(px, py) {
+                self.pixels[index] = *pixel;
+                self.mask[index] = true;
+            }
+        }
+    }
+
+    /// Paints the covered pixels with a fill
+    ///
+    /// The covered pixels are the masked pixels of the coverage canvas (the shape drawn in white).
+    /// They are painted like the overlay does: with the colors of the fill, and part of the mask
+    /// where the color of the shape is neither black nor transparent.
+    ///
+    /// # Arguments
+    ///
+    /// * `coverage` - The canvas of the covered pixels (same size as the canvas)
+    /// * `rect` - The box of the shape (in pixels, the origin of the fill)
+    /// * `fill` - The fill
+    /// * `forground` - The forground color of the shape
+    /// * `background` - The background color of the shape
+    pub fn paint(&mut self, coverage: &Canvas, rect: Rect, fill: &Fill, forground: &Color, background: &Color) {
+        for (index, _) in coverage.mask.iter().enumerate().filter(|(_, covered)| **covered) {
+            let (x, y) = ((index % coverage.size.x as usize) as i32, (index / coverage.size.x as usize) as i32);
+            let Some(target) = self.index(x, y) else {
+                continue;
+            };
+
+            if let Some((color, masked)) = fill.pixel(x - rect.x, y - rect.y, rect, forground, background) {
+                if let Some(color) = color {
+                    self.pixels[target] = color;
+                }
+                self.mask[target] = masked;
+            }
+        }
+    }
+
+    /// Fills a rectangle
+    pub fn fill_rect(&mut self, rect: Rect, color: &Color) {
+        let area = Rect::new(0, 0, self.size.x as i32, self.size.y as i32);
+        let Some(rect) = rect.intersection(&area) else {
+            return;
+        };
+
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                self.plot(x, y, color);
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle
+    ///
+    /// Like the X server, the outline covers `width + 1` by `height + 1` pixels.
+    pub fn draw_rect(&mut self, rect: Rect, color: &Color) {
+        let (left, top, right, bottom) = (rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+
+        self.draw_line(left, top, right, top, color);
+        self.draw_line(right, top, right, bottom, color);
+        self.draw_line(right, bottom, left, bottom, color);
+        self.draw_line(left, bottom, left, top, color);
+    }
+
+    /// Draws a one pixel wide line (both ends included)
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: &Color) {
+        // Bresenham algorithm
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+
+        loop {
+            self.plot(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draws a polyline with a line style
+    ///
+    /// Lines of width 0 or 1 are drawn one pixel wide, wider lines are filled with their caps and joins.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points of the polyline (in pixels)
+    /// * `style` - The width, dashes, caps and joins of the line
+    /// * `color` - The line color
+    ///
+    pub fn stroke(&mut self, points: &[Vec2f], style: &LineStyle, color: &Color) {
+        for dash in style.dash(points) {
+            if style.width <= 1 {
+                for segment in dash.windows(2) {
+                    let (start, end) = (segment[0], segment[1]);
+                    self.draw_line(
+                        start.x.round() as i32,
+                        start.y.round() as i32,
+                        end.x.round() as i32,
+                        end.y.round() as i32,
+                        color,
+                    );
+                }
+            } else {
+                for polygon in style.outline(&dash) {
+                    self.fill_polygon(&polygon, FillRule::Winding, color);
+                }
+            }
+        }
+    }
+
+    /// Fills a polygon
+    ///
+    /// Like the X server, a pixel is filled if its center is inside the polygon.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The vertices of the polygon (in pixels, the polygon is closed automatically)
+    /// * `rule` - The rule deciding which parts of a self-intersecting polygon are inside
+    /// * `color` - The fill color
+    ///
+    pub fn fill_polygon(&mut self, points: &[Vec2f], rule: FillRule, color: &Color) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let (top, bottom) = points
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(top, bottom), point| (top.min(point.y), bottom.max(point.y)));
+        let top = (top - 0.5).ceil().max(0.0) as i32;
+        let bottom = ((bottom - 0.5).ceil() as i32).min(self.size.y as i32);
+
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in top..bottom {
+            let center = y as f32 + 0.5;
+
+            // Crossings of the edges with the row, with the direction of the edge
+            crossings.clear();
+            for (index, start) in points.iter().enumerate() {
+                let end = points[(index + 1) % points.len()];
+                let direction = match (start.y <= center, end.y <= center) {
+                    (true, false) => 1,
+                    (false, true) => -1,
+                    _ => continue,
+                };
+                let x = start.x + (center - start.y) * (end.x - start.x) / (end.y - start.y);
+                crossings.push((x, direction));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if !rule.is_inside(winding) {
+                    continue;
+                }
+                let left = (pair[0].0 - 0.5).ceil() as i32;
+                let right = (pair[1].0 - 0.5).ceil() as i32;
+                for x in left.max(0)..right.min(self.size.x as i32) {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Fills an arc (pie slice) inscribed in the given rectangle
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The bounding rectangle of the full ellipse
+    /// * `start` - The start angle (degrees, counterclockwise from 3 o'clock)
+    /// * `extent` - The angular extent (degrees, negative is clockwise)
+    /// * `color` - The fill color
+    ///
+    pub fn fill_arc(&mut self, rect: Rect, start: f32, extent: f32, color: &Color) {
+        if rect.is_empty() {
+            return;
+        }
+
+        let (rx, ry) = (rect.width as f32 / 2.0, rect.height as f32 / 2.0);
+        let (cx, cy) = (rect.x as f32 + rx, rect.y as f32 + ry);
+
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                // Test the center of the pixel
+                let dx = (x as f32 + 0.5 - cx) / rx;
+                let dy = (cy - y as f32 - 0.5) / ry;
+
+                if dx * dx + dy * dy <= 1.0 && in_sweep(dy.atan2(dx).to_degrees(), start, extent) {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Fills an arc closed by a chord (the line between its end points), inscribed in the given rectangle
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The bounding rectangle of the full ellipse
+    /// * `start` - The start angle (degrees, counterclockwise from 3 o'clock)
+    /// * `extent` - The angular extent (degrees, negative is clockwise)
+    /// * `color` - The fill color
+    ///
+    pub fn fill_chord(&mut self, rect: Rect, start: f32, extent: f32, color: &Color) {
+        if rect.is_empty() {
+            return;
+        }
+
+        let (rx, ry) = (rect.width as f32 / 2.0, rect.height as f32 / 2.0);
+        let (cx, cy) = (rect.x as f32 + rx, rect.y as f32 + ry);
+
+        // On the unit circle, the inside is on the same side of the chord as the middle of the arc
+        let point = |angle: f32| Vec2f::new(angle.to_radians().cos(), angle.to_radians().sin());
+        let (first, last) = (point(start), point(start + extent));
+        let middle = point(start + extent / 2.0);
+        let side = |p: Vec2f| (last.x - first.x) * (p.y - first.y) - (last.y - first.y) * (p.x - first.x);
+        let inside = side(middle);
+
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                // Test the center of the pixel
+                let dx = (x as f32 + 0.5 - cx) / rx;
+                let dy = (cy - y as f32 - 0.5) / ry;
+
+                if dx * dx + dy * dy <= 1.0 && (extent.abs() >= 360.0 || side(Vec2f::new(dx, dy)) * inside >= 0.0) {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Draws the outline of an arc inscribed in the given rectangle
+    ///
+    /// Like the X server, the outline of the full ellipse covers `width + 1` by `height + 1` pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The bounding rectangle of the full ellipse
+    /// * `start` - The start angle (degrees, counterclockwise from 3 o'clock)
+    /// * `extent` - The angular extent (degrees, negative is clockwise)
+    /// * `color` - The outline color
+    ///
+    pub fn draw_arc(&mut self, rect: Rect, start: f32, extent: f32, color: &Color) {
+        let (rx, ry) = (rect.width as f32 / 2.0, rect.height as f32 / 2.0);
+        let (cx, cy) = (rect.x as f32 + rx, rect.y as f32 + ry);
+        let extent = extent.clamp(-360.0, 360.0);
+
+        // Enough steps to never leave a gap between two points
+        let steps = ((rx.max(ry) * 2.0 * std::f32::consts::PI * extent.abs() / 360.0).ceil() as usize * 2).max(1);
+
+        let mut previous = None;
+        for step in 0..=steps {
+            let angle = (start + extent * step as f32 / steps as f32).to_radians();
+            let point = (
+                (cx + rx * angle.cos()).round() as i32,
+                (cy - ry * angle.sin()).round() as i32,
+            );
+            if previous != Some(point) {
+                self.plot(point.0, point.1, color);
+                previous = Some(point);
+            }
+        }
+    }
+
+    /// Draws text with the built-in font
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The left of the text
+    /// * `y` - The baseline of the text
+    /// * `text` - The text to draw (missing characters are replaced by '?')
+    /// * `forground` - The color of the glyphs
+    /// * `background` - The color of the glyph cells (None to keep the pixels around the glyphs)
+    ///
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, forground: &Color, background: Option<&Color>) {
+        let top = y - glyphs::GLYPH_ASCENT as i32;
+
+        for (index, c) in text.chars().enumerate() {
+            let left = x + index as i32 * glyphs::GLYPH_WIDTH as i32;
+
+            for row in 0..glyphs::GLYPH_HEIGHT {
+                for column in 0..glyphs::GLYPH_WIDTH {
+                    let (px, py) = (left + column as i32, top + row as i32);
+                    if glyphs::is_set(c, column, row) {
+                        self.plot(px, py, forground);
+                    } else if let Some(background) = background {
+                        self.plot(px, py, background);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the size of a text drawn with the built-in font (in pixels)
+    pub fn text_size(text: &str) -> Vec2<u16> {
+        let len = text.chars().count() as u16;
+        Vec2::new(len.saturating_mul(glyphs::GLYPH_WIDTH), glyphs::GLYPH_HEIGHT)
+    }
+
+    /// Draws a primitive
+    ///
+    /// # Arguments
+    ///
+    /// * `primitive` - The primitive to draw
+    /// * `color` - The color of the primitive (the forground color of the graphics context)
+    ///
+    pub fn draw_primitive(&mut self, primitive: &Primitive, color: &Color) {
+        match primitive {
+            Primitive::Rectangle(rectangle) => self.draw_rect(rectangle_to_rect(rectangle), color),
+            Primitive::FillRectangle(rectangle) => self.fill_rect(rectangle_to_rect(rectangle), color),
+            Primitive::Arc(arc) => {
+                let (rect, start, extent) = arc_to_rect(arc);
+                self.draw_arc(rect, start, extent, color)
+            }
+            Primitive::FillArc(arc) => {
+                let (rect, start, extent) = arc_to_rect(arc);
+                self.fill_arc(rect, start, extent, color)
+            }
+            Primitive::Segment(segment) => {
+                self.draw_line(segment.x1 as i32, segment.y1 as i32, segment.x2 as i32, segment.y2 as i32, color)
+            }
+            Primitive::Line(points) => {
+                for segment in points.windows(2) {
+                    self.draw_line(
+                        segment[0].x as i32,
+                        segment[0].y as i32,
+                        segment[1].x as i32,
+                        segment[1].y as i32,
+                        color,
+                    );
+                }
+            }
+            Primitive::FillPolygon(points, _) => {
+                // The default fill rule of the graphics context
+                let points: Vec<Vec2f> = points.iter().map(|point| Vec2f::new(point.x as f32, point.y as f32)).collect();
+                self.fill_polygon(&points, FillRule::EvenOdd, color)
+            }
+        }
+    }
+
+    /// Returns the index of a pixel (None if outside of the canvas)
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.size.x as i32 || y >= self.size.y as i32 {
+            return None;
+        }
+        Some(y as usize * self.size.x as usize + x as usize)
+    }
+}
+
+/// Converts an X rectangle to a `Rect`
+fn rectangle_to_rect(rectangle: &XRectangle) -> Rect {
+    Rect::new(
+        rectangle.x as i32,
+        rectangle.y as i32,
+        rectangle.width as i32,
+        rectangle.height as i32,
+    )
+}
+
+/// Converts an X arc to its bounding rectangle, start angle and extent (degrees)
+fn arc_to_rect(arc: &XArc) -> (Rect, f32, f32) {
+    let rect = Rect::new(arc.x as i32, arc.y as i32, arc.width as i32, arc.height as i32);
+    (rect, arc.angle1 as f32 / 64.0, arc.angle2 as f32 / 64.0)
+}
+
+/// Returns true if the angle is inside the sweep of an arc (degrees)
+fn in_sweep(angle: f32, start: f32, extent: f32) -> bool {
+    if extent.abs() >= 360.0 {
+        return true;
+    }
+    if extent >= 0.0 {
+        (angle - start).rem_euclid(360.0) <= extent
+    } else {
+        (start - angle).rem_euclid(360.0) <= -extent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the pixels of the mask
+    fn masked(canvas: &Canvas) -> usize {
+        canvas.mask().iter().filter(|bit| **bit).count()
+    }
+
+    #[test]
+    fn test_fill_rect() {
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        canvas.fill_rect(Rect::new(-2, 2, 5, 3), &Color::RED);
+
+        assert_eq!(masked(&canvas), 9);
+        assert_eq!(canvas.pixel(0, 2), Some(Color::RED.rgba()));
+        assert_eq!(canvas.pixel(3, 2), Some([0; 4]));
+        assert!(!canvas.is_masked(0, 5));
+    }
+
+    #[test]
+    fn test_draw_rect() {
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        canvas.draw_rect(Rect::new(1, 1, 4, 4), &Color::WHITE);
+
+        // 5x5 outline
+        assert_eq!(masked(&canvas), 16);
+        assert!(canvas.is_masked(5, 5));
+        assert!(!canvas.is_masked(3, 3));
+    }
+
+    #[test]
+    fn test_draw_line() {
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        canvas.draw_line(0, 0, 9, 9, &Color::WHITE);
+
+        assert_eq!(masked(&canvas), 10);
+        assert!((0..10).all(|i| canvas.is_masked(i, i)));
+    }
+
+    #[test]
+    fn test_fill_chord() {
+        let mut pie = Canvas::new(Vec2::new(20, 20));
+        pie.fill_arc(Rect::new(0, 0, 20, 20), 0.0, 90.0, &Color::WHITE);
+        let mut chord = Canvas::new(Vec2::new(20, 20));
+        chord.fill_chord(Rect::new(0, 0, 20, 20), 0.0, 90.0, &Color::WHITE);
+
+        // The triangle between the center and the end points is removed
+        assert!(pie.is_masked(11, 8));
+        assert!(!chord.is_masked(11, 8));
+        assert!(chord.is_masked(15, 4));
+        assert!((masked(&pie) as i32 - masked(&chord) as i32 - 50).abs() <= 6);
+    }
+
+    #[test]
+    fn test_fill_polygon() {
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        let triangle = [Vec2f::new(0.0, 0.0), Vec2f::new(10.0, 0.0), Vec2f::new(0.0, 10.0)];
+        canvas.fill_polygon(&triangle, FillRule::EvenOdd, &Color::WHITE);
+
+        // Half of the canvas (pixel centers on the diagonal excluded)
+        assert_eq!(masked(&canvas), 45);
+        assert!(canvas.is_masked(0, 8));
+        assert!(!canvas.is_masked(9, 9));
+    }
+
+    #[test]
+    fn test_fill_polygon_rules() {
+        // Square drawn twice in the same direction (winding number 2)
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let twice: Vec<Vec2f> = square.iter().chain(square.iter()).map(|point| (*point).into()).collect();
+
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        canvas.fill_polygon(&twice, FillRule::EvenOdd, &Color::WHITE);
+        assert_eq!(masked(&canvas), 0);
+
+        canvas.fill_polygon(&twice, FillRule::Winding, &Color::WHITE);
+        assert_eq!(masked(&canvas), 100);
+    }
+
+    #[test]
+    fn test_stroke() {
+        let mut canvas = Canvas::new(Vec2::new(20, 10));
+        let points = [Vec2f::new(0.0, 5.0), Vec2f::new(20.0, 5.0)];
+
+        canvas.stroke(&points, &LineStyle::new(4), &Color::WHITE);
+        assert_eq!(masked(&canvas), 80);
+
+        canvas.clear();
+        let dashed = LineStyle {
+            dashes: vec![5, 5],
+            ..LineStyle::new(2)
+        };
+        canvas.stroke(&points, &dashed, &Color::WHITE);
+        assert_eq!(masked(&canvas), 20);
+        assert!(canvas.is_masked(2, 4));
+        assert!(!canvas.is_masked(7, 4));
+    }
+
+    #[test]
+    fn test_fill_arc() {
+        let mut canvas = Canvas::new(Vec2::new(20, 20));
+        canvas.fill_arc(Rect::new(0, 0, 20, 20), 0.0, 360.0, &Color::WHITE);
+
+        assert!(canvas.is_masked(10, 10));
+        assert!(!canvas.is_masked(0, 0));
+        // Close to the area of the disc (pi * 10^2)
+        assert!((masked(&canvas) as i32 - 314).abs() < 20);
+
+        // Top right quarter only
+        canvas.clear();
+        canvas.fill_arc(Rect::new(0, 0, 20, 20), 0.0, 90.0, &Color::WHITE);
+        assert!(canvas.is_masked(14, 5));
+        assert!(!canvas.is_masked(5, 5));
+        assert!(!canvas.is_masked(14, 14));
+    }
+
+    #[test]
+    fn test_draw_arc() {
+        let mut canvas = Canvas::new(Vec2::new(21, 21));
+        canvas.draw_arc(Rect::new(0, 0, 20, 20), 0.0, 360.0, &Color::WHITE);
+
+        assert!(canvas.is_masked(20, 10));
+        assert!(canvas.is_masked(10, 0));
+        assert!(!canvas.is_masked(10, 10));
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut canvas = Canvas::new(Vec2::new(12, 8));
+        canvas.draw_text(0, 7, "I", &Color::WHITE, Some(&Color::BLUE));
+
+        // Vertical bar of the 'I'
+        assert!((0..7).all(|y| canvas.pixel(2, y) == Some(Color::WHITE.rgba())));
+        assert_eq!(canvas.pixel(0, 0), Some(Color::BLUE.rgba()));
+        // Outside of the text
+        assert_eq!(canvas.pixel(6, 0), Some([0; 4]));
+        assert_eq!(Canvas::text_size("Hello"), Vec2::new(30, 8));
+    }
+
+    #[test]
+    fn test_mask_colors() {
+        let mut canvas = Canvas::new(Vec2::new(4, 1));
+        canvas.fill_rect(Rect::new(0, 0, 4, 1), &Color::RED);
+        canvas.plot(1, 0, &Color::BLACK);
+        canvas.plot(2, 0, &Color::TRANSPARENT);
+
+        assert_eq!(canvas.mask(), &[true, false, false, true]);
+        assert_eq!(canvas.pixel(1, 0), Some(Color::BLACK.rgba()));
+        // Transparent keeps the previous color
+        assert_eq!(canvas.pixel(2, 0), Some(Color::RED.rgba()));
+    }
+
+    #[test]
+    fn test_render_shapes() {
+        use crate::shape::{coord::{Anchor, Coord, Size}, Rectangle, Text};
+        use x11rb::rust_connection::RustConnection;
+
+        let background = Rectangle::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), Color::GRAY).unwrap();
+        let hidden = Rectangle::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), Color::RED).unwrap();
+        hidden.borrow_mut().set_visible(false);
+        let hole = Rectangle::fill(Anchor::Center, Coord::new(0.5, 0.5), Size::new(0.2, 0.2), Color::TRANSPARENT).unwrap();
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "A");
+
+        let shapes: Vec<Rc<RefCell<dyn Shape<RustConnection>>>> = vec![background, hidden, hole, text];
+
+        let mut canvas = Canvas::new(Vec2::new(50, 50));
+        canvas.render(&shapes).unwrap();
+
+        assert_eq!(canvas.pixel(40, 40), Some(Color::GRAY.rgba()));
+        assert!(canvas.is_masked(40, 40));
+        assert!(!canvas.is_masked(25, 25));
+        // The text is drawn on the bottom of its box (baseline at y = 8)
+        // Left column of the 'A' glyph, on a black background
+        assert_eq!(canvas.pixel(0, 3), Some(Color::WHITE.rgba()));
+        assert_eq!(canvas.pixel(0, 1), Some(Color::BLACK.rgba()));
+        assert!(!canvas.is_masked(0, 1));
+    }
+
+    #[test]
+    fn test_in_sweep() {
+        assert!(in_sweep(45.0, 0.0, 90.0));
+        assert!(!in_sweep(135.0, 0.0, 90.0));
+        assert!(in_sweep(-45.0, 0.0, -90.0));
+        assert!(in_sweep(10.0, 350.0, 30.0));
+        assert!(in_sweep(123.0, 0.0, 360.0));
+    }
+}
@@ -6,6 +6,8 @@ use x11rb::protocol::xproto::Drawable as XDrawable;
 
 use crate::{color::Depth, math::vec::Vec2};
 
+pub mod buffer;
+pub mod canvas;
 pub mod pixmap;
 pub mod window;
 
@@ -31,8 +33,8 @@ pub trait Drawable {
     /// - Depth::D24 (24 bits per pixel - true color)
     /// - Depth::D32 (32 bits per pixel - true color with alpha.)
     /// 
-    /// The alpha channel is a hack to represent full transparency.
-    /// Currently the lib does not support XComposite extension (To maximize compatibility with all X servers.)
+    /// The alpha channel is only blended on windows using an ARGB visual (see `Transparency::Argb`),
+    /// which requires a compositing manager. Otherwise transparency is emulated with the XShape extension.
     fn depth(&self) -> Depth;
 
     /// Returns the width of the drawable object.
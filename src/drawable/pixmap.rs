@@ -19,21 +19,22 @@ use crate::{color::Depth, math::vec::Vec2};
 /// 
 /// It is used to create off-screen buffers for drawing operations.
 /// 
-/// The `Pixmap` object is associated with a window, and has the same size and depth as the window.
-/// It also have the same lifetime as the window.
+/// The `Pixmap` object is associated with a window, and has the same size and depth as the window
+/// at the time it was created (it is not resized with the window).
 /// 
-pub struct Pixmap<'w> {
+pub struct Pixmap {
     /// The ID of the pixmap.
     id: u32,
-    /// The window that the pixmap is associated with.
-    /// Lifetime of the pixmap is the same as the window.
-    window: &'w Window,
+    /// The size of the pixmap (size of the window at creation).
+    size: Vec2<u16>,
+    /// The position of the window that the pixmap is associated with.
+    position: Vec2<i16>,
     /// The depth of the pixmap. (Number of bits per pixel.)
     depth: Depth,
 }
 
 /// Implementation of the `Pixmap` object.
-impl<'w> Pixmap<'w> {
+impl Pixmap {
     /// Creates a new pixmap drawable object.
     /// 
     /// # Arguments
@@ -52,7 +53,7 @@ impl<'w> Pixmap<'w> {
     /// 
     pub fn new<C: Connection>(
         conn: &C,
-        window: &'w Window,
+        window: &Window,
         depth: Option<Depth>,
     ) -> Result<Self, Box<dyn Error>> {
         let depth = depth.unwrap_or(window.depth());
@@ -61,11 +62,18 @@ impl<'w> Pixmap<'w> {
 
         conn.create_pixmap(depth.value(), id, window.id(), window.width(), window.height())?;
 
-        Ok(Self { id, window, depth })
+        Ok(Self {
+            id,
+            size: window.size(),
+            position: window.position(),
+            depth,
+        })
     }
 
     /// Frees the pixmap drawable object.
     /// 
+    /// The pixmap must not be used afterwards.
+    /// 
     /// # Arguments
     /// 
     /// * `conn` - The X11 connection.
@@ -78,13 +86,13 @@ impl<'w> Pixmap<'w> {
     /// 
     /// Returns an error if the pixmap could not be freed.
     /// 
-    pub fn free<C: Connection>(self, conn: &C) -> Result<(), Box<dyn Error>> {
+    pub fn free<C: Connection>(&self, conn: &C) -> Result<(), Box<dyn Error>> {
         conn.free_pixmap(self.id)?;
         Ok(())
     }
 }
 
-impl<'w> Drawable for Pixmap<'w> {
+impl Drawable for Pixmap {
 
     /// Returns the ID of the pixmap.
     fn id(&self) -> u32 {
@@ -96,13 +104,13 @@ impl<'w> Drawable for Pixmap<'w> {
         self.depth
     }
 
-    /// Returns the window that the pixmap is associated with.
+    /// Returns the size of the pixmap.
     fn size(&self) -> Vec2<u16> {
-        self.window.size()
+        self.size
     }
 
-    /// Returns the position of the pixmap.
+    /// Returns the position of the window that the pixmap is associated with.
     fn position(&self) -> Vec2<i16> {
-        self.window.position()
+        self.position
     }
 }
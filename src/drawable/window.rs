@@ -30,7 +30,7 @@ use x11rb::{
             ConnectionExt as _, DeviceUse, EventMask as XIEventMask, XIEventMask as XIEventMaskRef,
         },
         xproto::{
-            AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask as XEventMask, Window as XWindow, WindowClass
+            AtomEnum, ChangeWindowAttributesAux, ColormapAlloc, Colormap, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask as XEventMask, Screen, Setup, VisualClass, Visualid, Window as XWindow, WindowClass
         },
     },
 };
@@ -55,6 +55,20 @@ pub enum Mapping {
     Percent { fpos: Vec2<f32>, fsize: Vec2<f32> },
 }
 
+/// Describe how the transparent parts of the window are handled
+///
+/// - Shape: Transparent parts are cut out of the window with the XShape extension (no translucency)
+/// - Argb: The window uses a 32-bit ARGB visual, `Color::RGBA` is blended by the compositing manager.
+///   Falls back to `Shape` when no compositing manager is running.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Transparency {
+    /// Transparent parts are cut out with the XShape extension
+    #[default]
+    Shape,
+    /// Real translucency with an ARGB visual (if a compositing manager is running)
+    Argb,
+}
+
 /// Macro to define the event mask for the overlay window
 ///
 /// Arguments:
@@ -64,6 +78,7 @@ pub enum Mapping {
 macro_rules! EVENT_MASK {
     (overlay) => {
         XEventMask::STRUCTURE_NOTIFY          // Notify when the parent window is resized
+        | XEventMask::EXPOSURE                // Notify when the overlay content is lost
     };
     (parent) => {
         XEventMask::STRUCTURE_NOTIFY          // Notify when the parent window is resized 
@@ -96,12 +111,15 @@ pub struct Window {
     pos: Vec2<i16>,
     /// The window size
     size: Vec2<u16>,
+    /// The colormap of the ARGB visual (None if the window uses the parent visual)
+    colormap: Option<Colormap>,
 }
 
 impl Window {
     /// Create a new window
     /// 
-    /// This window will be mapped to the parent window with the specified mapping
+    /// This window will be mapped to the parent window with the specified mapping.
+    /// Its transparent parts are cut out with the XShape extension (see `new_with_transparency`).
     /// 
     /// # Arguments:
     /// 
@@ -121,6 +139,34 @@ impl Window {
         conn: &C,
         parent: &Window,
         mapping: &Mapping,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_transparency(conn, parent, mapping, Transparency::Shape)
+    }
+
+    /// Create a new window, choosing how its transparent parts are handled
+    /// 
+    /// This window will be mapped to the parent window with the specified mapping
+    /// 
+    /// # Arguments:
+    /// 
+    /// * `conn` - The X11 connection
+    /// * `parent` - The parent window
+    /// * `mapping` - The window mapping
+    /// * `transparency` - How the transparent parts of the window are handled
+    /// 
+    /// # Returns:
+    /// 
+    /// A new window
+    /// 
+    /// # Errors:
+    /// 
+    /// This method can return an error if the coordinates or percentages are invalid
+    /// 
+    pub fn new_with_transparency<C: Connection>(
+        conn: &C,
+        parent: &Window,
+        mapping: &Mapping,
+        transparency: Transparency,
     ) -> Result<Self, Box<dyn Error>> {
         let xwindow = conn.generate_id()?;
 
@@ -168,7 +214,26 @@ impl Window {
             }
         };
 
-        let depth = Depth::from(parent.depth as u8);
+        let aux = CreateWindowAux::new()
+            .override_redirect(1)
+            .event_mask(EVENT_MASK!(overlay));
+
+        let argb_visual = match transparency {
+            Transparency::Argb => Self::argb_visual(conn, parent.root)?,
+            Transparency::Shape => None,
+        };
+
+        let (depth, visual, colormap, aux) = match argb_visual {
+            Some(visual) => {
+                // A window with a different depth than its parent needs its own colormap and border
+                let colormap = conn.generate_id()?;
+                conn.create_colormap(ColormapAlloc::NONE, colormap, parent.root, visual)?;
+
+                let aux = aux.background_pixel(0).border_pixel(0).colormap(colormap);
+                (Depth::D32, visual, Some(colormap), aux)
+            }
+            None => (Depth::from(parent.depth as u8), 0, None, aux),
+        };
 
         conn.create_window(
             depth.value(),
@@ -180,10 +245,8 @@ impl Window {
             height,
             0,
             WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new()
-                .override_redirect(1)
-                .event_mask(EVENT_MASK!(overlay)),
+            visual,
+            &aux,
         )?;
 
         conn.map_window(xwindow)?;
@@ -195,9 +258,70 @@ impl Window {
             pos: (x, y).into(),
             size: (width, height).into(),
             mapping: mapping.clone(),
+            colormap,
         })
     }
 
+    /// Find the ARGB visual to use for the window
+    /// 
+    /// # Arguments:
+    /// 
+    /// * `conn` - The X11 connection
+    /// * `root` - The root window of the screen
+    /// 
+    /// # Returns:
+    /// 
+    /// The 32-bit TrueColor visual of the screen, None if there is no compositing manager
+    /// (the `_NET_WM_CM_Sn` selection has no owner) or no such visual
+    /// 
+    /// # Errors:
+    /// 
+    /// This method can return an error if the compositing manager selection cannot be fetched
+    /// 
+    fn argb_visual<C: Connection>(conn: &C, root: XWindow) -> Result<Option<Visualid>, Box<dyn Error>> {
+        let Some((screen_num, screen)) = Self::find_screen(conn.setup(), root) else {
+            return Ok(None);
+        };
+
+        // A compositing manager owns the _NET_WM_CM_Sn selection of its screen
+        let selection = format!("_NET_WM_CM_S{}", screen_num);
+        let atom = conn.intern_atom(false, selection.as_bytes())?.reply()?.atom;
+        let owner = conn.get_selection_owner(atom)?.reply()?.owner;
+
+        if owner == x11rb::NONE {
+            return Ok(None);
+        }
+
+        Ok(Self::find_visual(screen, Depth::D32))
+    }
+
+    /// Find the screen of a root window (with its number)
+    fn find_screen(setup: &Setup, root: XWindow) -> Option<(usize, &Screen)> {
+        setup.roots.iter().enumerate().find(|(_, screen)| screen.root == root)
+    }
+
+    /// Find a TrueColor visual of the given depth
+    fn find_visual(screen: &Screen, depth: Depth) -> Option<Visualid> {
+        screen
+            .allowed_depths
+            .iter()
+            .filter(|allowed| allowed.depth == depth.value::<u8>())
+            .flat_map(|allowed| allowed.visuals.iter())
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+            .map(|visual| visual.visual_id)
+    }
+
+    /// Check if the window uses an ARGB visual
+    /// 
+    /// # Returns:
+    /// 
+    /// True if the transparency is handled by the compositing manager,
+    /// false if it is handled with the XShape extension
+    /// 
+    pub fn is_argb(&self) -> bool {
+        self.colormap.is_some()
+    }
+
     /// Fetch new size and position of the window
     /// regarding the mapping and the parent window.
     /// 
@@ -301,6 +425,9 @@ impl Window {
     pub fn free<C: Connection>(self, conn: &C) -> Result<(), Box<dyn Error>> {
         // Use self to consume the window
         conn.destroy_window(self.id)?;
+        if let Some(colormap) = self.colormap {
+            conn.free_colormap(colormap)?;
+        }
         Ok(())
     }
 
@@ -389,6 +516,7 @@ impl Window {
             pos: (x, y).into(),
             size: (width, height).into(),
             mapping: Mapping::FullScreen,
+            colormap: None,
         })
     }
 
@@ -472,3 +600,56 @@ impl Drawable for Window {
         self.depth
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::{Depth as XDepth, Visualtype};
+
+    use super::*;
+
+    fn visual(visual_id: Visualid, class: VisualClass) -> Visualtype {
+        Visualtype {
+            visual_id,
+            class,
+            bits_per_rgb_value: 8,
+            colormap_entries: 256,
+            red_mask: 0xFF0000,
+            green_mask: 0xFF00,
+            blue_mask: 0xFF,
+        }
+    }
+
+    fn screen(root: XWindow, allowed_depths: Vec<XDepth>) -> Screen {
+        Screen { root, allowed_depths, ..Screen::default() }
+    }
+
+    #[test]
+    fn test_find_visual() {
+        let screen = screen(
+            0x100,
+            vec![
+                XDepth { depth: 24, visuals: vec![visual(0x21, VisualClass::TRUE_COLOR)] },
+                XDepth { depth: 32, visuals: vec![visual(0x40, VisualClass::DIRECT_COLOR), visual(0x41, VisualClass::TRUE_COLOR)] },
+            ],
+        );
+
+        // The first TrueColor visual of the depth
+        assert_eq!(Window::find_visual(&screen, Depth::D32), Some(0x41));
+        assert_eq!(Window::find_visual(&screen, Depth::D24), Some(0x21));
+        assert_eq!(Window::find_visual(&screen, Depth::D16), None);
+
+        // No ARGB visual without TrueColor visual
+        let screen = self::screen(0x100, vec![XDepth { depth: 32, visuals: vec![visual(0x40, VisualClass::DIRECT_COLOR)] }]);
+        assert_eq!(Window::find_visual(&screen, Depth::D32), None);
+    }
+
+    #[test]
+    fn test_find_screen() {
+        let setup = Setup { roots: vec![screen(0x100, Vec::new()), screen(0x200, Vec::new())], ..Setup::default() };
+
+        let (screen_num, screen) = Window::find_screen(&setup, 0x200).unwrap();
+        assert_eq!(screen_num, 1);
+        assert_eq!(screen.root, 0x200);
+        assert!(Window::find_screen(&setup, 0x300).is_none());
+    }
+}
@@ -11,7 +11,7 @@ use std::{error::Error, fmt::Debug};
 
 use x11rb::{
     connection::Connection,
-    protocol::{xinput::{ButtonPressEvent, RawButtonPressEvent, RawKeyPressEvent}, xproto::ConfigureNotifyEvent, Event as XEvent},
+    protocol::{xinput::{ButtonPressEvent, RawButtonPressEvent, RawKeyPressEvent}, xproto::{ConfigureNotifyEvent, ExposeEvent}, Event as XEvent},
 };
 
 use crate::{key::Key, math::vec::Vec2, shape::coord::Coord, Drawable, Overlay};
@@ -47,7 +47,11 @@ pub enum Event {
     /// This trigger only when the parent window is the source of the event
     KeyRelease(Key),
     /// Event indicating that a redraw is needed.
+    /// 
+    /// Only the shapes that changed since the last draw are repainted.
     Redraw,
+    /// Event indicating that the overlay content was lost and must be fully repainted.
+    Expose,
     /// Event indicating that the event loop should stop.
     StopEventLoop,
     /// Event indicating that nothing has happened.
@@ -59,7 +63,7 @@ pub enum Event {
 /// Implement the event handling system for the overlay.
 impl Event {
 
-    pub const DB_SIZE: usize = 9;
+    pub const DB_SIZE: usize = 10;
 
     #[inline(always)]
     pub fn gen_debounce_table() -> [std::time::Instant; Self::DB_SIZE] {
@@ -88,6 +92,7 @@ impl Event {
             Self::StopEventLoop => 6,
             Self::Nothing => 7,
             Self::Unkown => 8,
+            Self::Expose => 9,
         }
     }
 
@@ -102,6 +107,7 @@ impl Event {
             Self::StopEventLoop => std::time::Duration::from_millis(0),
             Self::Nothing => std::time::Duration::from_millis(0),
             Self::Unkown => std::time::Duration::from_millis(0),
+            Self::Expose => std::time::Duration::from_millis(0),
         }
     }
 
@@ -173,7 +179,8 @@ impl Event {
                     Ok(Self::Unkown)
                 }
             }
-            XEvent::MapNotify(_) => Ok(Self::Redraw),
+            XEvent::MapNotify(_) => Ok(Self::Expose),
+            XEvent::Expose(ExposeEvent { count: 0, .. }) => Ok(Self::Expose),
             XEvent::NoExposure(_) => Ok(Self::Redraw),
             _ => {
                 Ok(Self::Unkown)
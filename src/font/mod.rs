@@ -0,0 +1,648 @@
+//! Font module
+//!
+//! This module describes the core fonts used to draw the texts.
+//!
+//! Texts are encoded in UCS-2 (`Char2b`, the code point split in two bytes), as expected by
+//! the ISO10646-1 core fonts (ISO8859-1 fonts also work for the first 256 code points).
+//! A core font rarely covers the whole of Unicode, so the overlay draws with a list of fonts:
+//! every character is drawn with the first font that has its glyph, and characters missing
+//! from every font (or outside of the Basic Multilingual Plane) are drawn with a replacement
+//! glyph (`U+FFFD`, or `?` if no font has it).
+//!
+//! Each text may name its own font (`FontSpec`, matched against the XLFD names of the server fonts).
+//! The fonts are opened on demand by the font registry of the overlay, and kept open until the overlay is dropped.
+
+use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc};
+
+#[cfg(feature = "truetype")]
+pub mod truetype;
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Char2b, ConnectionExt, FontWrapper, Fontable, QueryFontReply},
+};
+
+/// Character drawn in place of the glyphs missing from every font
+pub const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// Replacement used when no font has the replacement character
+const FALLBACK_REPLACEMENT: char = '?';
+
+/// Maximum number of characters drawn by a single `ImageText16` request
+pub(crate) const MAX_RUN_LENGTH: usize = 255;
+
+/// Default pixel size of the fonts
+pub const DEFAULT_PIXEL_SIZE: u16 = 13;
+
+/// Maximum number of font names listed when looking for the available pixel sizes
+const MAX_LISTED_FONTS: u16 = 1000;
+
+/// Describe the weight of a font (the `WEIGHT_NAME` field of its XLFD name)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FontWeight {
+    Light,
+    Regular,
+    #[default]
+    Medium,
+    DemiBold,
+    Bold,
+    /// Any weight (the first matching font is used)
+    Any,
+}
+
+impl FontWeight {
+    /// Returns the weight as written in the XLFD names
+    fn xlfd(&self) -> &'static str {
+        match self {
+            FontWeight::Light => "light",
+            FontWeight::Regular => "regular",
+            FontWeight::Medium => "medium",
+            FontWeight::DemiBold => "demibold",
+            FontWeight::Bold => "bold",
+            FontWeight::Any => "*",
+        }
+    }
+}
+
+/// Describe the font of a text
+///
+/// The font is matched against the XLFD names of the server fonts (upright ISO10646-1 fonts).
+/// The default font is the medium `fixed` font of the default pixel size.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontSpec {
+    /// The family of the font (`fixed`, `helvetica`, `dejavu sans`, ..., any family if None)
+    pub family: Option<String>,
+    /// The weight of the font
+    pub weight: FontWeight,
+    /// The height of the font (in pixels)
+    pub pixel_size: u16,
+}
+
+impl Default for FontSpec {
+    fn default() -> Self {
+        Self {
+            family: Some("fixed".to_string()),
+            weight: FontWeight::default(),
+            pixel_size: DEFAULT_PIXEL_SIZE,
+        }
+    }
+}
+
+impl FontSpec {
+    /// Creates a font description
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - The family of the font (case insensitive, wildcards allowed)
+    /// * `weight` - The weight of the font
+    /// * `pixel_size` - The height of the font (in pixels)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{FontSpec, FontWeight};
+    ///
+    /// let font = FontSpec::new("fixed", FontWeight::Bold, 18);
+    /// assert_eq!(font.xlfd(), "-*-fixed-bold-r-*--18-*-*-*-*-*-iso10646-1");
+    /// ```
+    pub fn new(family: &str, weight: FontWeight, pixel_size: u16) -> Self {
+        Self {
+            family: Some(family.to_string()),
+            weight,
+            pixel_size,
+        }
+    }
+
+    /// Returns the XLFD pattern matching the font
+    pub fn xlfd(&self) -> String {
+        self.pattern(&self.pixel_size.to_string())
+    }
+
+    /// Returns a copy of the description with another pixel size
+    pub fn with_pixel_size(&self, pixel_size: u16) -> Self {
+        Self {
+            pixel_size,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the XLFD pattern matching the font in every pixel size
+    fn xlfd_any_size(&self) -> String {
+        self.pattern("*")
+    }
+
+    fn pattern(&self, pixel_size: &str) -> String {
+        // The fields of the name are separated by dashes
+        let family = self.family.as_deref().unwrap_or("*").replace('-', " ");
+        format!(
+            "-*-{}-{}-r-*--{}-*-*-*-*-*-iso10646-1",
+            family,
+            self.weight.xlfd(),
+            pixel_size
+        )
+    }
+}
+
+/// Returns the available pixel size closest to the wanted one
+///
+/// Scalable fonts (pixel size 0 in their name) are available in every size.
+/// Between two bitmap sizes at the same distance, the smaller one is used.
+///
+/// # Arguments
+///
+/// * `names` - The XLFD names of the fonts
+/// * `wanted` - The wanted pixel size
+///
+/// # Returns
+///
+/// The pixel size (None if no name has a pixel size)
+fn closest_pixel_size<S: AsRef<str>>(names: &[S], wanted: u16) -> Option<u16> {
+    let sizes: Vec<u16> = names
+        .iter()
+        .filter_map(|name| name.as_ref().split('-').nth(7)?.parse().ok())
+        .collect();
+
+    if sizes.contains(&0) {
+        return Some(wanted);
+    }
+    sizes.into_iter().min_by_key(|size| (size.abs_diff(wanted), *size))
+}
+
+/// Encodes a character for the 16-bit text requests
+///
+/// # Returns
+///
+/// The code point split in two bytes (None outside of the Basic Multilingual Plane)
+pub(crate) fn encode(c: char) -> Option<Char2b> {
+    let code = u16::try_from(c as u32).ok()?;
+    let [byte1, byte2] = code.to_be_bytes();
+    Some(Char2b { byte1, byte2 })
+}
+
+/// Describe the glyphs of a core font (as returned by `QueryFont`)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Glyphs {
+    /// The first row of the font (first byte of the characters)
+    min_byte1: u8,
+    /// The last row of the font
+    max_byte1: u8,
+    /// The first column of the font (second byte of the characters)
+    min_byte2: u16,
+    /// The last column of the font
+    max_byte2: u16,
+    /// Whether each character of the rows and columns range has a glyph (empty if they all do)
+    exists: Vec<bool>,
+    /// The logical ascent of the font (in pixels)
+    ascent: i16,
+    /// The logical descent of the font (in pixels)
+    descent: i16,
+}
+
+impl Glyphs {
+    /// Creates the glyph table of a font from its `QueryFont` reply
+    ///
+    /// Characters whose metrics are all zero do not exist in the font.
+    pub fn from_reply(reply: &QueryFontReply) -> Self {
+        let exists = reply
+            .char_infos
+            .iter()
+            .map(|info| {
+                info.left_side_bearing != 0
+                    || info.right_side_bearing != 0
+                    || info.character_width != 0
+                    || info.ascent != 0
+                    || info.descent != 0
+                    || info.attributes != 0
+            })
+            .collect();
+
+        Self {
+            min_byte1: reply.min_byte1,
+            max_byte1: reply.max_byte1,
+            min_byte2: reply.min_char_or_byte2,
+            max_byte2: reply.max_char_or_byte2,
+            exists,
+            ascent: reply.font_ascent,
+            descent: reply.font_descent,
+        }
+    }
+
+    /// Returns true if the font has a glyph for the character
+    pub fn contains(&self, c: char) -> bool {
+        let Some(Char2b { byte1, byte2 }) = encode(c) else {
+            return false;
+        };
+        let byte2 = byte2 as u16;
+        if !(self.min_byte1..=self.max_byte1).contains(&byte1) || !(self.min_byte2..=self.max_byte2).contains(&byte2) {
+            return false;
+        }
+        if self.exists.is_empty() {
+            return true;
+        }
+
+        let columns = (self.max_byte2 - self.min_byte2) as usize + 1;
+        let index = (byte1 - self.min_byte1) as usize * columns + (byte2 - self.min_byte2) as usize;
+        self.exists.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns the logical ascent of the font (in pixels)
+    pub fn ascent(&self) -> i16 {
+        self.ascent
+    }
+
+    /// Returns the logical descent of the font (in pixels)
+    pub fn descent(&self) -> i16 {
+        self.descent
+    }
+}
+
+/// A run of characters drawn with the same font
+#[derive(Clone, Debug)]
+pub(crate) struct TextRun {
+    /// The font of the run
+    pub font: Fontable,
+    /// The encoded characters (at most `MAX_RUN_LENGTH`)
+    pub chars: Vec<Char2b>,
+}
+
+/// Represents an ordered list of fonts, the first one is the primary font
+///
+/// The fonts are only referenced, they are owned (and closed) by the overlay.
+#[derive(Clone, Debug, Default)]
+pub struct FontSet {
+    fonts: Vec<(Fontable, Rc<Glyphs>)>,
+}
+
+impl FontSet {
+    /// Creates an empty font set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a font at the end of the set (used for the characters missing from the previous fonts)
+    pub fn push(&mut self, font: Fontable, glyphs: Glyphs) {
+        self.fonts.push((font, Rc::new(glyphs)));
+    }
+
+    /// Adds the fonts of another set at the end of the set (skipping the fonts already in the set)
+    pub fn extend(&mut self, other: &FontSet) {
+        for (font, glyphs) in &other.fonts {
+            if self.glyphs(*font).is_none() {
+                self.fonts.push((*font, glyphs.clone()));
+            }
+        }
+    }
+
+    /// Returns the primary font of the set
+    pub fn primary(&self) -> Option<Fontable> {
+        self.fonts.first().map(|(font, _)| *font)
+    }
+
+    /// Returns the fonts of the set, in order
+    pub fn fonts(&self) -> impl Iterator<Item = Fontable> + '_ {
+        self.fonts.iter().map(|(font, _)| *font)
+    }
+
+    /// Returns the glyph table of a font of the set
+    pub fn glyphs(&self, font: Fontable) -> Option<&Glyphs> {
+        self.fonts.iter().find(|(id, _)| *id == font).map(|(_, glyphs)| glyphs.as_ref())
+    }
+
+    /// Returns the number of fonts in the set
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    /// Returns true if the set has no font
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    /// Returns the first font of the set that has a glyph for the character
+    pub fn font_for(&self, c: char) -> Option<Fontable> {
+        self.fonts.iter().find(|(_, glyphs)| glyphs.contains(c)).map(|(font, _)| *font)
+    }
+
+    /// Splits a text in runs of characters drawn with the same font
+    ///
+    /// Characters missing from every font are replaced, so every character of the text is drawn.
+    ///
+    /// # Returns
+    ///
+    /// The runs, in order (empty if the set has no font)
+    pub(crate) fn runs(&self, text: &str) -> Vec<TextRun> {
+        let Some(primary) = self.primary() else {
+            return Vec::new();
+        };
+
+        let replacement = [REPLACEMENT_CHARACTER, FALLBACK_REPLACEMENT]
+            .into_iter()
+            .find_map(|c| self.font_for(c).map(|font| (font, c)))
+            .unwrap_or((primary, FALLBACK_REPLACEMENT));
+
+        let mut runs: Vec<TextRun> = Vec::new();
+        for c in text.chars() {
+            let (font, c) = self.font_for(c).map(|font| (font, c)).unwrap_or(replacement);
+            // Only characters of the Basic Multilingual Plane have glyphs
+            let char2b = encode(c).unwrap_or(Char2b { byte1: 0, byte2: FALLBACK_REPLACEMENT as u8 });
+
+            match runs.last_mut() {
+                Some(run) if run.font == font && run.chars.len() < MAX_RUN_LENGTH => run.chars.push(char2b),
+                _ => runs.push(TextRun { font, chars: vec![char2b] }),
+            }
+        }
+
+        runs
+    }
+}
+
+/// A core font opened on the X server (closed when dropped) and its glyphs
+type OpenedFont<C> = (FontWrapper<Rc<C>>, FontSet);
+
+/// Opens a core font and queries its glyphs
+///
+/// # Arguments
+///
+/// * `conn` - The X11 connection
+/// * `pattern` - The name of the font (XLFD, wildcards allowed)
+///
+/// # Returns
+///
+/// The font and a set holding only this font
+///
+/// # Errors
+///
+/// Returns an error if no font matches the pattern.
+fn open<C: Connection>(conn: &Rc<C>, pattern: &str) -> Result<OpenedFont<C>, Box<dyn Error>> {
+    let id = conn.generate_id()?;
+    conn.open_font(id, pattern.as_bytes())?
+        .check()
+        .map_err(|_| format!("No font matches {pattern}"))?;
+
+    // The font is only owned once opened (closed when dropped)
+    let font = FontWrapper::for_font(conn.clone(), id);
+    let reply = conn.query_font(id)?.reply()?;
+
+    let mut set = FontSet::new();
+    set.push(id, Glyphs::from_reply(&reply));
+    Ok((font, set))
+}
+
+/// Represents the fonts opened by an overlay
+///
+/// The fonts are opened on demand and cached by XLFD pattern (patterns matching no font are cached too).
+/// Every font set built by the registry ends with the default fonts, used for the missing glyphs.
+pub(crate) struct FontRegistry<C: Connection> {
+    conn: Rc<C>,
+    /// The opened fonts by pattern (None if no font matches the pattern)
+    fonts: RefCell<HashMap<String, Option<OpenedFont<C>>>>,
+    /// The names of the fonts matching a description in every pixel size
+    names: RefCell<HashMap<String, Vec<String>>>,
+    /// The default fonts (the primary font, then the fallback fonts)
+    default: FontSet,
+}
+
+impl<C: Connection> FontRegistry<C> {
+    /// Opens the default fonts
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection
+    /// * `primary` - The candidates for the primary font (the first available one is used)
+    /// * `fallbacks` - The fallback fonts (skipped if not available)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no primary font is available.
+    pub fn new(conn: &Rc<C>, primary: &[&str], fallbacks: &[&str]) -> Result<Self, Box<dyn Error>> {
+        let mut registry = Self {
+            conn: conn.clone(),
+            fonts: RefCell::new(HashMap::new()),
+            names: RefCell::new(HashMap::new()),
+            default: FontSet::new(),
+        };
+
+        let primary = primary
+            .iter()
+            .find_map(|pattern| registry.load(pattern).ok())
+            .ok_or("No font available")?;
+        registry.default = primary;
+        for pattern in fallbacks {
+            if let Ok(fallback) = registry.load(pattern) {
+                registry.default.extend(&fallback);
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// Opens a font (or returns the cached one)
+    ///
+    /// # Returns
+    ///
+    /// A set holding only the font
+    fn load(&self, pattern: &str) -> Result<FontSet, Box<dyn Error>> {
+        let mut fonts = self.fonts.borrow_mut();
+        if !fonts.contains_key(pattern) {
+            fonts.insert(pattern.to_string(), open(&self.conn, pattern).ok());
+        }
+
+        match &fonts[pattern] {
+            Some((_, set)) => Ok(set.clone()),
+            None => Err(format!("No font matches {pattern}"))?,
+        }
+    }
+
+    /// Returns the default fonts
+    pub fn default_fonts(&self) -> &FontSet {
+        &self.default
+    }
+
+    /// Adds a fallback font at the end of the default fonts
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no font matches the pattern.
+    pub fn add_fallback(&mut self, pattern: &str) -> Result<(), Box<dyn Error>> {
+        let fallback = self.load(pattern)?;
+        self.default.extend(&fallback);
+        Ok(())
+    }
+
+    /// Returns the available pixel size of a font closest to the wanted one
+    ///
+    /// The server fonts are listed once per description (family and weight).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no font matches the description.
+    pub fn pixel_size(&self, spec: &FontSpec) -> Result<u16, Box<dyn Error>> {
+        let pattern = spec.xlfd_any_size();
+        let mut names = self.names.borrow_mut();
+        if !names.contains_key(&pattern) {
+            let reply = self.conn.list_fonts(MAX_LISTED_FONTS, pattern.as_bytes())?.reply()?;
+            let listed = reply.names.iter().map(|name| String::from_utf8_lossy(&name.name).into_owned()).collect();
+            names.insert(pattern.clone(), listed);
+        }
+
+        Ok(closest_pixel_size(&names[&pattern], spec.pixel_size).ok_or(format!("No font matches {pattern}"))?)
+    }
+
+    /// Returns the fonts of a text: the font matching the description, then the default fonts
+    ///
+    /// The pixel size of the description is replaced by the closest available one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no font matches the description.
+    pub fn open(&self, spec: &FontSpec) -> Result<FontSet, Box<dyn Error>> {
+        let pixel_size = self.pixel_size(spec)?;
+        let mut set = self.load(&spec.with_pixel_size(pixel_size).xlfd())?;
+        set.extend(&self.default);
+        Ok(set)
+    }
+
+    /// Returns the fonts of a text, the default fonts are used if no font matches the description
+    pub fn font_set(&self, spec: Option<&FontSpec>) -> FontSet {
+        spec.and_then(|spec| self.open(spec).ok())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::Charinfo;
+
+    use super::*;
+
+    /// A glyph table covering the rows and columns, with the given missing characters
+    fn glyphs(rows: (u8, u8), columns: (u16, u16), missing: &[char]) -> Glyphs {
+        let mut reply = QueryFontReply {
+            min_byte1: rows.0,
+            max_byte1: rows.1,
+            min_char_or_byte2: columns.0,
+            max_char_or_byte2: columns.1,
+            ..Default::default()
+        };
+        for byte1 in rows.0..=rows.1 {
+            for byte2 in columns.0..=columns.1 {
+                let c = char::from_u32((byte1 as u32) << 8 | byte2 as u32).unwrap_or(FALLBACK_REPLACEMENT);
+                let width = match missing.contains(&c) {
+                    true => 0,
+                    false => 6,
+                };
+                reply.char_infos.push(Charinfo { character_width: width, ..Default::default() });
+            }
+        }
+        Glyphs::from_reply(&reply)
+    }
+
+    fn bytes(runs: &[TextRun]) -> Vec<(Fontable, Vec<(u8, u8)>)> {
+        runs.iter()
+            .map(|run| (run.font, run.chars.iter().map(|c| (c.byte1, c.byte2)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_encode() {
+        let encoded = encode('°').unwrap();
+        assert_eq!((encoded.byte1, encoded.byte2), (0x00, 0xB0));
+        let encoded = encode('€').unwrap();
+        assert_eq!((encoded.byte1, encoded.byte2), (0x20, 0xAC));
+        assert!(encode('😀').is_none());
+    }
+
+    #[test]
+    fn test_contains() {
+        // Latin-1 font without the degree sign
+        let latin = glyphs((0, 0), (0x20, 0xFF), &['°']);
+        assert!(latin.contains('A'));
+        assert!(latin.contains('é'));
+        assert!(!latin.contains('°'));
+        assert!(!latin.contains('\u{1F}'));
+        assert!(!latin.contains('€'));
+
+        // Fonts without metrics per character have every glyph of their range
+        let uniform = Glyphs::from_reply(&QueryFontReply { max_byte1: 0x30, max_char_or_byte2: 0xFF, ..Default::default() });
+        assert!(uniform.contains('€'));
+        assert!(!uniform.contains('😀'));
+    }
+
+    #[test]
+    fn test_fallback() {
+        let mut fonts = FontSet::new();
+        fonts.push(1, glyphs((0, 0), (0x20, 0x7E), &[]));
+        fonts.push(2, glyphs((0x00, 0x20), (0x00, 0xFF), &[]));
+
+        assert_eq!(fonts.primary(), Some(1));
+        assert_eq!(fonts.font_for('a'), Some(1));
+        assert_eq!(fonts.font_for('é'), Some(2));
+        assert_eq!(
+            bytes(&fonts.runs("a°b")),
+            vec![(1, vec![(0, b'a')]), (2, vec![(0, 0xB0)]), (1, vec![(0, b'b')])]
+        );
+    }
+
+    #[test]
+    fn test_replacement() {
+        // The replacement character is missing, '?' is used instead
+        let mut fonts = FontSet::new();
+        fonts.push(1, glyphs((0, 0), (0x20, 0x7E), &[]));
+        assert_eq!(bytes(&fonts.runs("a€😀")), vec![(1, vec![(0, b'a'), (0, b'?'), (0, b'?')])]);
+
+        // U+FFFD is drawn with the font that has it
+        fonts.push(2, glyphs((0xFF, 0xFF), (0xF0, 0xFF), &[]));
+        assert_eq!(bytes(&fonts.runs("a€")), vec![(1, vec![(0, b'a')]), (2, vec![(0xFF, 0xFD)])]);
+
+        assert!(FontSet::new().runs("a").is_empty());
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut fonts = FontSet::new();
+        fonts.push(3, glyphs((0, 0), (0x20, 0x7E), &[]));
+
+        let mut default = FontSet::new();
+        default.push(1, glyphs((0, 0), (0x20, 0x7E), &[]));
+        default.push(3, glyphs((0, 0), (0x20, 0x7E), &[]));
+        fonts.extend(&default);
+
+        assert_eq!(fonts.fonts().collect::<Vec<_>>(), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_spec() {
+        assert_eq!(FontSpec::default().xlfd(), "-*-fixed-medium-r-*--13-*-*-*-*-*-iso10646-1");
+        assert_eq!(
+            FontSpec::new("DejaVu-Sans", FontWeight::Any, 20).xlfd(),
+            "-*-DejaVu Sans-*-r-*--20-*-*-*-*-*-iso10646-1"
+        );
+    }
+
+    #[test]
+    fn test_closest_pixel_size() {
+        let names = [
+            "-misc-fixed-medium-r-normal--10-100-75-75-c-60-iso10646-1",
+            "-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1",
+            "-misc-fixed-medium-r-normal--20-200-75-75-c-100-iso10646-1",
+        ];
+        assert_eq!(closest_pixel_size(&names, 12), Some(13));
+        assert_eq!(closest_pixel_size(&names, 30), Some(20));
+        // Same distance to 13 and 20
+        assert_eq!(closest_pixel_size(&names[1..], 16), Some(13));
+        assert_eq!(closest_pixel_size::<&str>(&[], 13), None);
+
+        // Scalable fonts have every size
+        let scalable = ["-bitstream-charter-medium-r-normal--0-0-0-0-p-0-iso10646-1"];
+        assert_eq!(closest_pixel_size(&scalable, 37), Some(37));
+    }
+
+    #[test]
+    fn test_long_runs() {
+        let mut fonts = FontSet::new();
+        fonts.push(1, glyphs((0, 0), (0x20, 0x7E), &[]));
+        let runs = fonts.runs(&"a".repeat(MAX_RUN_LENGTH + 1));
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].chars.len(), MAX_RUN_LENGTH);
+    }
+}
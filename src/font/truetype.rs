@@ -0,0 +1,314 @@
+//! TrueType module (behind the `truetype` feature)
+//!
+//! This module rasterizes the texts client-side with TrueType and OpenType fonts,
+//! so they are anti-aliased and do not depend on the fonts installed on the X server.
+//!
+//! Glyphs are rasterized once per pixel size and kept in the glyph cache of the font
+//! (shared by every text using it). A text is composed into a coverage map (one byte per pixel),
+//! uploaded as an image by the `Text` shape: the pixels whose coverage is above the coverage
+//! threshold are part of the window shape, so the edges stay crisp without a compositing manager.
+
+use std::{cell::RefCell, collections::HashMap, error::Error, path::Path, rc::Rc};
+
+use ab_glyph::{point, Font, FontArc, GlyphId, PxScale, ScaleFont};
+
+use super::REPLACEMENT_CHARACTER;
+
+/// A glyph rasterized at a pixel size
+#[derive(Debug)]
+struct RasterGlyph {
+    /// The offset of the left of the glyph from the pen position (in pixels)
+    left: i32,
+    /// The offset of the top of the glyph from the baseline (in pixels, negative above it)
+    top: i32,
+    /// The width of the glyph bitmap
+    width: u32,
+    /// The coverage of the pixels of the glyph (row major)
+    coverage: Vec<u8>,
+}
+
+/// Represents the coverage of a rasterized text (one byte per pixel, 0 is not covered)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coverage {
+    width: u16,
+    height: u16,
+    /// The distance from the top of the text to its baseline (in pixels)
+    ascent: u16,
+    values: Vec<u8>,
+}
+
+impl Coverage {
+    /// Returns the width of the text (in pixels)
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height of the text (in pixels), from the ascent to the descent of the font
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the distance from the top of the text to its baseline (in pixels)
+    pub fn ascent(&self) -> u16 {
+        self.ascent
+    }
+
+    /// Returns the coverage of the pixels (row major)
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// Creates an empty coverage
+    pub(crate) fn blank(width: u16, height: u16, ascent: u16) -> Self {
+        Self { width, height, ascent, values: vec![0; width as usize * height as usize] }
+    }
+
+    /// Copies another coverage at the given position (the highest coverage is kept, clipped to the coverage)
+    pub(crate) fn blit(&mut self, x: i32, y: i32, other: &Coverage) {
+        for (row, line) in other.values.chunks(other.width.max(1) as usize).enumerate() {
+            let py = y + row as i32;
+            if py < 0 || py >= self.height as i32 {
+                continue;
+            }
+            for (column, value) in line.iter().enumerate() {
+                let px = x + column as i32;
+                if px < 0 || px >= self.width as i32 {
+                    continue;
+                }
+                let pixel = &mut self.values[py as usize * self.width as usize + px as usize];
+                *pixel = (*pixel).max(*value);
+            }
+        }
+    }
+}
+
+/// The rasterized glyphs of a font, by glyph and pixel size
+type GlyphCache = HashMap<(GlyphId, u16), Rc<RasterGlyph>>;
+
+/// Represents a TrueType or OpenType font, rasterized client-side
+///
+/// Cloning the font is cheap, the clones share the font data and the glyph cache.
+#[derive(Clone)]
+pub struct TrueTypeFont {
+    font: FontArc,
+    glyphs: Rc<RefCell<GlyphCache>>,
+}
+
+impl std::fmt::Debug for TrueTypeFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrueTypeFont")
+            .field("cached_glyphs", &self.cached_glyphs())
+            .finish()
+    }
+}
+
+impl PartialEq for TrueTypeFont {
+    /// Fonts are equal if they share the same glyph cache (clones of the same font).
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.glyphs, &other.glyphs)
+    }
+}
+
+impl TrueTypeFont {
+    /// Loads a font from the content of a TTF or OTF file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not a valid font.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        let font = FontArc::try_from_vec(data).map_err(|_| "Invalid TrueType font")?;
+        Ok(Self {
+            font,
+            glyphs: Rc::new(RefCell::new(HashMap::new())),
+        })
+    }
+
+    /// Loads a font from a TTF or OTF file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or is not a valid font.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use xoverlay::TrueTypeFont;
+    ///
+    /// let font = TrueTypeFont::open("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf").unwrap();
+    /// assert_eq!(font.rasterize("Hello", 16).height(), font.rasterize("World", 16).height());
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Returns the number of glyphs in the glyph cache
+    pub fn cached_glyphs(&self) -> usize {
+        self.glyphs.borrow().len()
+    }
+
+    /// Returns the scale of the font for a pixel size (the height of the em square)
+    fn scale(&self, pixel_size: u16) -> PxScale {
+        let units_per_em = self.font.units_per_em().unwrap_or(1.0);
+        PxScale::from(pixel_size as f32 * self.font.height_unscaled() / units_per_em)
+    }
+
+    /// Returns true if the font has a glyph for the character
+    pub fn contains(&self, c: char) -> bool {
+        self.font.glyph_id(c) != GlyphId(0)
+    }
+
+    /// Returns the advance of a character (in pixels, without kerning)
+    pub fn advance(&self, c: char, pixel_size: u16) -> f32 {
+        self.font.as_scaled(self.scale(pixel_size)).h_advance(self.glyph_id(c))
+    }
+
+    /// Returns the ascent and the height (ascent plus descent) of a line of text (in pixels)
+    pub fn line_metrics(&self, pixel_size: u16) -> (u16, u16) {
+        let scaled = self.font.as_scaled(self.scale(pixel_size));
+        let ascent = scaled.ascent().ceil().max(0.0) as u16;
+        (ascent, ascent.saturating_add((-scaled.descent()).ceil().max(0.0) as u16))
+    }
+
+    /// Returns the glyph of a character (the replacement glyph if the font does not have it)
+    fn glyph_id(&self, c: char) -> GlyphId {
+        match self.font.glyph_id(c) {
+            GlyphId(0) => self.font.glyph_id(REPLACEMENT_CHARACTER),
+            id => id,
+        }
+    }
+
+    /// Rasterizes a glyph (or returns the cached one)
+    fn glyph(&self, id: GlyphId, pixel_size: u16) -> Rc<RasterGlyph> {
+        if let Some(glyph) = self.glyphs.borrow().get(&(id, pixel_size)) {
+            return glyph.clone();
+        }
+
+        let glyph = id.with_scale_and_position(self.scale(pixel_size), point(0.0, 0.0));
+        let raster = match self.font.outline_glyph(glyph) {
+            Some(outline) => {
+                let bounds = outline.px_bounds();
+                let width = bounds.width() as u32;
+                let mut coverage = vec![0; width as usize * bounds.height() as usize];
+                outline.draw(|x, y, value| {
+                    if let Some(pixel) = coverage.get_mut((y * width + x) as usize) {
+                        *pixel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    }
+                });
+                RasterGlyph {
+                    left: bounds.min.x as i32,
+                    top: bounds.min.y as i32,
+                    width,
+                    coverage,
+                }
+            }
+            // Blank glyphs (spaces)
+            None => RasterGlyph { left: 0, top: 0, width: 0, coverage: Vec::new() },
+        };
+
+        let raster = Rc::new(raster);
+        self.glyphs.borrow_mut().insert((id, pixel_size), raster.clone());
+        raster
+    }
+
+    /// Rasterizes a text on a single line
+    ///
+    /// The glyphs are placed on whole pixels (with kerning), so they can be cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text
+    /// * `pixel_size` - The height of the em square (in pixels)
+    ///
+    /// # Returns
+    ///
+    /// The coverage of the text, its height is the ascent plus the descent of the font
+    pub fn rasterize(&self, text: &str, pixel_size: u16) -> Coverage {
+        let scaled = self.font.as_scaled(self.scale(pixel_size));
+        let (ascent, height) = self.line_metrics(pixel_size);
+
+        // Place the glyphs on the baseline
+        let mut pen = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        let mut placed = Vec::new();
+        for c in text.chars() {
+            let id = self.glyph_id(c);
+            if let Some(previous) = previous {
+                pen += scaled.kern(previous, id);
+            }
+            placed.push((pen.round() as i32, self.glyph(id, pixel_size)));
+            pen += scaled.h_advance(id);
+            previous = Some(id);
+        }
+
+        let width = pen.ceil().clamp(0.0, u16::MAX as f32) as u16;
+        let mut values = vec![0u8; width as usize * height as usize];
+        for (x, glyph) in placed {
+            if glyph.width == 0 {
+                continue;
+            }
+            for (row, line) in glyph.coverage.chunks(glyph.width as usize).enumerate() {
+                let y = ascent as i32 + glyph.top + row as i32;
+                if y < 0 || y >= height as i32 {
+                    continue;
+                }
+                for (column, value) in line.iter().enumerate() {
+                    let px = x + glyph.left + column as i32;
+                    if px < 0 || px >= width as i32 {
+                        continue;
+                    }
+                    let pixel = &mut values[y as usize * width as usize + px as usize];
+                    *pixel = (*pixel).max(*value);
+                }
+            }
+        }
+
+        Coverage { width, height, ascent, values }
+    }
+}
+
+/// Blends a color over another one
+///
+/// # Arguments
+///
+/// * `under` - The `[r, g, b, a]` components of the color below
+/// * `over` - The `[r, g, b, a]` components of the color above
+/// * `coverage` - The coverage of the color above (0 keeps the color below)
+pub(crate) fn blend(under: [u8; 4], over: [u8; 4], coverage: u8) -> [u8; 4] {
+    let mut color = [0; 4];
+    for channel in 0..4 {
+        let (under, over) = (under[channel] as u32, over[channel] as u32);
+        color[channel] = ((over * coverage as u32 + under * (255 - coverage as u32) + 127) / 255) as u8;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let (black, white) = ([0, 0, 0, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(blend(black, white, 0), black);
+        assert_eq!(blend(black, white, 0xFF), white);
+        assert_eq!(blend(black, white, 0x80), [0x80, 0x80, 0x80, 0xFF]);
+
+        // Over a transparent pixel, the coverage becomes the alpha
+        assert_eq!(blend([0; 4], white, 0x40)[3], 0x40);
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut coverage = Coverage::blank(3, 2, 1);
+        let dot = Coverage { width: 2, height: 1, ascent: 1, values: vec![0x80, 0xFF] };
+        coverage.blit(2, 1, &dot);
+        coverage.blit(-1, 1, &dot);
+        assert_eq!(coverage.values(), &[0, 0, 0, 0xFF, 0, 0x80]);
+    }
+
+    #[test]
+    fn test_invalid_font() {
+        assert!(TrueTypeFont::from_bytes(vec![0; 16]).is_err());
+        assert!(TrueTypeFont::open("/nonexistent/font.ttf").is_err());
+    }
+}
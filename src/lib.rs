@@ -131,7 +131,6 @@
 //! - Update and improve the documentation
 //! - Add text support (with font)
 //! - Add more shapes (circle, triangle, etc.)
-//! - Optimize the drawing system regarding the number of shapes
 //! - Add more event (resize, etc.)
 //! - Add more examples
 //! - Use compositor for transparency
@@ -140,15 +139,24 @@
 //! - Create github action for CI (build, test, etc.)
 //! - Improve the error handling
 
+/// Codec module is used to convert images (X11 images, PPM, BMP, PNG)
+mod codec;
+
 /// Color module is used to define color for the shapes
 mod color;
 
+/// Damage module is used to track the regions of the overlay to redraw
+mod damage;
+
 /// Drawable module is used to define the drawable object (window, pixmap, etc.)
 mod drawable;
 
 /// Event module is used to define the event system
 pub mod event;
 
+/// Font module is used to describe the core fonts of the texts (Unicode glyphs and fallback fonts)
+mod font;
+
 /// Key module is used to define the key event
 pub mod key;
 
@@ -164,33 +172,71 @@ mod overlay;
 /// Currently it is used to define the window find by name functions
 mod utils;
 
+/// Render queue module is used to keep the shapes of the overlay in drawing order (layers)
+mod render_queue;
+
+/// Render module is used to draw anti-aliased shapes with the XRender extension
+#[cfg(feature = "render")]
+mod render;
+
 /// Shape module is used to define the shape object
 /// Current shapes are:
 ///    - Rectangle
 ///         - Fill
 ///         - Stroke
+///         - Gradient (linear or radial), stipple pattern or image tile
 ///    - Arc
 ///         - Fill
 ///         - Stroke
 ///         - Circle
 ///             - Fill
 ///             - Stroke
+///         - Ring (donut segment)
+///    - Line
+///         - Polyline
+///    - Polygon
+///         - Fill (even-odd or winding rule)
+///         - Stroke
+///         - Gradient (linear or radial), stipple pattern or image tile
+///    - Path (lines, curves and arcs, SVG path data)
+///         - Fill
+///         - Stroke
+///    - Image (PPM, PGM, BMP, PNG with the `png` feature)
+///    - Text (Unicode, with fallback fonts, multi-line with wrapping and alignment, TrueType fonts with the `truetype` feature)
 pub mod shape;
 
+/// Export Bitmap struct from codec module (content of the image shapes)
+pub use codec::Bitmap;
+
 /// Export Color enum from color module
 pub use color::Color;
 
+/// Export font set and font description from font module (fonts used by the texts)
+pub use font::{FontSet, FontSpec, FontWeight, Glyphs, DEFAULT_PIXEL_SIZE, REPLACEMENT_CHARACTER};
+
+/// Export TrueType font from font module (texts rasterized client-side)
+#[cfg(feature = "truetype")]
+pub use font::truetype::{Coverage, TrueTypeFont};
+
+/// Export Rect struct from math module (shapes bounds)
+pub use math::rect::Rect;
+
 /// Export Window Find functions from utils module
 pub use utils::find_window_by_name;
 
 /// Export drawable object from drawable module
 pub use drawable::{
-    window::{Mapping, Window},
+    canvas::Canvas,
+    window::{Mapping, Transparency, Window},
     Drawable,
 };
 
 /// Export Overlay object from overlay module
-pub use overlay::{Overlay, Parent, ResizePolicy};
+pub use overlay::{Overlay, Parent, ResizePolicy, ShapeId, DEFAULT_LAYER};
+
+/// Export rendering quality from render module
+#[cfg(feature = "render")]
+pub use render::Quality;
 
 /// Re-export x11rb crate to allow to use it in the lib
 pub use x11rb;
@@ -4,5 +4,7 @@
 //! 
 //! Currently implemented objects are:
 //!    - Euclidean space vector
+//!    - Axis aligned rectangle
 
+pub mod rect;
 pub mod vec;
@@ -0,0 +1,188 @@
+//! Contains the Rect struct and its implementations
+//!
+//! This module contains the Rect struct, an axis aligned rectangle expressed in pixels.
+//!
+//! Rect is used to describe the area covered by a shape on a drawable (bounding box),
+//! and to compute the regions of the overlay that need to be redrawn.
+
+#![allow(dead_code)]
+
+use x11rb::protocol::xproto::Rectangle as XRectangle;
+
+use super::vec::Vec2f;
+
+/// Represents an axis aligned rectangle in pixels
+///
+/// The rectangle is described by its top left corner and its size.
+/// A rectangle with a null (or negative) width or height is considered empty.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// Returns a new Rect with the given position and size
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    /// Returns the smallest Rect containing the given real (pixel) coordinate and size
+    ///
+    /// # Arguments
+    ///
+    /// * `coord` - The top left corner of the area (in pixels)
+    /// * `size` - The size of the area (in pixels)
+    ///
+    pub fn from_real(coord: Vec2f, size: Vec2f) -> Self {
+        let x = coord.x.floor() as i32;
+        let y = coord.y.floor() as i32;
+        let right = (coord.x + size.x).ceil() as i32;
+        let bottom = (coord.y + size.y).ceil() as i32;
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Returns the x coordinate of the right edge (exclusive)
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    /// Returns the y coordinate of the bottom edge (exclusive)
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    /// Returns true if the rectangle does not cover any pixel
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// Returns true if the given pixel is inside the rectangle
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Returns true if both rectangles share at least one pixel
+    pub fn intersects(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    /// Returns the intersection of both rectangles, if any
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        Some(Rect::new(x, y, right - x, bottom - y))
+    }
+
+    /// Returns the smallest rectangle containing both rectangles
+    ///
+    /// Empty rectangles are ignored.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Returns the rectangle grown by `margin` pixels on every side
+    pub fn inflate(&self, margin: i32) -> Rect {
+        Rect::new(
+            self.x - margin,
+            self.y - margin,
+            self.width + 2 * margin,
+            self.height + 2 * margin,
+        )
+    }
+}
+
+/// Implements convertion to the x11 rectangle (values are saturated to the x11 ranges)
+impl From<Rect> for XRectangle {
+    fn from(rect: Rect) -> Self {
+        XRectangle {
+            x: rect.x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            y: rect.y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            width: rect.width.clamp(0, u16::MAX as i32) as u16,
+            height: rect.height.clamp(0, u16::MAX as i32) as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_real() {
+        let rect = Rect::from_real(Vec2f::new(1.5, 2.2), Vec2f::new(3.0, 3.0));
+        assert_eq!(rect, Rect::new(1, 2, 4, 4));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(Rect::new(0, 0, 0, 10).is_empty());
+        assert!(Rect::new(0, 0, 10, -1).is_empty());
+        assert!(!Rect::new(0, 0, 1, 1).is_empty());
+    }
+
+    #[test]
+    fn test_intersection() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(5, 5, 10, 10);
+        let c = Rect::new(10, 0, 5, 5);
+
+        assert_eq!(a.intersection(&b), Some(Rect::new(5, 5, 5, 5)));
+        assert_eq!(a.intersection(&c), None);
+        assert!(!a.intersects(&c));
+    }
+
+    #[test]
+    fn test_union() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(5, 5, 10, 10);
+
+        assert_eq!(a.union(&b), Rect::new(0, 0, 15, 15));
+        assert_eq!(a.union(&Rect::default()), a);
+    }
+
+    #[test]
+    fn test_contains() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert!(a.contains(0, 0));
+        assert!(a.contains(9, 9));
+        assert!(!a.contains(10, 5));
+    }
+
+    #[test]
+    fn test_to_xrectangle() {
+        let rect: XRectangle = Rect::new(-5, 40000, 70000, 3).into();
+        assert_eq!(rect.x, -5);
+        assert_eq!(rect.y, i16::MAX);
+        assert_eq!(rect.width, u16::MAX);
+        assert_eq!(rect.height, 3);
+    }
+}
@@ -40,23 +40,48 @@ use x11rb::{
     protocol::{
         shape::{self as shape, ConnectionExt as ShapeConnectionExt},
         xproto::{
-            ConnectionExt, Drawable as XDrawable, FontWrapper, Fontable, Window as XWindow
+            ClipOrdering, ConnectionExt, Drawable as XDrawable, Fontable, Rectangle as XRectangle, Window as XWindow
         },
     },
     rust_connection::RustConnection,
 };
 
 use crate::{
-    color::Depth, drawable::{
-        pixmap::Pixmap,
-        window::{Mapping, Window},
+    codec::ximage, color::Depth, damage::Damage, drawable::{
+        buffer::BackBuffer,
+        canvas::Canvas,
+        window::{Mapping, Transparency, Window},
         Drawable,
-    }, event::Event, math::vec::Vec2, shape::{
-        coord::{Anchor, Coord, Size}, GcontextWrapperExt, Rectangle, Shape
+    }, event::Event, font::{FontRegistry, FontSet, FontSpec}, math::{rect::Rect, vec::{Vec2, Vec2f}}, render_queue::{RenderItem, RenderQueue}, shape::{
+        coord::{Coord, CoordExt}, Batch, BatchState, Fill, GcontextWrapperExt, LineStyle, Pattern, Shape, XColor
     }, utils, Color
 };
 
-const SELECTED_FONT: &str = "-misc-fixed-*";
+#[cfg(feature = "render")]
+use crate::render::{tessellate, Quality, Renderer};
+
+pub use crate::render_queue::{ShapeId, DEFAULT_LAYER};
+
+/// Candidates for the primary font of the texts (the first available one is used)
+const SELECTED_FONTS: &[&str] = &[
+    "-misc-fixed-medium-r-semicondensed--13-*-*-*-*-*-iso10646-1",
+    "-misc-fixed-*",
+    "fixed",
+];
+
+/// Fonts used for the characters missing from the primary font, in order (skipped if not available)
+///
+/// Named fonts with a broad coverage come first, so the fallback glyphs are the same on every server.
+/// The wildcard pattern is the last resort, it opens whatever font the server lists first.
+const FALLBACK_FONTS: &[&str] = &[
+    "-misc-fixed-medium-r-normal--13-*-*-*-*-*-iso10646-1",
+    "-gnu-unifont-medium-r-normal--*-*-*-*-*-*-iso10646-1",
+    "-*-*-*-*-*--13-*-*-*-*-*-iso10646-1",
+];
+
+/// Extra pixels added around the shapes bounds when computing the damage
+/// (covers the outline drawn by stroked shapes)
+const DAMAGE_MARGIN: i32 = 1;
 
 /// The overlay struct
 /// 
@@ -71,16 +96,27 @@ where
     parent: Window,
     /// The overlay window
     window: Window,
-    /// The render queue (shapes to draw)
-    render_queue: Vec<Rc<RefCell<dyn Shape<C>>>>,
+    /// The render queue (shapes to draw, sorted by layer z-order)
+    render_queue: RenderQueue<C>,
+    /// The regions to redraw that are not tracked by a shape (removed shapes, resize, ...)
+    damage: Damage,
+    /// The input region last applied to the window (None if never applied)
+    input_region: Option<Vec<Rect>>,
     /// The last mouse position
     last_mouse_pos: Coord,
-    /// The selected font
-    font: FontWrapper<Rc<C>>,
+    /// The fonts of the texts (opened on demand)
+    fonts: FontRegistry<C>,
+    /// The anti-aliased renderer of the color buffer (None for core protocol drawing)
+    #[cfg(feature = "render")]
+    renderer: Option<Renderer<C>>,
+    /// The off-screen buffers (color and shape mask) reused across frames
+    buffer: BackBuffer<C>,
     /// The debounce table
     debounce_table: [std::time::Instant; Event::DB_SIZE],
     /// The resize policy
     resize_policy: ResizePolicy,
+    /// Whether translucent shapes are dithered in the shape mask
+    dithering: bool,
 }
 
 pub enum Parent<'a> {
@@ -100,6 +136,29 @@ pub enum ResizePolicy {
     KeepBoth,
 }
 
+impl ResizePolicy {
+    /// Returns the factors applied to the coordinates and sizes of the shapes (in percentage of the window)
+    /// when the window is resized
+    /// 
+    /// The axes kept by the policy are scaled so that they keep their size in pixels,
+    /// the others keep their percentage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `previous` - The previous size of the window
+    /// * `size` - The new size of the window
+    /// 
+    pub fn scale(&self, previous: Vec2<u16>, size: Vec2<u16>) -> Vec2f {
+        let keep = |previous: u16, size: u16| previous as f32 / size.max(1) as f32;
+        match self {
+            ResizePolicy::KeepAspectRatio => Vec2f::new(1.0, 1.0),
+            ResizePolicy::KeepWidth => Vec2f::new(keep(previous.x, size.x), 1.0),
+            ResizePolicy::KeepHeight => Vec2f::new(1.0, keep(previous.y, size.y)),
+            ResizePolicy::KeepBoth => Vec2f::new(keep(previous.x, size.x), keep(previous.y, size.y)),
+        }
+    }
+}
+
 impl Overlay<RustConnection> {
 
     /// Initialize a new overlay, binding it to the parent window
@@ -129,6 +188,48 @@ impl Overlay<RustConnection> {
         parent: Parent,
         mapping: &Mapping,
         host: Option<&str>,
+    ) -> Result<Overlay<RustConnection>, Box<dyn Error>> {
+        Overlay::init_with_transparency(parent, mapping, host, Transparency::default())
+    }
+
+    /// Initialize a new overlay, choosing how its transparent parts are handled
+    ///
+    /// With `Transparency::Argb`, the overlay uses an ARGB visual when a compositing manager is running,
+    /// so `Color::RGBA` colors are translucent. Without compositing manager, it falls back to the XShape
+    /// extension (`Transparency::Shape`).
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - The parent window
+    /// * `mapping` - The overlay mapping
+    /// * `host` - The host to connect to (if None, connect to $DISPLAY)
+    /// * `transparency` - How the transparent parts are handled
+    ///
+    /// # Returns
+    ///
+    /// A new overlay struct
+    ///
+    /// # Errors
+    ///
+    /// If the overlay could not be created
+    ///
+    /// # Example
+    /// 
+    /// ```no_run
+    /// use xoverlay::{Mapping, Overlay, Parent, Transparency};
+    /// let parent = 0x12345678; // The parent window id
+    /// let overlay = Overlay::init_with_transparency(Parent::Id(parent), &Mapping::FullScreen, None, Transparency::Argb).unwrap();
+    /// 
+    /// if !overlay.window().is_argb() {
+    ///     println!("No compositing manager, RGBA colors are opaque");
+    /// }
+    /// ```
+    /// 
+    pub fn init_with_transparency(
+        parent: Parent,
+        mapping: &Mapping,
+        host: Option<&str>,
+        transparency: Transparency,
     ) -> Result<Overlay<RustConnection>, Box<dyn Error>> {
         // Create a new connection
         let (conn, screen_num) = x11rb::connect(host)?;
@@ -149,7 +250,7 @@ impl Overlay<RustConnection> {
             }
         };
 
-        Overlay::init_with_conn(parent_id, mapping, conn, screen_num)
+        Overlay::init_with_conn_and_transparency(parent_id, mapping, conn, screen_num, transparency)
     }
 }
 
@@ -158,6 +259,13 @@ where
     C: Connection,
 {
     pub fn init_with_conn(parent: XWindow, mapping: &Mapping, conn: C, screen_num: usize) -> Result<Self, Box<dyn Error>>
+    {
+        Self::init_with_conn_and_transparency(parent, mapping, conn, screen_num, Transparency::default())
+    }
+
+    /// Initialize a new overlay on an existing connection, choosing how its transparent parts are handled
+    /// (see `init_with_transparency`)
+    pub fn init_with_conn_and_transparency(parent: XWindow, mapping: &Mapping, conn: C, screen_num: usize, transparency: Transparency) -> Result<Self, Box<dyn Error>>
     {
         // Encapsulate the connection
         let conn = Rc::new(conn);
@@ -167,33 +275,53 @@ where
 
         // Create a new window
         let parent = Window::from(&conn, parent, root)?;
-        let window = Window::new(&conn, &parent, mapping)?;
+        let window = Window::new_with_transparency(&conn, &parent, mapping, transparency)?;
+
+        // Open the default fonts
+        let fonts = FontRegistry::new(&conn, SELECTED_FONTS, FALLBACK_FONTS)?;
 
-        // Create a new font
-        let font = FontWrapper::open_font(conn.clone(), SELECTED_FONT.as_bytes())?;
+        // Create the off-screen buffers
+        let buffer = BackBuffer::new(&conn, &window, Some(fonts.default_fonts().clone()))?;
+
+        // The whole window needs to be drawn at least once
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, window.width() as i32, window.height() as i32));
 
         // Create the overlay
-        Ok(Self {
+        let mut overlay = Self {
             conn,
             parent,
             window,
-            render_queue: Vec::new(),
+            render_queue: RenderQueue::new(),
+            damage,
+            input_region: None,
             last_mouse_pos: Coord::new(0.0, 0.0),
-            font,
+            fonts,
+            #[cfg(feature = "render")]
+            renderer: None,
+            buffer,
             debounce_table: Event::gen_debounce_table(),
             resize_policy: ResizePolicy::default(),
-        })
+            dithering: false,
+        };
+
+        // The overlay is click-through until interactive shapes are drawn
+        overlay.update_input_region()?;
+
+        Ok(overlay)
     }
 
     /// Add a shape to the overlay
     /// 
+    /// The shape is added on top of the default layer.
+    /// 
     /// # Arguments
     /// 
     /// * `shape` - The shape to add (any shape implementing the Shape trait)
     /// 
     /// # Returns
     /// 
-    /// The overlay struct
+    /// The identifier of the shape
     /// 
     /// # Example
     /// 
@@ -219,20 +347,21 @@ where
     /// // Add the rectangle to the overlay
     /// overlay.add_shape(rec);
     /// ```
-    pub fn add_shape(&mut self, shape: Rc<RefCell<dyn Shape<C>>>) -> &mut Self {
-        self.render_queue.push(shape);
-        self
+    pub fn add_shape(&mut self, shape: Rc<RefCell<dyn Shape<C>>>) -> ShapeId {
+        self.insert_shape(0, shape)
     }
 
     /// Add multiple shapes to the overlay
     /// 
+    /// The shapes are added on top of the default layer, in order.
+    /// 
     /// # Arguments
     /// 
     /// * `shapes` - An iterator of shapes to add
     /// 
     /// # Returns
     /// 
-    /// The overlay struct
+    /// The identifiers of the shapes
     /// 
     /// # Example
     ///
@@ -259,14 +388,211 @@ where
     /// overlay.add_shapes(to_draw);
     /// ```
     /// 
-    pub fn add_shapes<I>(&mut self, shapes: I) -> &mut Self
+    pub fn add_shapes<I>(&mut self, shapes: I) -> Vec<ShapeId>
     where
         I: IntoIterator<Item = Rc<RefCell<dyn Shape<C>>>>,
     {
-        self.render_queue.extend(shapes);
+        shapes
+            .into_iter()
+            .map(|shape| self.insert_shape(0, shape))
+            .collect()
+    }
+
+    /// Add a shape to a layer of the overlay
+    /// 
+    /// The shape is added on top of the layer.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `layer` - The name of the layer (see `add_layer`)
+    /// * `shape` - The shape to add
+    /// 
+    /// # Returns
+    /// 
+    /// The identifier of the shape
+    /// 
+    /// # Errors
+    /// 
+    /// If the layer does not exist
+    /// 
+    /// # Example
+    /// 
+    /// ```no_run
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Rectangle}, Color, Mapping, Overlay, Parent};
+    /// 
+    /// let mut overlay = Overlay::init(Parent::Name("My Beautiful Window"), &Mapping::FullScreen, None).unwrap();
+    /// 
+    /// // The HUD is always drawn above the default layer
+    /// overlay.add_layer("hud", 10);
+    /// 
+    /// let panel = Rectangle::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 0.1), Color::GRAY).unwrap();
+    /// let marker = Rectangle::fill(Anchor::Center, Coord::new(0.5, 0.05), Size::new(0.1, 0.1), Color::RED).unwrap();
+    /// 
+    /// overlay.add_shape_to_layer("hud", panel).unwrap();
+    /// overlay.add_shape(marker); // Drawn below the panel
+    /// ```
+    /// 
+    pub fn add_shape_to_layer(&mut self, layer: &str, shape: Rc<RefCell<dyn Shape<C>>>) -> Result<ShapeId, Box<dyn Error>> {
+        let layer = self.render_queue.layer_index(layer).ok_or("No layer found")?;
+        Ok(self.insert_shape(layer, shape))
+    }
+
+    /// Add a layer to the overlay (or change the z-order of an existing one)
+    /// 
+    /// Layers with a higher z-order are drawn above the others.
+    /// Layers with the same z-order are drawn in creation order.
+    /// The default layer has a z-order of 0.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `name` - The name of the layer
+    /// * `z` - The z-order of the layer
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    pub fn add_layer(&mut self, name: &str, z: i32) -> &mut Self {
+        self.render_queue.add_layer(name, z, &mut self.damage);
         self
     }
 
+    /// Remove a shape from the overlay
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id` - The identifier of the shape
+    /// 
+    /// # Returns
+    /// 
+    /// The removed shape (None if the identifier is unknown)
+    /// 
+    pub fn remove_shape(&mut self, id: ShapeId) -> Option<Rc<RefCell<dyn Shape<C>>>> {
+        self.render_queue.remove(id, &mut self.damage)
+    }
+
+    /// Get a shape of the overlay
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id` - The identifier of the shape
+    /// 
+    /// # Returns
+    /// 
+    /// The shape (None if the identifier is unknown)
+    /// 
+    pub fn shape(&self, id: ShapeId) -> Option<Rc<RefCell<dyn Shape<C>>>> {
+        self.render_queue.get(id).map(|item| item.shape.clone())
+    }
+
+    /// Draw a shape one step above in its layer
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id` - The identifier of the shape
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If the identifier is unknown
+    /// 
+    pub fn raise(&mut self, id: ShapeId) -> Result<&mut Self, Box<dyn Error>> {
+        self.render_queue.raise(id, &mut self.damage)?;
+        Ok(self)
+    }
+
+    /// Draw a shape one step below in its layer
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id` - The identifier of the shape
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If the identifier is unknown
+    /// 
+    pub fn lower(&mut self, id: ShapeId) -> Result<&mut Self, Box<dyn Error>> {
+        self.render_queue.lower(id, &mut self.damage)?;
+        Ok(self)
+    }
+
+    /// Move a shape to the given position in its layer
+    /// 
+    /// Position 0 is drawn first (bottom of the layer), positions past the end of the layer
+    /// move the shape on top of the layer.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id` - The identifier of the shape
+    /// * `index` - The new position of the shape in its layer
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If the identifier is unknown
+    /// 
+    pub fn move_to_index(&mut self, id: ShapeId, index: usize) -> Result<&mut Self, Box<dyn Error>> {
+        self.render_queue.move_to_index(id, index, &mut self.damage)?;
+        Ok(self)
+    }
+
+    /// Get the topmost visible shape at the given position
+    /// 
+    /// Hidden shapes are ignored.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `coord` - The position to test (relative to the window, like `mouse_coord`)
+    /// 
+    /// # Returns
+    /// 
+    /// The identifier of the shape (None if no shape covers the position)
+    /// 
+    /// # Errors
+    /// 
+    /// If the bounds of a shape could not be computed
+    /// 
+    pub fn shape_at(&self, coord: Coord) -> Result<Option<ShapeId>, Box<dyn Error>> {
+        let size = self.window.size();
+        let point = coord.to_real_coord(size);
+        let (x, y) = (point.x.floor() as i32, point.y.floor() as i32);
+
+        for item in self.render_queue.items().iter().rev() {
+            let shape = item.shape.borrow();
+            if !shape.is_visible() {
+                continue;
+            }
+            let fonts = self.fonts.font_set(shape.font(size).as_ref());
+            if shape.bounds(&self.conn, Some(&fonts), size)?.contains(x, y) {
+                return Ok(Some(item.id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Measure a shape and insert it on top of a layer
+    /// 
+    /// The size of the texts is known before the first frame. If the shape could not be measured,
+    /// it is measured again (and the error is returned) when the overlay is drawn.
+    fn insert_shape(&mut self, layer: usize, shape: Rc<RefCell<dyn Shape<C>>>) -> ShapeId {
+        let size = self.window.size();
+        let fonts = self.fonts.font_set(shape.borrow().font(size).as_ref());
+        let _ = shape.borrow().measure(&self.conn, Some(&fonts), size);
+
+        self.render_queue.insert(layer, shape)
+    }
+
     /// Get the window of the overlay
     pub fn window(&self) -> &Window {
         &self.window
@@ -286,8 +612,91 @@ where
         self
     }
 
+    /// Check if translucent shapes are dithered (see `set_dithering`)
+    pub fn dithering(&self) -> bool {
+        self.dithering
+    }
+
+    /// Approximate the translucency of the shapes with an ordered dithering of the shape mask
+    /// 
+    /// Without a compositing manager, the alpha of `Color::RGBA` colors is dropped.
+    /// With dithering, the alpha of a shape becomes an 8x8 dither pattern in the shape mask:
+    /// a 50% alpha shape lets every other pixel of the windows below show through.
+    /// Only the filled shapes with a plain color (and primitives) are dithered,
+    /// the setting has no effect with an ARGB visual (the compositing manager blends the colors).
+    /// The whole overlay is redrawn on the next draw.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `dithering` - Whether translucent shapes are dithered (disabled by default)
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    pub fn set_dithering(&mut self, dithering: bool) -> &mut Self {
+        if self.dithering == dithering {
+            return self;
+        }
+        self.dithering = dithering;
+        self.invalidate()
+    }
+
+    /// Get the rendering quality of the overlay
+    /// 
+    /// # Returns
+    /// 
+    /// The rendering quality
+    /// 
+    #[cfg(feature = "render")]
+    pub fn quality(&self) -> Quality {
+        match self.renderer {
+            Some(_) => Quality::AntiAliased,
+            None => Quality::Core,
+        }
+    }
+
+    /// Set the rendering quality of the overlay
+    /// 
+    /// With `Quality::AntiAliased`, shapes are drawn with the XRender extension (smooth edges).
+    /// Text is still drawn with the core protocol. The whole overlay is redrawn on the next draw.
+    /// 
+    /// Note: Without ARGB visual, the edges of the shapes are blended with the color buffer
+    /// but the window shape itself stays aliased.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `quality` - The rendering quality
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If the XRender extension is not available
+    /// 
+    #[cfg(feature = "render")]
+    pub fn set_quality(&mut self, quality: Quality) -> Result<&mut Self, Box<dyn Error>> {
+        if quality == self.quality() {
+            return Ok(self);
+        }
+
+        self.renderer = match quality {
+            Quality::Core => None,
+            Quality::AntiAliased => Some(Renderer::new(&self.conn, self.buffer.pixmaps().0)?),
+        };
+
+        Ok(self.invalidate())
+    }
+
     /// Draw the shapes in the overlay
     /// 
+    /// Only the regions damaged since the last draw are repainted (and re-shaped):
+    /// the previous and new bounds of every changed shape, and the regions invalidated
+    /// by the overlay itself (removed shapes, resize, ...).
+    /// Drawing an unchanged overlay does not send any request to the X server.
+    /// 
     /// # Returns
     /// 
     /// The overlay struct
@@ -324,84 +733,156 @@ where
     /// overlay.draw().unwrap();
     /// ```
     /// 
-    pub fn draw(&self) -> Result<&Self, Box<dyn Error>> {
-        // Let's build the shape pixmap
-        let pixmap = Pixmap::new(&self.conn, &self.window, Some(Depth::D1))?;
-
-        // Create the graphics context
-        // let not_transparent_gc = self.conn.generate_id()?;
-        // self.conn.create_gc(
-        //     not_transparent_gc,
-        //     pixmap.id(),
-        //     &CreateGCAux::new().foreground(1),
-        // )?;
-        // let transparent_gc = self.conn.generate_id()?;
-        // self.conn.create_gc(
-        //     transparent_gc,
-        //     pixmap.id(),
-        //     &CreateGCAux::new().foreground(0),
-        // )?;
-
-        // let not_transparent_gc = GcontextWrapperExt::init(
-        //     self.conn.as_ref(),
-        //     pixmap.id(),
-        //     Some(Color::WHITE.value(&pixmap.depth())),
-        //     Some(Color::WHITE.value(&pixmap.depth())),
-        //     Some(self.font.font()),
-        // )?;
-
-        // let transparent_gc = GcontextWrapperExt::init(
-        //     self.conn.as_ref(),
-        //     pixmap.id(),
-        //     Some(Color::BLACK.value(&pixmap.depth())),
-        //     Some(Color::BLACK.value(&pixmap.depth())),
-        //     Some(self.font.font()),
-        // )?;
-
-        // // Draw the shapes
-        // for shape in &self.render_queue {
-        //     let shape = shape.borrow();
-        //     if shape.forground() == &Color::TRANSPARENT {
-        //         shape.draw(&self.conn, &transparent_gc, &pixmap)?;
-        //     } else {
-        //         shape.draw(&self.conn, &not_transparent_gc, &pixmap)?;
-        //     }
-        // }
-
-        let mut shape_gc = GcontextWrapperExt::init(
-            self.conn.as_ref(),
+    pub fn draw(&mut self) -> Result<&mut Self, Box<dyn Error>> {
+        let damage = self.collect_damage()?;
+
+        // Interactivity changes do not damage the overlay
+        self.update_input_region()?;
+
+        if damage.is_empty() {
+            // Nothing changed since the last draw
+            return Ok(self);
+        }
+
+        let damaged_rects: Vec<XRectangle> = damage.rects().iter().map(|rect| (*rect).into()).collect();
+
+        // With an ARGB visual, the compositing manager handles the transparency (no shape mask)
+        let argb = self.window.is_argb();
+        let dither = self.dithering && !argb;
+        if !argb {
+            self.draw_mask(&damage, &damaged_rects)?;
+        }
+
+        // Draw the shapes in the color buffer
+        let (pixmap, gc) = self.buffer.color();
+
+        // Only draw inside the damaged regions
+        gc.set_clip_rectangles(self.conn.as_ref(), damage.rects())?;
+
+        let depth = pixmap.depth();
+        if argb {
+            // Clear the damaged regions (fully transparent)
+            gc.set_foreground(self.conn.as_ref(), Some(Color::TRANSPARENT.value(&depth)))?;
+            self.conn.poly_fill_rectangle(pixmap.id(), gc.gcontext(), &damaged_rects)?;
+        }
+
+        // Transparent parts keep the previous color (they are masked anyway),
+        // unless the transparency is handled by the compositing manager
+        let colors = |shape: &dyn Shape<C>| {
+            let color = |color: &Color| (argb || color != &Color::TRANSPARENT).then(|| color.value(&depth));
+            (color(shape.forground()), color(shape.background()))
+        };
+
+        #[cfg(feature = "render")]
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_clip_rectangles(damage.rects())?;
+            Self::render_shapes_antialiased(
+                &self.conn,
+                self.render_queue.damaged(&damage),
+                gc,
+                pixmap,
+                renderer,
+                &self.fonts,
+                colors,
+                dither,
+            )?;
+        } else {
+            Self::render_shapes(&self.conn, self.render_queue.damaged(&damage), gc, pixmap, &self.fonts, colors, dither)?;
+        }
+
+        #[cfg(not(feature = "render"))]
+        Self::render_shapes(&self.conn, self.render_queue.damaged(&damage), gc, pixmap, &self.fonts, colors, dither)?;
+
+        // Copy the damaged regions of the buffer to the window (the gc clips the copy)
+        let bounds: XRectangle = damage.bounds().into();
+        self.conn.copy_area(
             pixmap.id(),
-            None,
-            None,
-            Some(self.font.font()),
+            self.window.id(),
+            gc.gcontext(),
+            bounds.x,
+            bounds.y,
+            bounds.x,
+            bounds.y,
+            bounds.width,
+            bounds.height,
         )?;
 
-        for shape in self.render_queue.iter() {
-            let shape = shape.borrow();
+        // Flush the connection
+        self.conn.flush()?;
 
-            shape_gc.set_foreground(
-                self.conn.as_ref(),
-                if shape.forground() == &Color::TRANSPARENT {
-                    Some(Color::BLACK.value(&pixmap.depth()))
-                } else {
-                    Some(shape.forground().value(&pixmap.depth()))
-                }
-            )?;
+        Ok(self)
+    }
 
-            shape_gc.set_background(
-                self.conn.as_ref(),
-                if shape.background() == &Color::TRANSPARENT {
-                    Some(Color::BLACK.value(&pixmap.depth()))
-                } else {
-                    Some(shape.background().value(&pixmap.depth()))
-                }
-            )?;
+    /// Apply the area of the visible interactive shapes as the input region of the window
+    /// 
+    /// Mouse events outside of the input region go through the overlay, to the windows below.
+    /// The region is only sent to the server when it changed.
+    /// 
+    /// # Errors
+    /// 
+    /// If the input region could not be applied
+    /// 
+    fn update_input_region(&mut self) -> Result<(), Box<dyn Error>> {
+        let region = self.render_queue.input_region(DAMAGE_MARGIN);
 
-            // Draw the shape
-            shape.draw(&self.conn, &shape_gc, &pixmap)?;
+        if self.input_region.as_ref() == Some(&region) {
+            return Ok(());
         }
 
+        let rects: Vec<XRectangle> = region.iter().map(|rect| (*rect).into()).collect();
+        self.conn.shape_rectangles(
+            shape::SO::SET,
+            shape::SK::INPUT,
+            ClipOrdering::UNSORTED,
+            self.window.id(),
+            0,
+            0,
+            &rects,
+        )?;
+
+        self.input_region = Some(region);
+        Ok(())
+    }
 
+    /// Build the shape mask of the damaged regions and apply it to the window
+    /// 
+    /// # Arguments
+    /// 
+    /// * `damage` - The damaged regions
+    /// * `damaged_rects` - The damaged regions, as X rectangles
+    /// 
+    /// # Errors
+    /// 
+    /// If the mask could not be drawn or applied
+    /// 
+    fn draw_mask(&mut self, damage: &Damage, damaged_rects: &[XRectangle]) -> Result<(), Box<dyn Error>> {
+        let (mask, mask_gc) = self.buffer.mask();
+
+        // Only draw inside the damaged regions
+        mask_gc.set_clip_rectangles(self.conn.as_ref(), damage.rects())?;
+
+        // Clear the damaged regions of the mask
+        mask_gc.set_foreground(self.conn.as_ref(), Some(Color::BLACK.value(&mask.depth())))?;
+        self.conn.poly_fill_rectangle(mask.id(), mask_gc.gcontext(), damaged_rects)?;
+
+        let depth = mask.depth();
+        Self::render_shapes(
+            &self.conn,
+            self.render_queue.damaged(damage),
+            mask_gc,
+            mask,
+            &self.fonts,
+            |shape| {
+                // Transparent parts are removed from the window shape
+                let mask_color = |color: &Color| if color == &Color::TRANSPARENT {
+                    Color::BLACK.value(&depth)
+                } else {
+                    color.value(&depth)
+                };
+                (Some(mask_color(shape.forground())), Some(mask_color(shape.background())))
+            },
+            self.dithering,
+        )?;
 
         // Compute the shape to window
         self.conn.shape_mask(
@@ -410,44 +891,30 @@ where
             self.window.id(),
             0,
             0,
-            pixmap.id(),
-        )?;
-
-        // Free the pixmap
-        pixmap.free(&self.conn)?;
-
-        // Create a new pixmap
-        let pixmap = Pixmap::new(&self.conn, &self.window, None)?;
-
-        // Create the graphics context for the shape
-        // let gc = self.conn.generate_id()?;
-        // self.conn.create_gc(gc, pixmap.id(), &CreateGCAux::new())?;
-        let mut gc = GcontextWrapperExt::init(
-            self.conn.as_ref(),
-            pixmap.id(),
-            None,
-            None,
-            Some(self.font.font()),
+            mask.id(),
         )?;
 
-        // Draw the pixmap to the window
-        for shape in &self.render_queue {
-            let shape = shape.borrow();
-            if shape.forground() != &Color::TRANSPARENT {
-                // Set the color
-                gc.set_foreground(&self.conn, Some(shape.forground().value(&pixmap.depth())))?;
-            }
+        Ok(())
+    }
 
-            if shape.background() != &Color::TRANSPARENT {
-                // Set the background color
-                gc.set_background(&self.conn, Some(shape.background().value(&pixmap.depth())))?;
-            }
+    /// Copy the whole back buffer to the window
+    /// 
+    /// Used when the window content is lost (exposed), the shapes are not redrawn.
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If the buffer could not be copied
+    /// 
+    fn present(&mut self) -> Result<&mut Self, Box<dyn Error>> {
+        let (pixmap, gc) = self.buffer.color();
 
-            // Draw the shape
-            shape.draw(&self.conn, &gc, &pixmap)?;
-        }
+        // Remove the clipping set by the last draw
+        gc.set_clip_rectangles(self.conn.as_ref(), &[])?;
 
-        // Copy the pixmap to the window
         self.conn.copy_area(
             pixmap.id(),
             self.window.id(),
@@ -456,21 +923,350 @@ where
             0,
             0,
             0,
-            self.window.width(),
-            self.window.height(),
+            pixmap.width(),
+            pixmap.height(),
         )?;
 
-        // Free the pixmap
-        pixmap.free(&self.conn)?;
-        // Free the graphics context
-        // self.conn.free_gc(gc)?;
-
-        // Flush the connection
         self.conn.flush()?;
 
         Ok(self)
     }
 
+    /// Draw the shapes on a drawable
+    /// 
+    /// Consecutive shapes sharing the same colors and primitive kind are sent in a single request,
+    /// and the graphics context is only updated when the colors change.
+    /// Shapes filled with a gradient, a pattern or an image are drawn with a tile or a stipple.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `conn` - The X11 connection
+    /// * `items` - The shapes to draw (in order)
+    /// * `gc` - The graphics context used for drawing
+    /// * `drawable` - The drawable to draw on
+    /// * `fonts` - The fonts of the texts (selected in the graphics context before drawing a text)
+    /// * `colors` - Returns the forground and background colors to use for a shape (None keeps the current one)
+    /// * `dither` - Whether translucent shapes are drawn with a dither pattern
+    /// 
+    /// # Errors
+    /// 
+    /// If the shapes could not be drawn
+    /// 
+    fn render_shapes<'a, F>(
+        conn: &Rc<C>,
+        items: impl Iterator<Item = &'a RenderItem<C>>,
+        gc: &mut GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        fonts: &FontRegistry<C>,
+        colors: F,
+        dither: bool,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        C: 'a,
+        F: Fn(&dyn Shape<C>) -> (Option<XColor>, Option<XColor>),
+    {
+        let mut batch = Batch::new();
+        let default_style = LineStyle::default();
+
+        for item in items {
+            let shape = item.shape.borrow();
+
+            let (fg, bg) = colors(&*shape);
+            let style = shape.line_style().unwrap_or(&default_style);
+            let rule = shape.fill_rule().unwrap_or_default();
+            let mode = shape.arc_mode().unwrap_or_default();
+
+            // The pending primitives must be drawn with the previous colors, line style, fill rule and arc mode
+            let state = BatchState {
+                foreground: fg.or(gc.foreground()),
+                background: bg.or(gc.background()),
+                line_style: style.clone(),
+                fill_rule: rule,
+                arc_mode: mode,
+            };
+            if let Some(pending) = batch.set_state(state) {
+                Batch::send(conn.as_ref(), gc, drawable, &pending)?;
+            }
+
+            // Only the values that differ from the graphics context state are sent
+            gc.set_foreground(conn.as_ref(), fg)?;
+            gc.set_background(conn.as_ref(), bg)?;
+            gc.set_line_style(conn.as_ref(), style)?;
+            gc.set_fill_rule(conn.as_ref(), rule)?;
+            gc.set_arc_mode(conn.as_ref(), mode)?;
+
+            let dithered = dither.then(|| Self::dither_fill(&*shape)).flatten();
+            match (shape.primitives(drawable.size()), dithered, shape.fill_style()) {
+                // The dither pattern starts at the origin of the window (overlapping shapes share it)
+                (Some(primitives), Some(fill), _) => {
+                    batch.flush(conn.as_ref(), gc, drawable)?;
+                    let rect = Rect::new(0, 0, drawable.width() as i32, drawable.height() as i32);
+                    fill.draw(conn.as_ref(), gc, drawable, rect, &primitives)?;
+                }
+                // Gradients, stipples and tiles change the fill style of the graphics context
+                (Some(primitives), None, Some(fill)) if fill != &Fill::Solid => {
+                    batch.flush(conn.as_ref(), gc, drawable)?;
+                    let rect = crate::shape::real_box(&*shape, drawable.size());
+                    fill.draw(conn.as_ref(), gc, drawable, rect, &primitives)?;
+                }
+                (Some(primitives), ..) => {
+                    for primitive in primitives {
+                        batch.push(conn.as_ref(), gc, drawable, primitive)?;
+                    }
+                }
+                (None, ..) => {
+                    batch.flush(conn.as_ref(), gc, drawable)?;
+                    gc.set_font(conn.as_ref(), Some(fonts.font_set(shape.font(drawable.size()).as_ref())))?;
+                    shape.draw(conn, gc, drawable)?;
+                }
+            }
+        }
+
+        batch.flush(conn.as_ref(), gc, drawable)
+    }
+
+    /// Draw the shapes on a drawable with anti-aliasing
+    /// 
+    /// The primitives of the shapes are tessellated and drawn with XRender,
+    /// shapes without primitives are drawn with the core protocol.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `conn` - The X11 connection
+    /// * `items` - The shapes to draw (in order)
+    /// * `gc` - The graphics context used by the shapes drawn with the core protocol
+    /// * `drawable` - The drawable to draw on
+    /// * `renderer` - The renderer of the drawable
+    /// * `fonts` - The fonts of the texts (selected in the graphics context before drawing a text)
+    /// * `colors` - Returns the forground and background colors to use for a shape (None keeps the current one)
+    /// * `dither` - Whether translucent shapes are drawn with a dither pattern (with the core protocol)
+    /// 
+    /// # Errors
+    /// 
+    /// If the shapes could not be drawn
+    /// 
+    #[cfg(feature = "render")]
+    #[allow(clippy::too_many_arguments)]
+    fn render_shapes_antialiased<'a, F>(
+        conn: &Rc<C>,
+        items: impl Iterator<Item = &'a RenderItem<C>>,
+        gc: &mut GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        renderer: &mut Renderer<C>,
+        fonts: &FontRegistry<C>,
+        colors: F,
+        dither: bool,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        C: 'a,
+        F: Fn(&dyn Shape<C>) -> (Option<XColor>, Option<XColor>),
+    {
+        let default_style = LineStyle::default();
+
+        for item in items {
+            let shape = item.shape.borrow();
+            let (fg, bg) = colors(&*shape);
+            let style = shape.line_style().unwrap_or(&default_style);
+            let rule = shape.fill_rule().unwrap_or_default();
+            let mode = shape.arc_mode().unwrap_or_default();
+
+            let dithered = dither.then(|| Self::dither_fill(&*shape)).flatten();
+            match (shape.primitives(drawable.size()), dithered.as_ref().or(shape.fill_style())) {
+                (Some(primitives), Some(Fill::Gradient(gradient))) => {
+                    let triangles: Vec<_> = primitives
+                        .iter()
+                        .flat_map(|primitive| tessellate::triangles(primitive, style, rule, mode))
+                        .collect();
+                    let rect = crate::shape::real_box(&*shape, drawable.size());
+                    renderer.fill_gradient(&triangles, gradient, rect)?;
+                }
+                // Patterns, images and dithered shapes are drawn with the core protocol
+                (Some(primitives), Some(fill)) if fill != &Fill::Solid => {
+                    gc.set_foreground(conn.as_ref(), fg)?;
+                    gc.set_background(conn.as_ref(), bg)?;
+                    gc.set_line_style(conn.as_ref(), style)?;
+                    gc.set_fill_rule(conn.as_ref(), rule)?;
+                    gc.set_arc_mode(conn.as_ref(), mode)?;
+                    let rect = match dithered {
+                        Some(_) => Rect::new(0, 0, drawable.width() as i32, drawable.height() as i32),
+                        None => crate::shape::real_box(&*shape, drawable.size()),
+                    };
+                    fill.draw(conn.as_ref(), gc, drawable, rect, &primitives)?;
+                }
+                (Some(primitives), _) => {
+                    // No color means the previous color is kept
+                    if fg.is_none() {
+                        continue;
+                    }
+
+                    let triangles: Vec<_> = primitives
+                        .iter()
+                        .flat_map(|primitive| tessellate::triangles(primitive, style, rule, mode))
+                        .collect();
+                    renderer.fill(&triangles, shape.forground())?;
+                }
+                (None, _) => {
+                    gc.set_foreground(conn.as_ref(), fg)?;
+                    gc.set_background(conn.as_ref(), bg)?;
+                    gc.set_line_style(conn.as_ref(), style)?;
+                    gc.set_fill_rule(conn.as_ref(), rule)?;
+                    gc.set_arc_mode(conn.as_ref(), mode)?;
+                    gc.set_font(conn.as_ref(), Some(fonts.font_set(shape.font(drawable.size()).as_ref())))?;
+                    shape.draw(conn, gc, drawable)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collect the damaged regions of the overlay
+    /// 
+    /// The previous and new bounds of every dirty shape are added to the pending damage.
+    /// Shapes are marked as clean and their new bounds are stored.
+    /// 
+    /// # Returns
+    /// 
+    /// The damage to repaint, restricted to the window area
+    /// 
+    /// # Errors
+    /// 
+    /// If the bounds of a shape could not be computed
+    /// 
+    fn collect_damage(&mut self) -> Result<Damage, Box<dyn Error>> {
+        let mut damage = std::mem::take(&mut self.damage);
+        let size = self.window.size();
+
+        let (conn, fonts) = (&self.conn, &self.fonts);
+        self.render_queue.update_bounds(&mut damage, |shape| {
+            let fonts = fonts.font_set(shape.font(size).as_ref());
+            shape.measure(conn, Some(&fonts), size)?;
+            Ok(shape.bounds(conn, Some(&fonts), size)?.inflate(DAMAGE_MARGIN))
+        })?;
+
+        damage.clip(&Rect::new(0, 0, size.x as i32, size.y as i32));
+
+        Ok(damage)
+    }
+
+    /// Returns the dither pattern of a translucent shape (None if the shape is not dithered)
+    /// 
+    /// Only the shapes with a plain color that is neither opaque nor fully transparent are dithered.
+    fn dither_fill(shape: &dyn Shape<C>) -> Option<Fill> {
+        let alpha = shape.forground().rgba()[3];
+        let plain = shape.fill_style().is_none_or(|fill| fill == &Fill::Solid);
+        (plain && alpha > 0 && alpha < u8::MAX).then(|| Fill::stipple(Pattern::dither(alpha)))
+    }
+
+    /// Render the shapes of the overlay on a software canvas
+    /// 
+    /// The canvas has the size of the window, shapes are drawn in the same order as on screen
+    /// (translucent shapes are dithered, see `set_dithering`).
+    /// No request is sent to the X server.
+    /// 
+    /// # Returns
+    /// 
+    /// The canvas holding the color image and the shape mask of the overlay
+    /// 
+    /// # Errors
+    /// 
+    /// If a shape could not be rasterized
+    /// 
+    pub fn snapshot(&self) -> Result<Canvas, Box<dyn Error>> {
+        let mut canvas = Canvas::new(self.window.size());
+        if !self.dithering || self.window.is_argb() {
+            canvas.render(self.render_queue.items().iter().map(|item| &item.shape))?;
+            return Ok(canvas);
+        }
+
+        // Translucent shapes are painted with their dither pattern
+        let window = Rect::new(0, 0, canvas.width() as i32, canvas.height() as i32);
+        for item in self.render_queue.items() {
+            let shape = item.shape.borrow();
+            if !shape.is_visible() {
+                continue;
+            }
+
+            match (Self::dither_fill(&*shape), shape.primitives(canvas.size())) {
+                (Some(fill), Some(primitives)) => {
+                    let mut coverage = Canvas::new(canvas.size());
+                    for primitive in primitives {
+                        coverage.draw_primitive(&primitive, &Color::WHITE);
+                    }
+                    canvas.paint(&coverage, window, &fill, shape.forground(), shape.background());
+                }
+                _ => shape.rasterize(&mut canvas)?,
+            }
+        }
+        Ok(canvas)
+    }
+
+    /// Capture the current frame of the overlay
+    /// 
+    /// The color buffer and the shape mask built by the last `draw` are fetched from the X server.
+    /// With an ARGB visual, the mask holds the pixels that are not fully transparent.
+    /// 
+    /// # Returns
+    /// 
+    /// The canvas holding the color image and the shape mask of the frame
+    /// 
+    /// # Errors
+    /// 
+    /// If the images could not be fetched
+    /// 
+    /// # Example
+    /// 
+    /// ```no_run
+    /// use xoverlay::{Mapping, Overlay, Parent};
+    /// 
+    /// let mut overlay = Overlay::init(Parent::Name("My Beautiful Window"), &Mapping::FullScreen, None).unwrap();
+    /// overlay.draw().unwrap();
+    /// 
+    /// let frame = overlay.capture().unwrap();
+    /// frame.save_ppm("frame.ppm").unwrap();
+    /// frame.save_pbm("mask.pbm").unwrap();
+    /// ```
+    /// 
+    pub fn capture(&self) -> Result<Canvas, Box<dyn Error>> {
+        let (color, mask) = self.buffer.pixmaps();
+
+        let depth = color.depth();
+        let argb = self.window.is_argb();
+        let pixels: Vec<[u8; 4]> = ximage::get_image(self.conn.as_ref(), color)?
+            .into_iter()
+            .map(|value| {
+                let [r, g, b, a] = Color::from_value(value, &depth).rgba();
+                [r, g, b, if argb { a } else { 0xFF }]
+            })
+            .collect();
+
+        let mask = if argb {
+            // No shape mask, the visible parts are the non transparent pixels
+            pixels.iter().map(|pixel| pixel[3] != 0).collect()
+        } else {
+            ximage::get_image(self.conn.as_ref(), mask)?
+                .into_iter()
+                .map(|value| value != 0)
+                .collect()
+        };
+
+        Canvas::from_parts(color.size(), pixels, mask)
+    }
+
+    /// Mark the whole overlay as damaged
+    /// 
+    /// The next draw will repaint every shape.
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    pub fn invalidate(&mut self) -> &mut Self {
+        let size = self.window.size();
+        self.damage.add(Rect::new(0, 0, size.x as i32, size.y as i32));
+        self
+    }
+
     /// Return the last mouse position
     /// 
     /// # Returns
@@ -488,7 +1284,11 @@ where
     /// The overlay struct
     /// 
     fn clear_shapes(&mut self) -> &mut Self {
-        self.render_queue.clear();
+        for item in self.render_queue.drain() {
+            if let Some(bounds) = item.bounds {
+                self.damage.add(bounds);
+            }
+        }
         self
     }
 
@@ -530,18 +1330,9 @@ where
     /// ```
     /// 
     pub fn clear(&mut self) -> Result<&mut Self, Box<dyn Error>> {
-        // Create a new rectangle
-        let clear_rect = Rectangle::fill(
-            Anchor::default(),
-            Coord::new(0.0, 0.0),
-            Size::new(1.0, 1.0),
-            Color::TRANSPARENT,
-        )?;
-
         self.clear_shapes();
-        self.add_shape(clear_rect);
+        self.invalidate();
         self.draw()?;
-        self.clear_shapes();
 
         Ok(self)
     }
@@ -572,55 +1363,27 @@ where
         let previous_size = self.window.size();
         self.window.refresh(&self.conn, Some(&self.parent))?;
 
-        match self.resize_policy {
-            ResizePolicy::KeepAspectRatio => {
-                // Nothing to do
-            }
-            ResizePolicy::KeepWidth => {
-                // Keep the width
-                for shape in self.render_queue.iter_mut() {
-                    let mut shape = shape.borrow_mut();
-                    let size = shape.size();
-                    let pos = shape.position();
-
-                    let new_size_width = size.x * (previous_size.x as f32) / (new_size.x as f32);
-                    let new_pos_width = pos.x * (previous_size.x as f32) / (new_size.x as f32);
-
-                    shape.set_size(Size::new(new_size_width, size.y));
-                    shape.set_position(Coord::new(new_pos_width, pos.y));
-                }
-            }
-            ResizePolicy::KeepHeight => {
-                // Keep the height
-                // Keep the width
-                for shape in self.render_queue.iter_mut() {
-                    let mut shape = shape.borrow_mut();
-                    let size = shape.size();
-                    let pos = shape.position();
-
-                    let new_size_height = size.y * (previous_size.y as f32) / (new_size.y as f32);
-                    let new_pos_height = pos.y * (previous_size.y as f32) / (new_size.y as f32);
-
-                    shape.set_size(Size::new(size.x, new_size_height));
-                    shape.set_position(Coord::new(pos.x, new_pos_height));
-                }
-            }
-            ResizePolicy::KeepBoth => {
-                // Keep the size
-                // Keep the width
-                for shape in self.render_queue.iter_mut() {
-                    let mut shape = shape.borrow_mut();
-                    let size = shape.size();
-                    let pos = shape.position();
-
-                    let size = size.hammard(previous_size.convert()).inv_hammard(new_size.convert());
-                    let pos = pos.hammard(previous_size.convert()).inv_hammard(new_size.convert());
-
-                    shape.set_size(size);
-                    shape.set_position(pos);
-                }
+        // The buffers must match the new window size
+        if self.buffer.size() != self.window.size() {
+            self.buffer = BackBuffer::new(&self.conn, &self.window, Some(self.fonts.default_fonts().clone()))?;
+
+            // The renderer is bound to the previous color buffer
+            #[cfg(feature = "render")]
+            if self.renderer.is_some() {
+                self.renderer = Some(Renderer::new(&self.conn, self.buffer.pixmaps().0)?);
             }
         }
+
+        // Every shape moved (in pixels), repaint the whole window
+        self.invalidate();
+        for item in self.render_queue.items_mut().iter_mut() {
+            item.bounds = None;
+        }
+
+        // Each shape follows the resize policy (circles stay round)
+        for item in self.render_queue.items_mut().iter_mut() {
+            item.shape.borrow_mut().resize(self.resize_policy, previous_size, new_size);
+        }
         Ok(self)
     }
 
@@ -658,6 +1421,9 @@ where
             Event::Redraw => {
                 self.draw()?;
             }
+            Event::Expose => {
+                self.draw()?.present()?;
+            }
             Event::MouseMotion { coord } => {
                 self.last_mouse_pos = coord;
             }
@@ -767,6 +1533,9 @@ where
                     Event::Redraw => {
                         self.draw()?;
                     }
+                    Event::Expose => {
+                        self.draw()?.present()?;
+                    }
                     Event::MouseMotion { coord } => {
                         self.last_mouse_pos = coord;
                     }
@@ -805,7 +1574,12 @@ where
     /// If the overlay could not be freed
     /// 
     fn free(self) -> Result<(), Box<dyn Error>> {
+        // Free the buffers before the window they are bound to
+        #[cfg(feature = "render")]
+        drop(self.renderer);
+        drop(self.buffer);
         self.window.free(&self.conn)?;
+        self.conn.flush()?;
         Ok(())
     }
 
@@ -826,7 +1600,93 @@ where
     /// The font of the overlay
     /// 
     pub fn font(&self) -> Option<Fontable> {
-        Some(self.font.font())
+        self.fonts.default_fonts().primary()
+    }
+
+    /// Get the default fonts of the texts
+    /// 
+    /// # Returns
+    /// 
+    /// The primary font, followed by the fallback fonts
+    /// 
+    pub fn fonts(&self) -> &FontSet {
+        self.fonts.default_fonts()
+    }
+
+    /// Get the fonts used to draw a text
+    /// 
+    /// The font is opened if needed, and kept open until the overlay is dropped.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `spec` - The font of the text (None for the default fonts)
+    /// 
+    /// # Returns
+    /// 
+    /// The font matching the description followed by the default fonts
+    /// (only the default fonts if no font matches the description)
+    /// 
+    pub fn font_set(&self, spec: Option<&FontSpec>) -> FontSet {
+        self.fonts.font_set(spec)
+    }
+
+    /// Open the font matching a description
+    /// 
+    /// Texts open their font when they are drawn, this checks that a font is available beforehand.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `spec` - The font description
+    /// 
+    /// # Returns
+    /// 
+    /// The font matching the description followed by the default fonts
+    /// 
+    /// # Errors
+    /// 
+    /// If no font matches the description
+    /// 
+    /// # Example
+    /// 
+    /// ```no_run
+    /// use xoverlay::{FontSpec, FontWeight, Mapping, Overlay, Parent};
+    /// 
+    /// let overlay = Overlay::init(Parent::Name("My Beautiful Window"), &Mapping::FullScreen, None).unwrap();
+    /// let font = FontSpec::new("fixed", FontWeight::Bold, 18);
+    /// if overlay.load_font(&font).is_err() {
+    ///     println!("{} is not available, the default font will be used", font.xlfd());
+    /// }
+    /// ```
+    pub fn load_font(&self, spec: &FontSpec) -> Result<FontSet, Box<dyn Error>> {
+        self.fonts.open(spec)
+    }
+
+    /// Add a font used for the characters missing from the fonts of the texts
+    /// 
+    /// The font is tried after the primary font and the fallback fonts added before it.
+    /// Every shape is marked dirty, so the texts are measured again with the new font on the next draw.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `pattern` - The name of the font (XLFD, wildcards allowed)
+    /// 
+    /// # Returns
+    /// 
+    /// The overlay struct
+    /// 
+    /// # Errors
+    /// 
+    /// If no font matches the pattern
+    /// 
+    pub fn add_fallback_font(&mut self, pattern: &str) -> Result<&mut Self, Box<dyn Error>> {
+        self.fonts.add_fallback(pattern)?;
+
+        // The texts must be measured again with the new font
+        for item in self.render_queue.items() {
+            item.shape.borrow_mut().set_dirty(true);
+        }
+
+        Ok(self)
     }
 
 }
@@ -855,3 +1715,27 @@ where
         self.window.position()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::shape::{coord::{Anchor, Size}, Text};
+
+    use super::*;
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_add_shape_measures_text() {
+        let (conn, screen_num) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen_num].root;
+        let mut overlay = Overlay::init_with_conn(root, &Mapping::FullScreen, conn, screen_num).unwrap();
+
+        // The size is known before the first frame, whatever the way the text is added
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "Label");
+        overlay.add_shape(text.clone());
+        assert_ne!(Shape::<RustConnection>::size(&*text.borrow()), Size::new(0.0, 0.0));
+
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "Label");
+        overlay.add_shapes([text.clone() as Rc<RefCell<dyn Shape<RustConnection>>>]);
+        assert_ne!(Shape::<RustConnection>::size(&*text.borrow()), Size::new(0.0, 0.0));
+    }
+}
@@ -0,0 +1,340 @@
+//! Render module (behind the `render` feature)
+//!
+//! This module draws anti-aliased shapes with the XRender extension.
+//!
+//! The primitives of the shapes are tessellated into triangles, which are composited on the
+//! color buffer of the overlay with a solid fill picture of the shape color
+//! (or a gradient picture for the shapes filled with a gradient).
+//! Shapes without primitives (like text) are still drawn with the core protocol.
+
+use std::{collections::HashMap, error::Error, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        render::{
+            self, ChangePictureAux, ConnectionExt as _, CreatePictureAux, Fixed, PictOp, PictType, Pictformat,
+            Pictforminfo, Picture, Pointfix, Repeat, Transform, Triangle as XTriangle,
+        },
+        xproto::Rectangle as XRectangle,
+    },
+};
+
+use crate::{
+    color::Depth,
+    drawable::Drawable,
+    math::rect::Rect,
+    shape::{Gradient, GradientKind},
+    Color,
+};
+
+pub mod tessellate;
+
+use tessellate::Triangle;
+
+/// Maximum number of triangles sent in a single request
+const MAX_TRIANGLES: usize = 4096;
+
+/// Size of the box of the gradients in the gradient pictures (keeps the precision of the transform)
+const GRADIENT_SCALE: f32 = 1024.0;
+
+/// Describe how the shapes are rendered
+///
+/// - Core: Shapes are drawn with the core X11 protocol (aliased edges)
+/// - AntiAliased: Shapes are drawn with the XRender extension (smooth edges)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Quality {
+    /// Core protocol drawing
+    #[default]
+    Core,
+    /// Anti-aliased drawing with XRender
+    AntiAliased,
+}
+
+/// Draws anti-aliased triangles on a drawable
+pub struct Renderer<C>
+where
+    C: Connection,
+{
+    /// The X11 connection (used to free the pictures)
+    conn: Rc<C>,
+    /// The picture of the destination drawable
+    picture: Picture,
+    /// The 8-bit alpha format used to rasterize the triangles
+    mask_format: Pictformat,
+    /// The solid fill pictures, by color value
+    fills: HashMap<u32, Picture>,
+}
+
+impl<C> Renderer<C>
+where
+    C: Connection,
+{
+    /// Creates a renderer drawing on the given drawable
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection
+    /// * `drawable` - The destination drawable (usually the color buffer)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the XRender extension is not available
+    /// or has no picture format for the depth of the drawable.
+    pub fn new(conn: &Rc<C>, drawable: &dyn Drawable) -> Result<Self, Box<dyn Error>> {
+        if conn.extension_information(render::X11_EXTENSION_NAME)?.is_none() {
+            Err("XRender extension not available")?;
+        }
+
+        // Solid fill pictures need version 0.10
+        conn.render_query_version(0, 11)?.reply()?;
+
+        let formats = conn.render_query_pict_formats()?.reply()?.formats;
+        let format = find_format(&formats, drawable.depth()).ok_or("No picture format for the drawable depth")?;
+        let mask_format = formats
+            .iter()
+            .find(|info| {
+                info.type_ == PictType::DIRECT
+                    && info.depth == 8
+                    && info.direct.alpha_mask == 0xFF
+                    && info.direct.red_mask == 0
+            })
+            .ok_or("No alpha picture format")?
+            .id;
+
+        let picture = conn.generate_id()?;
+        conn.render_create_picture(picture, drawable.id(), format, &CreatePictureAux::new())?;
+
+        Ok(Self {
+            conn: conn.clone(),
+            picture,
+            mask_format,
+            fills: HashMap::new(),
+        })
+    }
+
+    /// Restricts the drawing to the given regions (no restriction if empty)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent.
+    pub fn set_clip_rectangles(&self, rects: &[Rect]) -> Result<(), Box<dyn Error>> {
+        if rects.is_empty() {
+            let aux = render::ChangePictureAux::new().clipmask(x11rb::NONE);
+            self.conn.render_change_picture(self.picture, &aux)?;
+        } else {
+            let rects: Vec<XRectangle> = rects.iter().map(|rect| (*rect).into()).collect();
+            self.conn.render_set_picture_clip_rectangles(self.picture, 0, 0, &rects)?;
+        }
+        Ok(())
+    }
+
+    /// Fills triangles with a color
+    ///
+    /// The color is blended over the drawable, the transparent color erases the covered pixels.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requests could not be sent.
+    pub fn fill(&mut self, triangles: &[Triangle], color: &Color) -> Result<(), Box<dyn Error>> {
+        if triangles.is_empty() {
+            return Ok(());
+        }
+
+        let (op, fill) = match color {
+            // Remove the covered coverage of the destination (dst * (1 - src.alpha))
+            Color::TRANSPARENT => (PictOp::OUT_REVERSE, self.fill_picture(&Color::BLACK)?),
+            color => (PictOp::OVER, self.fill_picture(color)?),
+        };
+
+        for chunk in to_triangles(triangles).chunks(MAX_TRIANGLES) {
+            self.conn
+                .render_triangles(op, fill, self.picture, self.mask_format, 0, 0, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Fills triangles with a gradient
+    ///
+    /// The gradient is blended over the drawable.
+    ///
+    /// # Arguments
+    ///
+    /// * `triangles` - The triangles to fill
+    /// * `gradient` - The gradient
+    /// * `rect` - The box of the gradient (in pixels)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requests could not be sent.
+    pub fn fill_gradient(&mut self, triangles: &[Triangle], gradient: &Gradient, rect: Rect) -> Result<(), Box<dyn Error>> {
+        if triangles.is_empty() || rect.is_empty() {
+            return Ok(());
+        }
+
+        let picture = self.gradient_picture(gradient, rect)?;
+
+        for chunk in to_triangles(triangles).chunks(MAX_TRIANGLES) {
+            // The source offset is the source pixel of the first point (the gradient is relative to its box)
+            let (x, y) = (chunk[0].p1.x >> 16, chunk[0].p1.y >> 16);
+            self.conn.render_triangles(
+                PictOp::OVER,
+                picture,
+                self.picture,
+                self.mask_format,
+                (x - rect.x) as i16,
+                (y - rect.y) as i16,
+                chunk,
+            )?;
+        }
+
+        self.conn.render_free_picture(picture)?;
+
+        Ok(())
+    }
+
+    /// Creates the picture of a gradient
+    ///
+    /// The gradient is defined on a `GRADIENT_SCALE` square, stretched to the box with the picture transform.
+    /// Outside of the stops, the picture is padded with the nearest color.
+    fn gradient_picture(&self, gradient: &Gradient, rect: Rect) -> Result<Picture, Box<dyn Error>> {
+        let mut stops: Vec<(f32, Color)> = gradient.stops().to_vec();
+        if stops.len() == 1 {
+            // A plain color
+            stops = vec![(0.0, stops[0].1), (1.0, stops[0].1)];
+        }
+
+        let offsets: Vec<Fixed> = stops.iter().map(|(offset, _)| to_fixed(*offset)).collect();
+        // Gradient colors are not premultiplied
+        let colors: Vec<render::Color> = stops
+            .iter()
+            .map(|(_, color)| {
+                let [red, green, blue, alpha] = color.rgba().map(|channel| channel as u16 * 0x101);
+                render::Color { red, green, blue, alpha }
+            })
+            .collect();
+
+        let point = |x: f32, y: f32| to_pointfix(x * GRADIENT_SCALE, y * GRADIENT_SCALE);
+        let picture = self.conn.generate_id()?;
+        match *gradient.kind() {
+            GradientKind::Linear { start, end } => {
+                self.conn.render_create_linear_gradient(
+                    picture,
+                    point(start.x, start.y),
+                    point(end.x, end.y),
+                    &offsets,
+                    &colors,
+                )?;
+            }
+            GradientKind::Radial { center, radius } => {
+                let center = point(center.x, center.y);
+                self.conn.render_create_radial_gradient(
+                    picture,
+                    center,
+                    center,
+                    0,
+                    to_fixed(radius * GRADIENT_SCALE),
+                    &offsets,
+                    &colors,
+                )?;
+            }
+        }
+
+        self.conn
+            .render_change_picture(picture, &ChangePictureAux::new().repeat(Repeat::PAD))?;
+
+        // Maps the pixels of the box (relative to its top left corner) to the gradient square
+        let transform = Transform {
+            matrix11: to_fixed(GRADIENT_SCALE / rect.width as f32),
+            matrix12: 0,
+            matrix13: 0,
+            matrix21: 0,
+            matrix22: to_fixed(GRADIENT_SCALE / rect.height as f32),
+            matrix23: 0,
+            matrix31: 0,
+            matrix32: 0,
+            matrix33: to_fixed(1.0),
+        };
+        self.conn.render_set_picture_transform(picture, transform)?;
+
+        Ok(picture)
+    }
+
+    /// Returns the solid fill picture of a color (created on first use)
+    fn fill_picture(&mut self, color: &Color) -> Result<Picture, Box<dyn Error>> {
+        let value = color.value(&Depth::D32);
+        if let Some(picture) = self.fills.get(&value) {
+            return Ok(*picture);
+        }
+
+        // XRender colors are premultiplied 16-bit channels
+        let channel = |shift: u32| ((value >> shift) & 0xFF) as u16 * 0x101;
+        let render_color = render::Color {
+            red: channel(16),
+            green: channel(8),
+            blue: channel(0),
+            alpha: channel(24),
+        };
+
+        let picture = self.conn.generate_id()?;
+        self.conn.render_create_solid_fill(picture, render_color)?;
+        self.fills.insert(value, picture);
+
+        Ok(picture)
+    }
+}
+
+impl<C> Drop for Renderer<C>
+where
+    C: Connection,
+{
+    /// Frees the pictures
+    fn drop(&mut self) {
+        // Errors are ignored, the connection may already be closed
+        for picture in self.fills.values() {
+            let _ = self.conn.render_free_picture(*picture);
+        }
+        let _ = self.conn.render_free_picture(self.picture);
+    }
+}
+
+/// Finds the picture format matching the visuals used by the overlay (8 bits per channel)
+fn find_format(formats: &[Pictforminfo], depth: Depth) -> Option<Pictformat> {
+    let with_alpha = depth == Depth::D32;
+
+    formats
+        .iter()
+        .find(|info| {
+            info.type_ == PictType::DIRECT
+                && info.depth == depth.value::<u8>()
+                && info.direct.red_shift == 16
+                && info.direct.red_mask == 0xFF
+                && info.direct.green_shift == 8
+                && info.direct.blue_shift == 0
+                && (info.direct.alpha_mask == 0xFF) == with_alpha
+        })
+        .map(|info| info.id)
+}
+
+/// Converts a value to XRender fixed point (16.16)
+fn to_fixed(value: f32) -> Fixed {
+    (value * 65536.0).round() as Fixed
+}
+
+/// Converts a point (in pixels) to XRender fixed point coordinates (16.16)
+fn to_pointfix(x: f32, y: f32) -> Pointfix {
+    Pointfix { x: to_fixed(x), y: to_fixed(y) }
+}
+
+/// Converts triangles (in pixels) to XRender triangles
+fn to_triangles(triangles: &[Triangle]) -> Vec<XTriangle> {
+    triangles
+        .iter()
+        .map(|[p1, p2, p3]| XTriangle {
+            p1: to_pointfix(p1.x, p1.y),
+            p2: to_pointfix(p2.x, p2.y),
+            p3: to_pointfix(p3.x, p3.y),
+        })
+        .collect()
+}
@@ -0,0 +1,349 @@
+//! Tessellation of the primitives
+//!
+//! XRender draws anti-aliased triangles, so every primitive is converted to a list of triangles
+//! covering the same pixels as the core protocol request:
+//! - filled shapes cover the pixels inside their outline (filled arcs follow the arc mode of the shape),
+//! - outlines are one pixel wide and centered on the pixels drawn by the core protocol,
+//! - lines are stroked with the line style of the shape (thin lines are one pixel wide),
+//! - polygons are split into horizontal trapezoids following the fill rule of the shape.
+
+use x11rb::protocol::xproto::{Arc as XArc, Rectangle as XRectangle};
+
+use crate::{
+    math::vec::Vec2f,
+    shape::{ArcMode, Cap, FillRule, LineStyle, Primitive},
+};
+
+/// A triangle (in pixels, the pixel (0, 0) covers the area from (0, 0) to (1, 1))
+pub type Triangle = [Vec2f; 3];
+
+/// Maximum length of the segments approximating a curve (in pixels)
+const SEGMENT_LENGTH: f32 = 1.0;
+
+/// Maximum number of segments approximating a curve
+const MAX_SEGMENTS: usize = 1024;
+
+/// Width of the outlines (in pixels)
+const LINE_WIDTH: f32 = 1.0;
+
+/// Converts a primitive to triangles
+///
+/// # Arguments
+///
+/// * `primitive` - The primitive
+/// * `style` - The line style of the shape (only used by the lines)
+/// * `rule` - The fill rule of the shape (only used by the polygons)
+/// * `mode` - The arc mode of the shape (only used by the filled arcs)
+pub fn triangles(primitive: &Primitive, style: &LineStyle, rule: FillRule, mode: ArcMode) -> Vec<Triangle> {
+    match primitive {
+        Primitive::FillRectangle(rectangle) => {
+            let (x, y, width, height) = rectangle_bounds(rectangle);
+            quad(x, y, x + width, y + height)
+        }
+        Primitive::Rectangle(rectangle) => {
+            // The outline covers `width + 1` by `height + 1` pixels
+            let (x, y, width, height) = rectangle_bounds(rectangle);
+            let (right, bottom) = (x + width + LINE_WIDTH, y + height + LINE_WIDTH);
+
+            let mut triangles = quad(x, y, right, y + LINE_WIDTH);
+            triangles.extend(quad(x, bottom - LINE_WIDTH, right, bottom));
+            triangles.extend(quad(x, y + LINE_WIDTH, x + LINE_WIDTH, bottom - LINE_WIDTH));
+            triangles.extend(quad(right - LINE_WIDTH, y + LINE_WIDTH, right, bottom - LINE_WIDTH));
+            triangles
+        }
+        Primitive::FillArc(arc) => {
+            let (center, radius, start, extent) = arc_geometry(arc, 0.0);
+            let points = ellipse_points(center, radius, start, extent, segments(radius, extent));
+
+            // Pie slice: fan from the center, chord: fan from the first point (the chord is convex)
+            let pivot = match mode {
+                ArcMode::PieSlice => center,
+                ArcMode::Chord => points[0],
+            };
+            points
+                .windows(2)
+                .map(|segment| [pivot, segment[0], segment[1]])
+                .collect()
+        }
+        Primitive::Arc(arc) => {
+            // The path goes through the center of the pixels drawn by the core protocol
+            let (center, radius, start, extent) = arc_geometry(arc, 0.5);
+            let half = LINE_WIDTH / 2.0;
+
+            let (outer, inner) = (radius + Vec2f::new(half, half), radius - Vec2f::new(half, half));
+
+            // Both sides need the same number of points
+            let segments = segments(outer, extent);
+            let outer = ellipse_points(center, outer, start, extent, segments);
+            let inner = ellipse_points(center, inner, start, extent, segments);
+
+            outer
+                .windows(2)
+                .zip(inner.windows(2))
+                .flat_map(|(outer, inner)| [[outer[0], outer[1], inner[0]], [inner[0], outer[1], inner[1]]])
+                .collect()
+        }
+        Primitive::Segment(segment) => stroke(
+            &[
+                Vec2f::new(segment.x1 as f32, segment.y1 as f32),
+                Vec2f::new(segment.x2 as f32, segment.y2 as f32),
+            ],
+            style,
+        ),
+        Primitive::Line(points) => {
+            let points: Vec<Vec2f> = points.iter().map(|point| Vec2f::new(point.x as f32, point.y as f32)).collect();
+            stroke(&points, style)
+        }
+        Primitive::FillPolygon(points, _) => {
+            let points: Vec<Vec2f> = points.iter().map(|point| Vec2f::new(point.x as f32, point.y as f32)).collect();
+            polygon(&points, rule)
+        }
+    }
+}
+
+/// Returns the triangles covering the inside of a polygon
+///
+/// The polygon is cut in horizontal bands at every vertex and edge intersection,
+/// so that the edges crossing a band never cross each other inside of it.
+/// The parts of the bands inside the polygon are trapezoids.
+fn polygon(points: &[Vec2f], rule: FillRule) -> Vec<Triangle> {
+    // Horizontal edges never cross a band
+    let edges: Vec<(Vec2f, Vec2f)> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .filter(|(start, end)| start.y != end.y)
+        .map(|(start, end)| (*start, *end))
+        .collect();
+
+    let mut cuts: Vec<f32> = points.iter().map(|point| point.y).collect();
+    for (index, first) in edges.iter().enumerate() {
+        cuts.extend(edges[index + 1..].iter().filter_map(|second| intersection_y(first, second)));
+    }
+    cuts.sort_by(f32::total_cmp);
+    cuts.dedup();
+
+    let x_at = |(start, end): &(Vec2f, Vec2f), y: f32| start.x + (y - start.y) * (end.x - start.x) / (end.y - start.y);
+
+    let mut triangles = Vec::new();
+    for band in cuts.windows(2) {
+        let (top, bottom) = (band[0], band[1]);
+        let middle = (top + bottom) / 2.0;
+
+        // Left to right crossings of the band, with the direction of the edge
+        let mut crossings: Vec<(f32, f32, f32, i32)> = edges
+            .iter()
+            .filter(|(start, end)| (start.y < middle) != (end.y < middle))
+            .map(|edge| {
+                let direction = if edge.0.y < edge.1.y { 1 } else { -1 };
+                (x_at(edge, middle), x_at(edge, top), x_at(edge, bottom), direction)
+            })
+            .collect();
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].3;
+            if !rule.is_inside(winding) {
+                continue;
+            }
+
+            let (left, right) = (pair[0], pair[1]);
+            let (top_left, top_right) = (Vec2f::new(left.1, top), Vec2f::new(right.1, top));
+            let (bottom_left, bottom_right) = (Vec2f::new(left.2, bottom), Vec2f::new(right.2, bottom));
+            triangles.push([top_left, top_right, bottom_right]);
+            triangles.push([top_left, bottom_right, bottom_left]);
+        }
+    }
+
+    triangles
+}
+
+/// Returns the height at which two edges cross (None if they don't cross)
+fn intersection_y((a, b): &(Vec2f, Vec2f), (c, d): &(Vec2f, Vec2f)) -> Option<f32> {
+    let (first, second) = (*b - *a, *d - *c);
+    let denominator = first.x * second.y - first.y * second.x;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let offset = *c - *a;
+    let t = (offset.x * second.y - offset.y * second.x) / denominator;
+    let u = (offset.x * first.y - offset.y * first.x) / denominator;
+
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(a.y + t * first.y)
+}
+
+/// Returns the triangles covering the stroke of a polyline
+fn stroke(points: &[Vec2f], style: &LineStyle) -> Vec<Triangle> {
+    let thin = style.width <= 1;
+    let points: Vec<Vec2f> = match thin {
+        // The path goes through the center of the pixels drawn by the core protocol
+        true => points.iter().map(|point| *point + Vec2f::new(0.5, 0.5)).collect(),
+        false => points.to_vec(),
+    };
+
+    // Thin lines include both end pixels
+    let style = match thin {
+        true => LineStyle {
+            cap: Cap::Projecting,
+            ..style.clone()
+        },
+        false => style.clone(),
+    };
+
+    style
+        .dash(&points)
+        .iter()
+        .flat_map(|dash| style.outline(dash))
+        .flat_map(|polygon| fan(&polygon))
+        .collect()
+}
+
+/// Returns the triangles of a convex polygon
+fn fan(polygon: &[Vec2f]) -> Vec<Triangle> {
+    polygon
+        .windows(2)
+        .skip(1)
+        .map(|edge| [polygon[0], edge[0], edge[1]])
+        .collect()
+}
+
+/// Returns the position and size of a rectangle
+fn rectangle_bounds(rectangle: &XRectangle) -> (f32, f32, f32, f32) {
+    (
+        rectangle.x as f32,
+        rectangle.y as f32,
+        rectangle.width as f32,
+        rectangle.height as f32,
+    )
+}
+
+/// Returns the two triangles of an axis aligned rectangle
+fn quad(left: f32, top: f32, right: f32, bottom: f32) -> Vec<Triangle> {
+    if right <= left || bottom <= top {
+        return Vec::new();
+    }
+
+    let (top_left, top_right) = (Vec2f::new(left, top), Vec2f::new(right, top));
+    let (bottom_left, bottom_right) = (Vec2f::new(left, bottom), Vec2f::new(right, bottom));
+
+    vec![[top_left, top_right, bottom_right], [top_left, bottom_right, bottom_left]]
+}
+
+/// Returns the center, radius, start angle and extent (radians) of an arc
+///
+/// # Arguments
+///
+/// * `arc` - The arc
+/// * `offset` - Offset added to the center (0.5 to go through the center of the pixels)
+fn arc_geometry(arc: &XArc, offset: f32) -> (Vec2f, Vec2f, f32, f32) {
+    let radius = Vec2f::new(arc.width as f32 / 2.0, arc.height as f32 / 2.0);
+    let center = Vec2f::new(arc.x as f32 + radius.x + offset, arc.y as f32 + radius.y + offset);
+
+    let start = (arc.angle1 as f32 / 64.0).to_radians();
+    let extent = (arc.angle2 as f32 / 64.0).clamp(-360.0, 360.0).to_radians();
+
+    (center, radius, start, extent)
+}
+
+/// Returns the number of segments approximating an elliptic arc
+fn segments(radius: Vec2f, extent: f32) -> usize {
+    let length = radius.x.max(radius.y).max(0.0) * extent.abs();
+    ((length / SEGMENT_LENGTH).ceil() as usize).clamp(1, MAX_SEGMENTS)
+}
+
+/// Returns points along an ellipse (angles counterclockwise from 3 o'clock, y axis pointing down)
+fn ellipse_points(center: Vec2f, radius: Vec2f, start: f32, extent: f32, segments: usize) -> Vec<Vec2f> {
+    (0..=segments)
+        .map(|step| {
+            let angle = start + extent * step as f32 / segments as f32;
+            Vec2f::new(center.x + radius.x * angle.cos(), center.y - radius.y * angle.sin())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use x11rb::protocol::xproto::{Point, PolyShape, Segment};
+
+    use super::*;
+
+    /// Sums the area of the triangles
+    fn area(triangles: &[Triangle]) -> f32 {
+        triangles
+            .iter()
+            .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn test_fill_rectangle() {
+        let rectangle = XRectangle { x: 1, y: 2, width: 10, height: 5 };
+        let triangles = triangles(&Primitive::FillRectangle(rectangle), &LineStyle::default(), FillRule::default(), ArcMode::default());
+
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area(&triangles), 50.0);
+    }
+
+    #[test]
+    fn test_rectangle() {
+        let rectangle = XRectangle { x: 0, y: 0, width: 4, height: 4 };
+        let triangles = triangles(&Primitive::Rectangle(rectangle), &LineStyle::default(), FillRule::default(), ArcMode::default());
+
+        // Same pixels as the core outline (16 pixels)
+        assert_eq!(area(&triangles), 16.0);
+    }
+
+    #[test]
+    fn test_fill_arc() {
+        let arc = XArc { x: 0, y: 0, width: 20, height: 20, angle1: 0, angle2: 360 * 64 };
+        let full = area(&triangles(&Primitive::FillArc(arc), &LineStyle::default(), FillRule::default(), ArcMode::default()));
+        assert!((full - PI * 100.0).abs() < 2.0);
+
+        let quarter = XArc { angle2: 90 * 64, ..arc };
+        let quarter = area(&triangles(&Primitive::FillArc(quarter), &LineStyle::default(), FillRule::default(), ArcMode::default()));
+        assert!((quarter - full / 4.0).abs() < 1.0);
+
+        // The chord removes the triangle between the center and the end points
+        let chord = XArc { angle2: 90 * 64, ..arc };
+        let chord = area(&triangles(&Primitive::FillArc(chord), &LineStyle::default(), FillRule::default(), ArcMode::Chord));
+        assert!((chord - (quarter - 50.0)).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_arc() {
+        let arc = XArc { x: 0, y: 0, width: 20, height: 20, angle1: 0, angle2: 360 * 64 };
+        let outline = area(&triangles(&Primitive::Arc(arc), &LineStyle::default(), FillRule::default(), ArcMode::default()));
+
+        // One pixel wide ring of radius 10
+        assert!((outline - 2.0 * PI * 10.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_segment() {
+        let segment = Primitive::Segment(Segment { x1: 0, y1: 0, x2: 9, y2: 0 });
+
+        // Thin lines cover the pixels of both ends
+        assert!((area(&triangles(&segment, &LineStyle::default(), FillRule::default(), ArcMode::default())) - 10.0).abs() < 1e-3);
+        assert!((area(&triangles(&segment, &LineStyle::new(4), FillRule::default(), ArcMode::default())) - 36.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_polygon() {
+        let point = |x, y| Point { x, y };
+        let triangle = Primitive::FillPolygon(vec![point(0, 0), point(10, 0), point(0, 10)], PolyShape::CONVEX);
+        assert!((area(&triangles(&triangle, &LineStyle::default(), FillRule::EvenOdd, ArcMode::default())) - 50.0).abs() < 1e-3);
+
+        // Square drawn twice (winding number 2)
+        let square = [point(0, 0), point(10, 0), point(10, 10), point(0, 10)];
+        let twice = Primitive::FillPolygon(square.repeat(2), PolyShape::COMPLEX);
+        assert_eq!(area(&triangles(&twice, &LineStyle::default(), FillRule::EvenOdd, ArcMode::default())), 0.0);
+        assert!((area(&triangles(&twice, &LineStyle::default(), FillRule::Winding, ArcMode::default())) - 100.0).abs() < 1e-3);
+
+        // Bow tie: two triangles meeting at the center
+        let bow_tie = Primitive::FillPolygon(vec![point(0, 0), point(10, 10), point(10, 0), point(0, 10)], PolyShape::COMPLEX);
+        assert!((area(&triangles(&bow_tie, &LineStyle::default(), FillRule::EvenOdd, ArcMode::default())) - 50.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,632 @@
+//! Render queue module
+//!
+//! This module keeps the shapes of the overlay in drawing order.
+//!
+//! The shapes are grouped in named layers: the queue is sorted by `(z-order, layer index)`,
+//! so the shapes of a layer are contiguous and layers with the same z-order are drawn in creation order.
+//! Inside a layer, the shapes are drawn in the order they were added (or moved to).
+//!
+//! Every change of the order adds the area of the moved shapes to the damage, so the overlay redraws them.
+
+use std::{cell::RefCell, error::Error, ops::Range, rc::Rc};
+
+use x11rb::connection::Connection;
+
+use crate::{damage::Damage, math::rect::Rect, shape::Shape};
+
+/// Name of the layer used by `Overlay::add_shape`
+pub const DEFAULT_LAYER: &str = "default";
+
+/// Stable identifier of a shape added to the overlay
+///
+/// It stays valid when the shape is moved in the render queue, until the shape is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeId(u64);
+
+/// A named group of shapes sharing the same z-order
+struct Layer {
+    /// The name of the layer
+    name: String,
+    /// The z-order of the layer (higher is drawn above)
+    z: i32,
+}
+
+/// A shape of the render queue
+pub(crate) struct RenderItem<C>
+where
+    C: Connection,
+{
+    /// The identifier of the shape
+    pub id: ShapeId,
+    /// The layer of the shape (index in the queue layers)
+    pub layer: usize,
+    /// The shape to draw
+    pub shape: Rc<RefCell<dyn Shape<C>>>,
+    /// The area covered by the shape when it was last drawn (None if never drawn)
+    pub bounds: Option<Rect>,
+}
+
+impl<C> RenderItem<C>
+where
+    C: Connection,
+{
+    fn new(id: ShapeId, layer: usize, shape: Rc<RefCell<dyn Shape<C>>>) -> Self {
+        Self { id, layer, shape, bounds: None }
+    }
+
+    /// Add the area of the shape to the damage (if it was drawn)
+    fn damage(&self, damage: &mut Damage) {
+        if let Some(bounds) = self.bounds {
+            damage.add(bounds);
+        }
+    }
+}
+
+/// The shapes to draw, sorted by layer z-order
+pub(crate) struct RenderQueue<C>
+where
+    C: Connection,
+{
+    /// The shapes, in drawing order
+    items: Vec<RenderItem<C>>,
+    /// The layers of the queue (the default layer is the first one)
+    layers: Vec<Layer>,
+    /// The identifier of the next added shape
+    next_id: u64,
+}
+
+impl<C> RenderQueue<C>
+where
+    C: Connection,
+{
+    /// Create an empty render queue with the default layer
+    pub(crate) fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            layers: vec![Layer { name: DEFAULT_LAYER.to_string(), z: 0 }],
+            next_id: 0,
+        }
+    }
+
+    /// Get the shapes, in drawing order
+    pub(crate) fn items(&self) -> &[RenderItem<C>] {
+        &self.items
+    }
+
+    /// Get the shapes, in drawing order (the order itself can't be changed)
+    pub(crate) fn items_mut(&mut self) -> &mut [RenderItem<C>] {
+        &mut self.items
+    }
+
+    /// Remove every shape (the layers are kept)
+    pub(crate) fn drain(&mut self) -> std::vec::Drain<'_, RenderItem<C>> {
+        self.items.drain(..)
+    }
+
+    /// Get a shape of the queue
+    pub(crate) fn get(&self, id: ShapeId) -> Option<&RenderItem<C>> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Get the index of a layer from its name
+    pub(crate) fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// Insert a shape on top of a layer
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The index of the layer (see `layer_index`, 0 is the default layer)
+    /// * `shape` - The shape to add
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the shape
+    ///
+    pub(crate) fn insert(&mut self, layer: usize, shape: Rc<RefCell<dyn Shape<C>>>) -> ShapeId {
+        let id = ShapeId(self.next_id);
+        self.next_id += 1;
+
+        let index = self.layer_range(layer).end;
+        self.items.insert(index, RenderItem::new(id, layer, shape));
+        id
+    }
+
+    /// Add a layer (or change the z-order of an existing one)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the layer
+    /// * `z` - The z-order of the layer
+    /// * `damage` - Receives the area of the shapes of the layer if its z-order changed
+    ///
+    pub(crate) fn add_layer(&mut self, name: &str, z: i32, damage: &mut Damage) {
+        match self.layer_index(name) {
+            Some(index) => {
+                if self.layers[index].z != z {
+                    self.layers[index].z = z;
+                    // The shapes of the layer may now be above or below other shapes
+                    self.damage_layer(index, damage);
+                    // The sort is stable, so the order inside the layers is kept
+                    let layers = &self.layers;
+                    self.items.sort_by_key(|item| (layers[item.layer].z, item.layer));
+                }
+            }
+            None => self.layers.push(Layer { name: name.to_string(), z }),
+        }
+    }
+
+    /// Remove a shape from the queue
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier of the shape
+    /// * `damage` - Receives the area of the removed shape
+    ///
+    /// # Returns
+    ///
+    /// The removed shape (None if the identifier is unknown)
+    ///
+    pub(crate) fn remove(&mut self, id: ShapeId, damage: &mut Damage) -> Option<Rc<RefCell<dyn Shape<C>>>> {
+        let index = self.shape_index(id)?;
+        let item = self.items.remove(index);
+        item.damage(damage);
+        Some(item.shape)
+    }
+
+    /// Move a shape one step above in its layer (see `move_to_index`)
+    pub(crate) fn raise(&mut self, id: ShapeId, damage: &mut Damage) -> Result<(), Box<dyn Error>> {
+        let position = self.position_in_layer(id)?;
+        self.move_to_index(id, position + 1, damage)
+    }
+
+    /// Move a shape one step below in its layer (see `move_to_index`)
+    pub(crate) fn lower(&mut self, id: ShapeId, damage: &mut Damage) -> Result<(), Box<dyn Error>> {
+        let position = self.position_in_layer(id)?;
+        self.move_to_index(id, position.saturating_sub(1), damage)
+    }
+
+    /// Move a shape to the given position in its layer
+    ///
+    /// Positions past the end of the layer move the shape on top of the layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier of the shape
+    /// * `index` - The new position of the shape in its layer
+    /// * `damage` - Receives the area of the shape if it moved
+    ///
+    /// # Errors
+    ///
+    /// If the identifier is unknown
+    ///
+    pub(crate) fn move_to_index(&mut self, id: ShapeId, index: usize, damage: &mut Damage) -> Result<(), Box<dyn Error>> {
+        let current = self.shape_index(id).ok_or("No shape found")?;
+        let item = self.items.remove(current);
+
+        // The range of the layer without the shape: the target is an index of the queue once it is inserted back
+        let range = self.layer_range(item.layer);
+        let target = range.start + index.min(range.len());
+
+        if target != current {
+            // The shape is now above or below other shapes
+            item.damage(damage);
+        }
+
+        self.items.insert(target, item);
+        Ok(())
+    }
+
+    /// Update the area of the shapes that changed, and add the regions to redraw to the damage
+    ///
+    /// Dirty shapes and shapes never drawn damage their previous and their new area.
+    /// Hidden shapes cover nothing: a shape that becomes hidden only damages its previous area,
+    /// and a hidden shape is only measured when it changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `damage` - Receives the regions to redraw
+    /// * `measure` - Measures a shape and returns its area (hidden shapes are measured too, their size must follow their changes)
+    ///
+    /// # Errors
+    ///
+    /// If a shape could not be measured
+    ///
+    pub(crate) fn update_bounds<F>(&mut self, damage: &mut Damage, mut measure: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&dyn Shape<C>) -> Result<Rect, Box<dyn Error>>,
+    {
+        for item in self.items.iter_mut() {
+            let mut shape = item.shape.borrow_mut();
+
+            if !shape.is_dirty() && (item.bounds.is_some() || !shape.is_visible()) {
+                continue;
+            }
+
+            if let Some(previous) = item.bounds.take() {
+                damage.add(previous);
+            }
+
+            let bounds = measure(&*shape)?;
+            if shape.is_visible() {
+                damage.add(bounds);
+                item.bounds = Some(bounds);
+            }
+
+            shape.set_dirty(false);
+        }
+
+        Ok(())
+    }
+
+    /// Iterate over the visible shapes overlapping the damage (in drawing order)
+    ///
+    /// Visible shapes whose area is unknown are always part of it.
+    pub(crate) fn damaged<'a>(&'a self, damage: &'a Damage) -> impl Iterator<Item = &'a RenderItem<C>> {
+        self.items.iter().filter(|item| {
+            item.shape.borrow().is_visible() && item.bounds
+                .map(|bounds| damage.intersects(&bounds))
+                .unwrap_or(true)
+        })
+    }
+
+    /// Get the input region of the window: the area of the interactive shapes
+    ///
+    /// Hidden shapes and shapes never drawn are not part of it. The region is empty without interactive shapes
+    /// (mouse events go through the whole window).
+    ///
+    /// # Arguments
+    ///
+    /// * `margin` - The margin included in the areas of the shapes (removed from the region)
+    ///
+    pub(crate) fn input_region(&self, margin: i32) -> Vec<Rect> {
+        self.items
+            .iter()
+            .filter(|item| item.shape.borrow().is_interactive())
+            .filter_map(|item| item.bounds)
+            .map(|bounds| bounds.inflate(-margin))
+            .filter(|bounds| !bounds.is_empty())
+            .collect()
+    }
+
+    /// Get the index of a shape in the queue
+    fn shape_index(&self, id: ShapeId) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    /// Get the position of a shape in its layer
+    fn position_in_layer(&self, id: ShapeId) -> Result<usize, Box<dyn Error>> {
+        let index = self.shape_index(id).ok_or("No shape found")?;
+        Ok(index - self.layer_range(self.items[index].layer).start)
+    }
+
+    /// Get the range of the queue covered by a layer
+    ///
+    /// For an empty layer, the range is empty and starts where the layer shapes would be inserted.
+    fn layer_range(&self, layer: usize) -> Range<usize> {
+        let key = (self.layers[layer].z, layer);
+        let layers = &self.layers;
+        let key_of = |item: &RenderItem<C>| (layers[item.layer].z, item.layer);
+
+        let start = self.items.partition_point(|item| key_of(item) < key);
+        let end = self.items.partition_point(|item| key_of(item) <= key);
+        start..end
+    }
+
+    /// Add the area of every shape of a layer to the damage
+    fn damage_layer(&self, layer: usize, damage: &mut Damage) {
+        for item in self.items.iter().filter(|item| item.layer == layer) {
+            item.damage(damage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use crate::{
+        shape::{coord::{Anchor, Coord, Size}, Rectangle},
+        Color,
+    };
+
+    use super::*;
+
+    fn shape() -> Rc<RefCell<dyn Shape<RustConnection>>> {
+        Rectangle::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(0.1, 0.1), Color::RED).unwrap()
+    }
+
+    /// Returns the identifiers of the shapes, in drawing order
+    fn order(queue: &RenderQueue<RustConnection>) -> Vec<ShapeId> {
+        queue.items().iter().map(|item| item.id).collect()
+    }
+
+    /// Gives every shape a distinct area (as if it was drawn)
+    fn draw(queue: &mut RenderQueue<RustConnection>) {
+        for (index, item) in queue.items_mut().iter_mut().enumerate() {
+            item.bounds = Some(Rect::new(index as i32 * 100, 0, 10, 10));
+        }
+    }
+
+    fn bounds(queue: &RenderQueue<RustConnection>, id: ShapeId) -> Rect {
+        queue.get(id).and_then(|item| item.bounds).unwrap()
+    }
+
+    /// A queue with a "hud" layer above the default one: [a, b] [h]
+    fn queue() -> (RenderQueue<RustConnection>, [ShapeId; 3]) {
+        let mut queue = RenderQueue::new();
+        queue.add_layer("hud", 10, &mut Damage::new());
+        let hud = queue.layer_index("hud").unwrap();
+
+        let a = queue.insert(0, shape());
+        let h = queue.insert(hud, shape());
+        let b = queue.insert(0, shape());
+        draw(&mut queue);
+        (queue, [a, b, h])
+    }
+
+    #[test]
+    fn test_insert() {
+        let (mut queue, [a, b, h]) = queue();
+        assert_eq!(order(&queue), vec![a, b, h]);
+
+        // Added on top of their layer
+        let c = queue.insert(0, shape());
+        let i = queue.insert(1, shape());
+        assert_eq!(order(&queue), vec![a, b, c, h, i]);
+        assert_eq!(queue.layer_index(DEFAULT_LAYER), Some(0));
+        assert_eq!(queue.layer_index("unknown"), None);
+    }
+
+    #[test]
+    fn test_empty_layer() {
+        let (mut queue, [a, b, h]) = queue();
+        queue.add_layer("middle", 5, &mut Damage::new());
+        queue.add_layer("top", 20, &mut Damage::new());
+        queue.add_layer("bottom", -1, &mut Damage::new());
+
+        // Empty ranges, where the shapes of the layers would be inserted
+        assert_eq!(queue.layer_range(queue.layer_index("middle").unwrap()), 2..2);
+        assert_eq!(queue.layer_range(queue.layer_index("top").unwrap()), 3..3);
+        assert_eq!(queue.layer_range(queue.layer_index("bottom").unwrap()), 0..0);
+
+        let m = queue.insert(queue.layer_index("middle").unwrap(), shape());
+        assert_eq!(order(&queue), vec![a, b, m, h]);
+        assert_eq!(queue.position_in_layer(m).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_raise_lower() {
+        let (mut queue, [a, b, h]) = queue();
+
+        // Moving past the ends of a layer does nothing
+        let mut damage = Damage::new();
+        queue.raise(b, &mut damage).unwrap();
+        queue.lower(a, &mut damage).unwrap();
+        queue.raise(h, &mut damage).unwrap();
+        queue.lower(h, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![a, b, h]);
+        assert!(damage.is_empty());
+
+        queue.raise(a, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![b, a, h]);
+        assert_eq!(damage.rects(), &[bounds(&queue, a)]);
+
+        let mut damage = Damage::new();
+        queue.lower(a, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![a, b, h]);
+        assert_eq!(damage.rects(), &[bounds(&queue, a)]);
+    }
+
+    #[test]
+    fn test_move_to_index() {
+        let (mut queue, [a, b, h]) = queue();
+        let c = queue.insert(0, shape());
+
+        // Clamped to the top of the layer, not moved to the layer above
+        let mut damage = Damage::new();
+        queue.move_to_index(a, 100, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![b, c, a, h]);
+        assert_eq!(damage.rects(), &[bounds(&queue, a)]);
+
+        // Already there
+        let mut damage = Damage::new();
+        queue.move_to_index(a, 2, &mut damage).unwrap();
+        queue.move_to_index(h, 7, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![b, c, a, h]);
+        assert!(damage.is_empty());
+
+        queue.move_to_index(a, 0, &mut damage).unwrap();
+        assert_eq!(order(&queue), vec![a, b, c, h]);
+    }
+
+    #[test]
+    fn test_layer_z() {
+        let (mut queue, [a, b, h]) = queue();
+        let i = queue.insert(1, shape());
+        draw(&mut queue);
+
+        // Unchanged z-order, nothing to redraw
+        let mut damage = Damage::new();
+        queue.add_layer("hud", 10, &mut damage);
+        assert!(damage.is_empty());
+
+        // The hud is now below the default layer, the order inside the layers is kept
+        queue.add_layer("hud", -10, &mut damage);
+        assert_eq!(order(&queue), vec![h, i, a, b]);
+        assert_eq!(damage.rects(), &[bounds(&queue, h), bounds(&queue, i)]);
+
+        // Same z-order as the default layer: drawn in creation order (after it)
+        queue.add_layer("hud", 0, &mut Damage::new());
+        assert_eq!(order(&queue), vec![a, b, h, i]);
+        let c = queue.insert(0, shape());
+        assert_eq!(order(&queue), vec![a, b, c, h, i]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let (mut queue, [a, b, h]) = queue();
+        let area = bounds(&queue, b);
+
+        let mut damage = Damage::new();
+        assert!(queue.remove(b, &mut damage).is_some());
+        assert_eq!(order(&queue), vec![a, h]);
+        assert_eq!(damage.rects(), &[area]);
+
+        // Never drawn, nothing to redraw
+        let c = queue.insert(0, shape());
+        let mut damage = Damage::new();
+        assert!(queue.remove(c, &mut damage).is_some());
+        assert!(damage.is_empty());
+    }
+
+    /// Measures the shapes from their position (a 10x10 square, 100 pixels per window)
+    fn measure(shape: &dyn Shape<RustConnection>) -> Result<Rect, Box<dyn Error>> {
+        let position = shape.position();
+        Ok(Rect::new((position.x * 100.0) as i32, (position.y * 100.0) as i32, 10, 10))
+    }
+
+    fn set_visible(queue: &RenderQueue<RustConnection>, id: ShapeId, visible: bool) {
+        queue.get(id).unwrap().shape.borrow_mut().set_visible(visible);
+    }
+
+    fn damaged(queue: &RenderQueue<RustConnection>, damage: &Damage) -> Vec<ShapeId> {
+        queue.damaged(damage).map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn test_update_bounds() {
+        let mut queue = RenderQueue::new();
+        let a = queue.insert(0, shape());
+
+        // Never drawn, not measured yet
+        assert_eq!(damaged(&queue, &Damage::new()), vec![a]);
+
+        let mut damage = Damage::new();
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 10, 10)]);
+        assert_eq!(queue.get(a).unwrap().bounds, Some(Rect::new(0, 0, 10, 10)));
+        assert!(!queue.get(a).unwrap().shape.borrow().is_dirty());
+
+        // Unchanged
+        let mut damage = Damage::new();
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert!(damage.is_empty());
+        assert!(damaged(&queue, &damage).is_empty());
+
+        // Moved: the previous and the new area are redrawn
+        queue.get(a).unwrap().shape.borrow_mut().set_position(Coord::new(0.5, 0.0));
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 10, 10), Rect::new(50, 0, 10, 10)]);
+        assert_eq!(damaged(&queue, &damage), vec![a]);
+    }
+
+    #[test]
+    fn test_hide() {
+        let mut queue = RenderQueue::new();
+        let (a, b) = (queue.insert(0, shape()), queue.insert(0, shape()));
+        queue.get(b).unwrap().shape.borrow_mut().set_position(Coord::new(0.5, 0.0));
+        queue.update_bounds(&mut Damage::new(), measure).unwrap();
+
+        // Only the previous area is redrawn, without the hidden shape
+        set_visible(&queue, a, false);
+        let mut damage = Damage::new();
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 10, 10)]);
+        assert_eq!(queue.get(a).unwrap().bounds, None);
+        assert!(damaged(&queue, &damage).is_empty());
+
+        // Hidden shapes are not measured again until they change
+        let mut damage = Damage::new();
+        queue.update_bounds(&mut damage, |_| Err("Measured".into())).unwrap();
+        assert!(damage.is_empty());
+        let mut full = Damage::new();
+        full.add(Rect::new(0, 0, 100, 100));
+        assert_eq!(damaged(&queue, &full), vec![b]);
+    }
+
+    #[test]
+    fn test_show() {
+        let mut queue = RenderQueue::new();
+        let a = queue.insert(0, shape());
+        set_visible(&queue, a, false);
+
+        // Never drawn while hidden
+        let mut damage = Damage::new();
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert!(damage.is_empty());
+        assert_eq!(queue.get(a).unwrap().bounds, None);
+
+        set_visible(&queue, a, true);
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 10, 10)]);
+        assert_eq!(damaged(&queue, &damage), vec![a]);
+    }
+
+    #[test]
+    fn test_hidden_while_dirty() {
+        let mut queue = RenderQueue::new();
+        let a = queue.insert(0, shape());
+        queue.update_bounds(&mut Damage::new(), measure).unwrap();
+        set_visible(&queue, a, false);
+        queue.update_bounds(&mut Damage::new(), measure).unwrap();
+
+        // Changed while hidden: measured, but nothing to redraw
+        queue.get(a).unwrap().shape.borrow_mut().set_position(Coord::new(0.5, 0.5));
+        let (mut damage, mut measured) = (Damage::new(), 0);
+        queue.update_bounds(&mut damage, |shape| {
+            measured += 1;
+            measure(shape)
+        }).unwrap();
+        assert_eq!(measured, 1);
+        assert!(damage.is_empty());
+        assert!(!queue.get(a).unwrap().shape.borrow().is_dirty());
+
+        // Shown where it was moved
+        set_visible(&queue, a, true);
+        queue.update_bounds(&mut damage, measure).unwrap();
+        assert_eq!(damage.rects(), &[Rect::new(50, 50, 10, 10)]);
+    }
+
+    #[test]
+    fn test_input_region() {
+        let mut queue = RenderQueue::new();
+        let (a, b, c) = (queue.insert(0, shape()), queue.insert(0, shape()), queue.insert(0, shape()));
+        queue.get(b).unwrap().shape.borrow_mut().set_position(Coord::new(0.5, 0.0));
+        queue.get(c).unwrap().shape.borrow_mut().set_position(Coord::new(0.0, 0.5));
+
+        // No interactive shape
+        queue.update_bounds(&mut Damage::new(), measure).unwrap();
+        assert!(queue.input_region(1).is_empty());
+
+        // Without the margin, in drawing order
+        for id in [a, b, c] {
+            queue.get(id).unwrap().shape.borrow_mut().set_interactive(true);
+        }
+        queue.move_to_index(a, 2, &mut Damage::new()).unwrap();
+        assert_eq!(queue.input_region(1), vec![Rect::new(51, 1, 8, 8), Rect::new(1, 51, 8, 8), Rect::new(1, 1, 8, 8)]);
+
+        // Hidden shapes, shapes never drawn and empty areas are left out
+        set_visible(&queue, b, false);
+        queue.update_bounds(&mut Damage::new(), measure).unwrap();
+        let d = queue.insert(0, shape());
+        queue.get(d).unwrap().shape.borrow_mut().set_interactive(true);
+        assert_eq!(queue.input_region(1), vec![Rect::new(1, 51, 8, 8), Rect::new(1, 1, 8, 8)]);
+        assert!(queue.input_region(5).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_id() {
+        let (mut queue, [a, b, h]) = queue();
+        let mut damage = Damage::new();
+        queue.remove(b, &mut damage);
+
+        assert!(queue.get(b).is_none());
+        assert!(queue.remove(b, &mut damage).is_none());
+        assert!(queue.raise(b, &mut damage).is_err());
+        assert!(queue.lower(b, &mut damage).is_err());
+        assert!(queue.move_to_index(b, 0, &mut damage).is_err());
+        assert_eq!(order(&queue), vec![a, h]);
+    }
+}
@@ -5,34 +5,151 @@
 //! Arc shape corresponds to a part of an ellipse.
 //! It also offers the possibility to draw a circle directly.
 //! 
+//! The radius of a circle is relative to the width, the height or the smallest dimension of the window,
+//! the circle stays round whatever the aspect ratio of the window.
+//! 
+//! Angles are given in degrees or radians (`Angle`), they are normalized before being sent to the server
+//! (start angle in `0..360` degrees, extent in `-360..=360` degrees).
+//! Filled arcs are closed as pie slices or chords (`ArcMode`), and an inner radius turns an arc into a
+//! ring segment (radial gauges, donuts).
+//! 
 //! # Future improvements
 //! 
 //! - Add more options to the arc shape
-//! - Improve circle drawing (currently it has a constant width/height ratio)
 
-use std::{cell::RefCell, error::Error, rc::Rc};
+use std::{cell::{Cell, RefCell}, error::Error, rc::Rc};
 
 use x11rb::{
     connection::Connection,
-    protocol::xproto::{Arc as XArc, ConnectionExt},
+    protocol::xproto::{Arc as XArc, Point as XPoint},
 };
 
-use crate::{color::Color, drawable::Drawable};
+use crate::{color::Color, drawable::{canvas::Canvas, Drawable}, font::FontSet, math::{rect::Rect, vec::Vec2}, ResizePolicy};
 
 use super::{
-    coord::{Anchor, Coord, CoordExt, Size, SizeExt}, GcontextWrapperExt, Shape
+    coord::{Anchor, Coord, CoordExt, Size, SizeExt}, polygon::poly_shape, ArcMode, Batch, GcontextWrapperExt, Primitive, Shape
 };
 
+/// Maximum length of the segments approximating a ring (in pixels)
+const RING_SEGMENT_LENGTH: f32 = 2.0;
+
+/// Maximum number of segments approximating a ring
+const RING_MAX_SEGMENTS: usize = 512;
+
+/// Describe an angle, counterclockwise from 3 o'clock
+///
+/// - Degrees: An angle in degrees
+/// - Radians: An angle in radians
+///
+/// A plain `f32` is in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Angle {
+    Degrees(f32),
+    Radians(f32),
+}
+
+impl From<f32> for Angle {
+    fn from(degrees: f32) -> Self {
+        Angle::Degrees(degrees)
+    }
+}
+
+impl Angle {
+    /// Returns the angle in degrees
+    pub fn degrees(&self) -> f32 {
+        match *self {
+            Angle::Degrees(degrees) => degrees,
+            Angle::Radians(radians) => radians.to_degrees(),
+        }
+    }
+}
+
+/// Returns the start angle normalized to `0..360` degrees
+fn normalize_start(angle: Angle) -> f32 {
+    let degrees = angle.degrees().rem_euclid(360.0);
+    // Rounding may give exactly 360
+    if degrees >= 360.0 { 0.0 } else { degrees }
+}
+
+/// Returns the extent clamped to `-360..=360` degrees (more than a turn is a full ellipse)
+fn normalize_extent(angle: Angle) -> f32 {
+    angle.degrees().clamp(-360.0, 360.0)
+}
+
+/// Converts degrees to the unit of the protocol (1/64 degree)
+fn to_x_angle(degrees: f32) -> i16 {
+    (degrees * 64.0).round() as i16
+}
+
+/// Describe the radius of a circle, relative to a dimension of the window
+///
+/// - Width: A fraction of the width of the window
+/// - Height: A fraction of the height of the window
+/// - Min: A fraction of the smallest dimension of the window
+///
+/// A plain `f32` is relative to the smallest dimension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CircleRadius {
+    Width(f32),
+    Height(f32),
+    Min(f32),
+}
+
+impl From<f32> for CircleRadius {
+    fn from(radius: f32) -> Self {
+        CircleRadius::Min(radius)
+    }
+}
+
+impl CircleRadius {
+    /// Returns the fraction of the reference dimension
+    pub fn value(&self) -> f32 {
+        match *self {
+            CircleRadius::Width(radius) | CircleRadius::Height(radius) | CircleRadius::Min(radius) => radius,
+        }
+    }
+
+    /// Returns a radius relative to the same dimension, with another fraction
+    pub fn with_value(&self, radius: f32) -> Self {
+        match self {
+            CircleRadius::Width(_) => CircleRadius::Width(radius),
+            CircleRadius::Height(_) => CircleRadius::Height(radius),
+            CircleRadius::Min(_) => CircleRadius::Min(radius),
+        }
+    }
+
+    /// Returns the reference dimension of a window of the given size
+    fn reference(&self, size: Vec2<f32>) -> f32 {
+        match self {
+            CircleRadius::Width(_) => size.x,
+            CircleRadius::Height(_) => size.y,
+            CircleRadius::Min(_) => size.x.min(size.y),
+        }
+    }
+
+    /// Returns the radius in pixels on a window of the given size
+    pub fn to_pixels(&self, size: Vec2<u16>) -> f32 {
+        self.value() * self.reference(size.convert())
+    }
+}
+
 /// Represents an arc shape.
 pub struct Arc {
     anchor: Anchor,
     position: Coord,
     size: Size,
+    radius: Option<CircleRadius>,
     start_angle: f32,
-    end_angle: f32,
+    extent: f32,
+    inner: f32,
+    mode: ArcMode,
     forground: Color,
     background: Color,
     filled: bool,
+    visible: bool,
+    interactive: bool,
+    window: Cell<Option<Vec2<u16>>>, // Size of the window the arc was last measured or drawn on
+    dirty: bool,
 }
 
 impl Arc {
@@ -43,8 +160,8 @@ impl Arc {
     /// * `anchor` - The anchor point of the arc.
     /// * `position` - The position of the arc.
     /// * `size` - The size of the arc.
-    /// * `start_angle` - The start angle of the arc in degrees.
-    /// * `end_angle` - The end angle of the arc in degrees.
+    /// * `start_angle` - The start angle of the arc (a `f32` is in degrees).
+    /// * `extent` - The angular extent of the arc, counterclockwise (a `f32` is in degrees, negative is clockwise).
     /// * `forground` - The color of the edge of the arc.
     /// * `background` - The color of the background of the arc.
     ///
@@ -55,8 +172,8 @@ impl Arc {
         anchor: Anchor,
         position: Coord,
         size: Size,
-        start_angle: f32,
-        end_angle: f32,
+        start_angle: impl Into<Angle>,
+        extent: impl Into<Angle>,
         forground: Color,
         background: Color,
     ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
@@ -64,11 +181,18 @@ impl Arc {
             anchor,
             position,
             size,
-            start_angle,
-            end_angle,
+            radius: None,
+            start_angle: normalize_start(start_angle.into()),
+            extent: normalize_extent(extent.into()),
+            inner: 0.0,
+            mode: ArcMode::default(),
             forground,
             background,
             filled: false,
+            visible: true,
+            interactive: false,
+            window: Cell::new(None),
+            dirty: true,
         })))
     }
 
@@ -79,8 +203,8 @@ impl Arc {
     /// * `anchor` - The anchor point of the arc.
     /// * `position` - The position of the arc.
     /// * `size` - The size of the arc.
-    /// * `start_angle` - The start angle of the arc in degrees.
-    /// * `end_angle` - The end angle of the arc in degrees.
+    /// * `start_angle` - The start angle of the arc (a `f32` is in degrees).
+    /// * `extent` - The angular extent of the arc, counterclockwise (a `f32` is in degrees, negative is clockwise).
     /// * `color` - The color of the arc.
     ///
     /// # Returns
@@ -90,83 +214,199 @@ impl Arc {
         anchor: Anchor,
         position: Coord,
         size: Size,
-        start_angle: f32,
-        end_angle: f32,
+        start_angle: impl Into<Angle>,
+        extent: impl Into<Angle>,
         color: Color,
     ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
         Ok(Rc::new(RefCell::new(Self {
             anchor,
             position,
             size,
-            start_angle,
-            end_angle,
+            radius: None,
+            start_angle: normalize_start(start_angle.into()),
+            extent: normalize_extent(extent.into()),
+            inner: 0.0,
+            mode: ArcMode::default(),
             forground: color,
             background: color, // Not used
             filled: true,
+            visible: true,
+            interactive: false,
+            window: Cell::new(None),
+            dirty: true,
         })))
     }
 
     /// Creates a new circle shape.
     ///
+    /// The circle is round in pixels, whatever the aspect ratio of the window.
+    ///
     /// # Arguments
     ///
     /// * `anchor` - The anchor point of the circle.
     /// * `position` - The position of the circle.
-    /// * `radius` - The radius of the circle.
-    /// * `color` - The color of the circle.
+    /// * `radius` - The radius of the circle (a `f32` is relative to the smallest dimension of the window).
+    /// * `forground` - The color of the edge of the circle.
+    /// * `background` - The color of the background of the circle.
     ///
     /// # Returns
     ///
     /// A `Result` containing a boxed `Arc` object or an error.
-    pub fn circle(
+    pub fn circle<R: Into<CircleRadius>>(
         anchor: Anchor,
         position: Coord,
-        radius: f32,
+        radius: R,
         forground: Color,
         background: Color,
     ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let radius = radius.into();
         Ok(Rc::new(RefCell::new(Self {
             anchor,
             position,
-            size: Size::new(radius, radius),
+            size: Size::new(radius.value() * 2.0, radius.value() * 2.0),
+            radius: Some(radius),
             start_angle: 0.0,
-            end_angle: 360.0,
+            extent: 360.0,
+            inner: 0.0,
+            mode: ArcMode::default(),
             forground,
             background,
             filled: false,
+            visible: true,
+            interactive: false,
+            window: Cell::new(None),
+            dirty: true,
         })))
     }
 
     /// Creates a new filled circle shape.
     ///
+    /// The circle is round in pixels, whatever the aspect ratio of the window.
+    ///
     /// # Arguments
     ///
     /// * `anchor` - The anchor point of the circle.
     /// * `position` - The position of the circle.
-    /// * `radius` - The radius of the circle.
+    /// * `radius` - The radius of the circle (a `f32` is relative to the smallest dimension of the window).
     /// * `color` - The color of the circle.
     ///
     /// # Returns
     ///
     /// A `Result` containing a boxed `Arc` object or an error.
-    pub fn filled_circle(
+    pub fn filled_circle<R: Into<CircleRadius>>(
         anchor: Anchor,
         position: Coord,
-        radius: f32,
+        radius: R,
         color: Color,
     ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let radius = radius.into();
         Ok(Rc::new(RefCell::new(Self {
             anchor,
             position,
-            size: Size::new(radius, radius),
+            size: Size::new(radius.value() * 2.0, radius.value() * 2.0),
+            radius: Some(radius),
             start_angle: 0.0,
-            end_angle: 360.0,
+            extent: 360.0,
+            inner: 0.0,
+            mode: ArcMode::default(),
             forground: color,
             background: color, // Not used
             filled: true,
+            visible: true,
+            interactive: false,
+            window: Cell::new(None),
+            dirty: true,
         })))
     }
 
+    /// Creates a new filled ring segment (a donut when the extent is a full turn).
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the ring.
+    /// * `position` - The position of the ring.
+    /// * `outer` - The outer radius of the ring (a `f32` is relative to the smallest dimension of the window).
+    /// * `inner` - The inner radius of the ring (relative to the same dimension as the outer radius).
+    /// * `start_angle` - The start angle of the segment (a `f32` is in degrees).
+    /// * `extent` - The angular extent of the segment, counterclockwise (a `f32` is in degrees, negative is clockwise).
+    /// * `color` - The color of the ring.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a boxed `Arc` object or an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the radii are not relative to the same dimension, or if the inner radius is not smaller than the outer radius.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord}, Angle, Arc}, Color};
+    ///
+    /// // Three quarters of a gauge, starting at the bottom left
+    /// let gauge = Arc::ring(Anchor::Center, Coord::new(0.5, 0.5), 0.2, 0.15, 225.0, Angle::Radians(-1.5 * std::f32::consts::PI), Color::GREEN).unwrap();
+    /// ```
+    pub fn ring<R: Into<CircleRadius>>(
+        anchor: Anchor,
+        position: Coord,
+        outer: R,
+        inner: R,
+        start_angle: impl Into<Angle>,
+        extent: impl Into<Angle>,
+        color: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let (outer, inner) = (outer.into(), inner.into());
+        if outer.with_value(inner.value()) != inner {
+            Err("Inner and outer radius must be relative to the same dimension")?;
+        }
+        if inner.value() < 0.0 || inner.value() >= outer.value() {
+            Err("Inner radius must be smaller than the outer radius")?;
+        }
+
+        let ring = Self::filled_circle(anchor, position, outer, color)?;
+        {
+            let mut arc = ring.borrow_mut();
+            arc.set_angles(start_angle, extent);
+            arc.inner = inner.value() / outer.value();
+        }
+        Ok(ring)
+    }
+
+    /// Returns the start angle (degrees, in `0..360`) and the extent (degrees, in `-360..=360`) of the arc.
+    pub fn angles(&self) -> (f32, f32) {
+        (self.start_angle, self.extent)
+    }
+
+    /// Sets the angles of the arc (normalized, see `angles`).
+    pub fn set_angles(&mut self, start_angle: impl Into<Angle>, extent: impl Into<Angle>) {
+        self.start_angle = normalize_start(start_angle.into());
+        self.extent = normalize_extent(extent.into());
+        self.dirty = true;
+    }
+
+    /// Returns how the filled arc is closed.
+    pub fn arc_mode(&self) -> ArcMode {
+        self.mode
+    }
+
+    /// Sets how the filled arc is closed (pie slice or chord, not used by rings and outlines).
+    pub fn set_arc_mode(&mut self, mode: ArcMode) {
+        self.mode = mode;
+        self.dirty = true;
+    }
+
+    /// Returns the inner radius of the ring, as a fraction of the outer radius (0 if the arc is not a ring).
+    pub fn inner_ratio(&self) -> f32 {
+        self.inner
+    }
+
+    /// Turns the arc into a ring segment, the inner radius is a fraction of the outer radius (0 removes the ring).
+    pub fn set_inner_ratio(&mut self, ratio: f32) {
+        self.inner = ratio.clamp(0.0, 1.0);
+        self.dirty = true;
+    }
+
     /// Returns the position of the arc.
     pub fn position(&self) -> &Coord {
         &self.position
@@ -175,26 +415,141 @@ impl Arc {
     /// Sets the position of the arc.
     pub fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
     }
 
     /// Returns the size of the arc.
+    ///
+    /// For a circle, the size is twice its radius, relative to the reference dimension of the radius
+    /// (see `Shape::size` for the size in percentage of the window).
     pub fn size(&self) -> &Size {
         &self.size
     }
 
     /// Sets the size of the arc.
+    ///
+    /// For a circle, the radius becomes half of the smallest component of the size
+    /// (still relative to the same dimension of the window).
     pub fn set_size(&mut self, size: Size) {
         self.size = size;
+        if let Some(radius) = self.radius {
+            let radius = radius.with_value(size.x.min(size.y) / 2.0);
+            self.size = Size::new(radius.value() * 2.0, radius.value() * 2.0);
+            self.radius = Some(radius);
+        }
+        self.dirty = true;
+    }
+
+    /// Returns the radius of the circle (None if the arc is not a circle).
+    pub fn radius(&self) -> Option<&CircleRadius> {
+        self.radius.as_ref()
+    }
+
+    /// Turns the arc into a circle of the given radius.
+    pub fn set_radius<R: Into<CircleRadius>>(&mut self, radius: R) {
+        let radius = radius.into();
+        self.size = Size::new(radius.value() * 2.0, radius.value() * 2.0);
+        self.radius = Some(radius);
+        self.dirty = true;
+    }
+
+    /// Returns the size of the arc (in percentage of a window of the given size).
+    ///
+    /// The box of a circle is a square in pixels.
+    fn box_size(&self, size: Vec2<u16>) -> Size {
+        match self.radius {
+            Some(radius) => {
+                let diameter = radius.to_pixels(size) * 2.0;
+                Size::new(diameter / size.x.max(1) as f32, diameter / size.y.max(1) as f32)
+            }
+            None => self.size,
+        }
+    }
+
+    /// Returns the primitives of a ring segment
+    ///
+    /// # Arguments
+    ///
+    /// * `coord` - The top left corner of the box of the outer ellipse (in pixels)
+    /// * `size` - The size of the box of the outer ellipse (in pixels)
+    fn ring_primitives(&self, coord: Coord, size: Size) -> Vec<Primitive> {
+        let radius = size * 0.5;
+        let center = coord + radius;
+
+        let extent = self.extent.to_radians();
+        let segments = ((radius.x.max(radius.y) * extent.abs() / RING_SEGMENT_LENGTH).ceil() as usize).clamp(1, RING_MAX_SEGMENTS);
+        let ellipse = |scale: f32| -> Vec<XPoint> {
+            (0..=segments)
+                .map(|step| {
+                    let angle = self.start_angle.to_radians() + extent * step as f32 / segments as f32;
+                    XPoint {
+                        x: (center.x + radius.x * scale * angle.cos()).round() as i16,
+                        y: (center.y - radius.y * scale * angle.sin()).round() as i16,
+                    }
+                })
+                .collect()
+        };
+        let (outer, inner) = (ellipse(1.0), ellipse(self.inner));
+        let full = self.extent.abs() >= 360.0;
+
+        match self.filled {
+            true => {
+                // Outer edge, then inner edge backwards (for a full ring, the radial edges cancel out)
+                let mut points = outer;
+                points.extend(inner.iter().rev());
+                if full {
+                    points.push(points[0]);
+                }
+                let shape = poly_shape(&points);
+                vec![Primitive::FillPolygon(points, shape)]
+            }
+            false if full => vec![Primitive::Line(outer), Primitive::Line(inner)],
+            false => {
+                let mut points = outer;
+                points.extend(inner.iter().rev());
+                points.push(points[0]);
+                vec![Primitive::Line(points)]
+            }
+        }
     }
 
     /// Sets the color of the arc.
     pub fn set_forground_color(&mut self, color: Color) {
         self.forground = color;
+        self.dirty = true;
     }
 
     /// Sets the background color of the arc.
     pub fn set_background_color(&mut self, color: Color) {
         self.background = color;
+        self.dirty = true;
+    }
+
+    /// Returns true if the arc is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the arc.
+    ///
+    /// A hidden arc is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the arc captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the arc captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
     }
 
 }
@@ -212,42 +567,58 @@ impl<C: Connection> Shape<C> for Arc {
     ///
     /// A `Result` indicating success or an error.
     fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let primitives = <Self as Shape<C>>::primitives(self, drawable.size()).unwrap_or_default();
+        Batch::draw(conn, gc, drawable, &primitives)
+    }
+
+    /// Returns the arc as a single `poly_arc` or `poly_fill_arc` element.
+    fn primitives(&self, size: Vec2<u16>) -> Option<Vec<Primitive>> {
+        self.window.set(Some(size));
+        let box_size = self.box_size(size);
         let coord = self
             .position
-            .top_left(&self.anchor, &self.size)
-            .to_real_coord(drawable.size());
-        let size = self.size.to_real_size(drawable.size());
+            .top_left(&self.anchor, &box_size)
+            .to_real_coord(size);
+        let size = box_size.to_real_size(size);
 
         let (x, y) = (coord.x as i16, coord.y as i16);
         let (width, height) = (size.x as u16, size.y as u16);
 
-        match self.filled {
-            true => conn.poly_fill_arc(
-                drawable.id(),
-                gc.gcontext(),
-                &[XArc {
-                    x,
-                    y,
-                    width,
-                    height,
-                    angle1: (self.start_angle * 64.0) as i16,
-                    angle2: (self.end_angle * 64.0) as i16,
-                }],
-            )?,
-            false => conn.poly_arc(
-                drawable.id(),
-                gc.gcontext(),
-                &[XArc {
-                    x,
-                    y,
-                    width,
-                    height,
-                    angle1: (self.start_angle * 64.0) as i16,
-                    angle2: (self.end_angle * 64.0) as i16,
-                }],
-            )?,
+        if self.inner > 0.0 {
+            return Some(self.ring_primitives(coord, size));
+        }
+
+        let arc = XArc {
+            x,
+            y,
+            width,
+            height,
+            angle1: to_x_angle(self.start_angle),
+            angle2: to_x_angle(self.extent),
         };
 
+        Some(vec![match self.filled {
+            true => Primitive::FillArc(arc),
+            false => Primitive::Arc(arc),
+        }])
+    }
+
+    /// Returns the arc mode of filled arcs (rings and outlines keep the default one).
+    fn arc_mode(&self) -> Option<ArcMode> {
+        (self.filled && self.inner == 0.0).then_some(self.mode)
+    }
+
+    /// Draws the arc on a software canvas (chords are not part of the primitives).
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        for primitive in <Self as Shape<C>>::primitives(self, canvas.size()).unwrap_or_default() {
+            match (&primitive, <Self as Shape<C>>::arc_mode(self)) {
+                (Primitive::FillArc(arc), Some(ArcMode::Chord)) => {
+                    let rect = Rect::new(arc.x as i32, arc.y as i32, arc.width as i32, arc.height as i32);
+                    canvas.fill_chord(rect, arc.angle1 as f32 / 64.0, arc.angle2 as f32 / 64.0, &self.forground);
+                }
+                _ => canvas.draw_primitive(&primitive, &self.forground),
+            }
+        }
         Ok(())
     }
 
@@ -265,14 +636,69 @@ impl<C: Connection> Shape<C> for Arc {
         &self.background
     }
 
-    /// Returns the shape size.
+    /// Returns the size of the box of the arc (in percentage of the window).
+    ///
+    /// The box of a circle depends on the size of the window, the last one the arc was measured
+    /// or drawn on is used (the size relative to the reference dimension of the radius until then).
     fn size(&self) -> Size {
-        self.size
+        match self.window.get() {
+            Some(window) => self.box_size(window),
+            None => self.size,
+        }
     }
 
-    /// Resizes the shape to the specified size.
+    /// Records the size of the window, for the size of the circles.
+    fn measure(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<(), Box<dyn Error>> {
+        self.window.set(Some(size));
+        Ok(())
+    }
+
+    /// Resizes the shape to the specified size (in percentage of the window).
+    ///
+    /// A circle fits in the size: its diameter is the smallest side of the size, in pixels.
     fn set_size(&mut self, size: Size) {
-        self.size = size;
+        match (self.radius, self.window.get()) {
+            (Some(radius), Some(window)) => {
+                let diameter = (size.x * window.x as f32).min(size.y * window.y as f32);
+                let reference = radius.reference(window.convert()).max(1.0);
+                self.set_radius(radius.with_value(diameter / 2.0 / reference));
+            }
+            _ => Arc::set_size(self, size),
+        }
+    }
+
+    /// Adapts the arc to a new window size.
+    ///
+    /// A circle keeps its radius in pixels along the dimensions kept by the policy,
+    /// and stays round along the others.
+    fn resize(&mut self, policy: ResizePolicy, previous: Vec2<u16>, size: Vec2<u16>) {
+        let scale = policy.scale(previous, size);
+        self.window.set(Some(size));
+        let Some(radius) = self.radius else {
+            // Ellipses follow the window like the other shapes
+            self.size = self.size.hammard(scale);
+            self.position = self.position.hammard(scale);
+            self.dirty = true;
+            return;
+        };
+
+        // Size of the window as seen by the kept dimensions
+        let window: Vec2<f32> = size.convert();
+        let reference = radius.reference(window.hammard(scale)) / radius.reference(window).max(1.0);
+        self.set_radius(radius.with_value(radius.value() * reference));
+        self.position = self.position.hammard(scale);
+    }
+
+    /// Returns the box of the arc (a square for circles).
+    fn bounds(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        self.window.set(Some(size));
+        let box_size = self.box_size(size);
+        let coord = self
+            .position
+            .top_left(&self.anchor, &box_size)
+            .to_real_coord(size);
+
+        Ok(Rect::from_real(coord, box_size.to_real_size(size)))
     }
     
     fn anchor(&self) -> &Anchor {
@@ -285,5 +711,164 @@ impl<C: Connection> Shape<C> for Arc {
     
     fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    /// Returns the box of the single primitive of an arc
+    fn arc_box(arc: &Arc, size: Vec2<u16>) -> (i16, i16, u16, u16) {
+        match Shape::<RustConnection>::primitives(arc, size).unwrap()[..] {
+            [Primitive::FillArc(XArc { x, y, width, height, .. }) | Primitive::Arc(XArc { x, y, width, height, .. })] => {
+                (x, y, width, height)
+            }
+            _ => panic!("Expected a single arc"),
+        }
+    }
+
+    #[test]
+    fn test_angles() {
+        let arc = Arc::filled(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), -90.0, 720.0, Color::WHITE).unwrap();
+        assert_eq!(arc.borrow().angles(), (270.0, 360.0));
+
+        // Large angles do not overflow the protocol unit
+        match Shape::<RustConnection>::primitives(&*arc.borrow(), Vec2::new(10, 10)).unwrap()[..] {
+            [Primitive::FillArc(arc)] => assert_eq!((arc.angle1, arc.angle2), (270 * 64, 360 * 64)),
+            _ => panic!("Expected a single arc"),
+        }
+
+        arc.borrow_mut().set_angles(Angle::Radians(std::f32::consts::PI), Angle::Radians(-std::f32::consts::FRAC_PI_2));
+        let (start, extent) = arc.borrow().angles();
+        assert!((start - 180.0).abs() < 1e-3 && (extent + 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_chord() {
+        let arc = Arc::filled(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), 0.0, 90.0, Color::WHITE).unwrap();
+        arc.borrow_mut().set_arc_mode(ArcMode::Chord);
+        assert_eq!(Shape::<RustConnection>::arc_mode(&*arc.borrow()), Some(ArcMode::Chord));
+
+        let mut canvas = Canvas::new(Vec2::new(20, 20));
+        Shape::<RustConnection>::rasterize(&*arc.borrow(), &mut canvas).unwrap();
+        assert!(canvas.is_masked(15, 4));
+        assert!(!canvas.is_masked(11, 8));
+    }
+
+    #[test]
+    fn test_ring() {
+        let ring = Arc::ring(Anchor::Center, Coord::new(0.5, 0.5), 0.5, 0.25, 0.0, 360.0, Color::WHITE).unwrap();
+        assert_eq!(Shape::<RustConnection>::arc_mode(&*ring.borrow()), None);
+
+        let mut canvas = Canvas::new(Vec2::new(40, 40));
+        Shape::<RustConnection>::rasterize(&*ring.borrow(), &mut canvas).unwrap();
+
+        // The hole is not filled
+        assert!(!canvas.is_masked(20, 20));
+        assert!(canvas.is_masked(20, 3));
+        assert!(canvas.is_masked(3, 20));
+        assert!(!canvas.is_masked(1, 1));
+
+        // Upper half only
+        ring.borrow_mut().set_angles(0.0, 180.0);
+        let mut canvas = Canvas::new(Vec2::new(40, 40));
+        Shape::<RustConnection>::rasterize(&*ring.borrow(), &mut canvas).unwrap();
+        assert!(canvas.is_masked(20, 3));
+        assert!(!canvas.is_masked(20, 36));
+
+        assert!(Arc::ring(Anchor::Center, Coord::new(0.5, 0.5), 0.25, 0.5, 0.0, 360.0, Color::WHITE).is_err());
+        assert!(Arc::ring(Anchor::Center, Coord::new(0.5, 0.5), CircleRadius::Width(0.5), CircleRadius::Height(0.25), 0.0, 360.0, Color::WHITE).is_err());
+    }
+
+    #[test]
+    fn test_circle_is_round() {
+        let circle = Arc::filled_circle(Anchor::Center, Coord::new(0.5, 0.5), 0.25, Color::WHITE).unwrap();
+
+        // The radius is relative to the smallest dimension
+        assert_eq!(arc_box(&circle.borrow(), Vec2::new(1600, 900)), (575, 225, 450, 450));
+
+        circle.borrow_mut().set_radius(CircleRadius::Width(0.125));
+        assert_eq!(arc_box(&circle.borrow(), Vec2::new(1600, 900)), (600, 250, 400, 400));
+
+        circle.borrow_mut().set_radius(CircleRadius::Height(0.125));
+        assert_eq!(arc_box(&circle.borrow(), Vec2::new(1600, 900)), (687, 337, 225, 225));
+    }
+
+    #[test]
+    fn test_circle_size() {
+        let circle = Arc::filled_circle(Anchor::Center, Coord::new(0.5, 0.5), 0.25, Color::WHITE).unwrap();
+        let window = Vec2::new(200, 100);
+        Shape::<RustConnection>::resize(&mut *circle.borrow_mut(), ResizePolicy::KeepAspectRatio, window, window);
+
+        // A square of 50 pixels, in percentage of the window
+        let size = Shape::<RustConnection>::size(&*circle.borrow());
+        assert_eq!(size, Size::new(0.25, 0.5));
+
+        Shape::<RustConnection>::set_size(&mut *circle.borrow_mut(), size);
+        assert_eq!(circle.borrow().radius(), Some(&CircleRadius::Min(0.25)));
+
+        // The circle fits in the size
+        Shape::<RustConnection>::set_size(&mut *circle.borrow_mut(), Size::new(0.5, 1.0));
+        assert_eq!(circle.borrow().radius(), Some(&CircleRadius::Min(0.5)));
+        assert_eq!(Shape::<RustConnection>::size(&*circle.borrow()), Size::new(0.5, 1.0));
+        assert_eq!(arc_box(&circle.borrow(), window), (50, 0, 100, 100));
+    }
+
+    #[test]
+    fn test_circle_resize() {
+        let (previous, size) = (Vec2::new(800, 400), Vec2::new(1600, 600));
+
+        for (policy, diameter) in [
+            (ResizePolicy::KeepAspectRatio, 300),
+            (ResizePolicy::KeepWidth, 300),
+            (ResizePolicy::KeepHeight, 200),
+            (ResizePolicy::KeepBoth, 200),
+        ] {
+            let circle = Arc::circle(Anchor::NorthWest, Coord::new(0.0, 0.0), 0.25, Color::WHITE, Color::BLACK).unwrap();
+            assert_eq!(arc_box(&circle.borrow(), previous), (0, 0, 200, 200));
+
+            Shape::<RustConnection>::resize(&mut *circle.borrow_mut(), policy, previous, size);
+            let (_, _, width, height) = arc_box(&circle.borrow(), size);
+            assert_eq!((width, height), (diameter, diameter), "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn test_ellipse_resize() {
+        let arc = Arc::filled(Anchor::NorthWest, Coord::new(0.5, 0.5), Size::new(0.5, 0.5), 0.0, 360.0, Color::WHITE).unwrap();
+        Shape::<RustConnection>::resize(&mut *arc.borrow_mut(), ResizePolicy::KeepBoth, Vec2::new(100, 100), Vec2::new(200, 400));
+
+        assert_eq!(arc_box(&arc.borrow(), Vec2::new(200, 400)), (50, 50, 50, 50));
     }
 }
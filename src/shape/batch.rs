@@ -0,0 +1,320 @@
+//! Primitive batching module
+//!
+//! Shapes describe what they draw as a list of `Primitive`s (the elements of the core X11 `poly_*` requests).
+//! The `Batch` accumulates consecutive primitives of the same kind and sends them in a single request,
+//! instead of one request per shape.
+//!
+//! The batch must be flushed before any change of the graphics context, as the server draws
+//! the whole request with the graphics context state at the time of the request.
+//!
+//! The grouping is decided without the server (`queue` and `set_state` return the primitives to send),
+//! the requests are sent by `send`.
+
+use std::{error::Error, mem::discriminant};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{
+        Arc as XArc, ConnectionExt, CoordMode, Point as XPoint, PolyShape, Rectangle as XRectangle, Segment as XSegment,
+    },
+};
+
+use crate::drawable::Drawable;
+
+use super::{ArcMode, FillRule, GcontextWrapperExt, LineStyle, XColor};
+
+/// Maximum number of elements sent in a single request
+///
+/// Keeps the requests well below the maximum request length of the server.
+const MAX_BATCH_SIZE: usize = 4096;
+
+/// Represents a drawing primitive (an element of a core X11 `poly_*` request)
+#[derive(Clone)]
+pub enum Primitive {
+    /// Outline of a rectangle (`poly_rectangle`)
+    Rectangle(XRectangle),
+    /// Filled rectangle (`poly_fill_rectangle`)
+    FillRectangle(XRectangle),
+    /// Outline of an arc (`poly_arc`)
+    Arc(XArc),
+    /// Filled arc (`poly_fill_arc`)
+    FillArc(XArc),
+    /// Line between two points (`poly_segment`)
+    Segment(XSegment),
+    /// Connected lines, joined at the corners (`poly_line`, one request per polyline)
+    Line(Vec<XPoint>),
+    /// Filled polygon, with the shape hint of the server (`fill_poly`, one request per polygon)
+    FillPolygon(Vec<XPoint>, PolyShape),
+}
+
+/// The state of the graphics context used to draw the primitives
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct BatchState {
+    pub foreground: Option<XColor>,
+    pub background: Option<XColor>,
+    pub line_style: LineStyle,
+    pub fill_rule: FillRule,
+    pub arc_mode: ArcMode,
+}
+
+/// Accumulates primitives of the same kind to send them in a single request
+#[derive(Default)]
+pub struct Batch {
+    /// The pending primitives (all of the same kind)
+    pending: Vec<Primitive>,
+    /// The state of the graphics context the pending primitives are drawn with
+    state: Option<BatchState>,
+}
+
+impl Batch {
+    /// Creates a new empty batch
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            state: None,
+        }
+    }
+
+    /// Draws the given primitives right away
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `gc` - The graphics context used for drawing.
+    /// * `drawable` - The drawable object on which the primitives will be drawn.
+    /// * `primitives` - The primitives to draw.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requests could not be sent.
+    pub fn draw<C: Connection>(
+        conn: &C,
+        gc: &GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        primitives: &[Primitive],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut batch = Batch::new();
+        for primitive in primitives {
+            batch.push(conn, gc, drawable, primitive.clone())?;
+        }
+        batch.flush(conn, gc, drawable)
+    }
+
+    /// Adds a primitive to the batch
+    ///
+    /// The pending primitives are sent first if they are of a different kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pending primitives could not be sent.
+    pub fn push<C: Connection>(
+        &mut self,
+        conn: &C,
+        gc: &GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        primitive: Primitive,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(primitives) = self.queue(primitive) {
+            Self::send(conn, gc, drawable, &primitives)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a primitive to the pending primitives
+    ///
+    /// # Returns
+    ///
+    /// The pending primitives to send before the new one (of a different kind, or a full batch)
+    pub(crate) fn queue(&mut self, primitive: Primitive) -> Option<Vec<Primitive>> {
+        let same_kind = self
+            .pending
+            .first()
+            .map(|first| discriminant(first) == discriminant(&primitive))
+            .unwrap_or(true);
+
+        let flushed = (!same_kind || self.pending.len() >= MAX_BATCH_SIZE).then(|| self.take());
+        self.pending.push(primitive);
+        flushed
+    }
+
+    /// Sets the state of the graphics context used to draw the next primitives
+    ///
+    /// # Returns
+    ///
+    /// The pending primitives to send before the graphics context changes (None if the state is the same)
+    pub(crate) fn set_state(&mut self, state: BatchState) -> Option<Vec<Primitive>> {
+        if self.state.as_ref() == Some(&state) {
+            return None;
+        }
+        self.state = Some(state);
+        (!self.pending.is_empty()).then(|| self.take())
+    }
+
+    /// Removes the pending primitives
+    pub(crate) fn take(&mut self) -> Vec<Primitive> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Returns true if no primitive is pending
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Sends the pending primitives in a single request
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent.
+    pub fn flush<C: Connection>(
+        &mut self,
+        conn: &C,
+        gc: &GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+    ) -> Result<(), Box<dyn Error>> {
+        let primitives = self.take();
+        Self::send(conn, gc, drawable, &primitives)
+    }
+
+    /// Sends primitives of the same kind in a single request (one request per polyline or polygon)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent.
+    pub(crate) fn send<C: Connection>(
+        conn: &C,
+        gc: &GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        primitives: &[Primitive],
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(first) = primitives.first() else {
+            return Ok(());
+        };
+
+        match first {
+            Primitive::Rectangle(_) | Primitive::FillRectangle(_) => {
+                let rectangles: Vec<XRectangle> = primitives
+                    .iter()
+                    .filter_map(|primitive| match primitive {
+                        Primitive::Rectangle(rectangle) | Primitive::FillRectangle(rectangle) => {
+                            Some(*rectangle)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+
+                if let Primitive::Rectangle(_) = first {
+                    conn.poly_rectangle(drawable.id(), gc.gcontext(), &rectangles)?;
+                } else {
+                    conn.poly_fill_rectangle(drawable.id(), gc.gcontext(), &rectangles)?;
+                }
+            }
+            Primitive::Arc(_) | Primitive::FillArc(_) => {
+                let arcs: Vec<XArc> = primitives
+                    .iter()
+                    .filter_map(|primitive| match primitive {
+                        Primitive::Arc(arc) | Primitive::FillArc(arc) => Some(*arc),
+                        _ => None,
+                    })
+                    .collect();
+
+                if let Primitive::Arc(_) = first {
+                    conn.poly_arc(drawable.id(), gc.gcontext(), &arcs)?;
+                } else {
+                    conn.poly_fill_arc(drawable.id(), gc.gcontext(), &arcs)?;
+                }
+            }
+            Primitive::Segment(_) => {
+                let segments: Vec<XSegment> = primitives
+                    .iter()
+                    .filter_map(|primitive| match primitive {
+                        Primitive::Segment(segment) => Some(*segment),
+                        _ => None,
+                    })
+                    .collect();
+
+                conn.poly_segment(drawable.id(), gc.gcontext(), &segments)?;
+            }
+            Primitive::Line(_) => {
+                // The lines of a single request are connected, so polylines can't be merged
+                for primitive in primitives {
+                    if let Primitive::Line(points) = primitive {
+                        conn.poly_line(CoordMode::ORIGIN, drawable.id(), gc.gcontext(), points)?;
+                    }
+                }
+            }
+            Primitive::FillPolygon(..) => {
+                for primitive in primitives {
+                    if let Primitive::FillPolygon(points, shape) = primitive {
+                        conn.fill_poly(drawable.id(), gc.gcontext(), *shape, CoordMode::ORIGIN, points)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle() -> Primitive {
+        Primitive::FillRectangle(XRectangle { x: 0, y: 0, width: 1, height: 1 })
+    }
+
+    fn arc() -> Primitive {
+        Primitive::Arc(XArc { x: 0, y: 0, width: 2, height: 2, angle1: 0, angle2: 360 * 64 })
+    }
+
+    #[test]
+    fn test_kinds() {
+        let mut batch = Batch::new();
+        assert!(batch.queue(rectangle()).is_none());
+        assert!(batch.queue(rectangle()).is_none());
+
+        // A different kind sends the pending primitives
+        let flushed = batch.queue(arc()).unwrap();
+        assert_eq!(flushed.len(), 2);
+        assert!(matches!(flushed[0], Primitive::FillRectangle(_)));
+
+        // Outlines and filled rectangles are different requests
+        let outline = Primitive::Rectangle(XRectangle { x: 0, y: 0, width: 1, height: 1 });
+        assert!(matches!(batch.queue(outline).unwrap()[..], [Primitive::Arc(_)]));
+        assert_eq!(batch.take().len(), 1);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_split() {
+        let mut batch = Batch::new();
+        for _ in 0..MAX_BATCH_SIZE {
+            assert!(batch.queue(rectangle()).is_none());
+        }
+
+        // The batch is full
+        assert_eq!(batch.queue(rectangle()).unwrap().len(), MAX_BATCH_SIZE);
+        assert_eq!(batch.take().len(), 1);
+    }
+
+    #[test]
+    fn test_state() {
+        let white = BatchState { foreground: Some(0xFFFFFF), ..BatchState::default() };
+        let mut batch = Batch::new();
+        assert!(batch.set_state(white.clone()).is_none());
+        batch.queue(rectangle());
+        batch.queue(rectangle());
+
+        // Same state, the primitives stay in the batch
+        assert!(batch.set_state(white.clone()).is_none());
+        assert!(!batch.is_empty());
+
+        // The pending primitives are drawn before the foreground changes
+        let red = BatchState { foreground: Some(0xFF0000), ..white };
+        assert_eq!(batch.set_state(red.clone()).unwrap().len(), 2);
+        assert!(batch.is_empty());
+
+        // Nothing to send without pending primitives
+        assert!(batch.set_state(BatchState { fill_rule: FillRule::Winding, ..red }).is_none());
+    }
+}
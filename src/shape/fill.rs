@@ -0,0 +1,485 @@
+//! Fill module
+//!
+//! This module defines how the inside of the shapes is painted:
+//! - solid: with the forground color of the shape,
+//! - gradient: with a linear or radial gradient,
+//! - stipple: with a 1-bit pattern, the forground color where the bits are set and the background color (opaque)
+//!   or nothing (see through) elsewhere,
+//! - tile: with an image repeated over the shape.
+//!
+//! Patterns and tiles start at the top left corner of the box of the shape, so they follow the shape when it moves.
+//! Common patterns (hatching, checkerboards, dots) are built in.
+
+use std::error::Error;
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{ConnectionExt, CreateGCAux},
+};
+
+use crate::{
+    codec::{ximage, Bitmap},
+    color::Depth,
+    drawable::Drawable,
+    math::rect::Rect,
+    Color,
+};
+
+use super::{Batch, GcFill, GcontextWrapperExt, Gradient, Primitive, DEFAULT_ALPHA_THRESHOLD};
+
+/// Ordered dithering thresholds (8x8 Bayer matrix)
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Describe the direction of the lines of a hatching pattern
+///
+/// - Horizontal: `-` lines
+/// - Vertical: `|` lines
+/// - Forward: `/` lines
+/// - Backward: `\` lines
+/// - Cross: `+` grid
+/// - DiagonalCross: `x` grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hatch {
+    Horizontal,
+    Vertical,
+    Forward,
+    Backward,
+    Cross,
+    DiagonalCross,
+}
+
+/// Represents a 1-bit pattern, repeated over the shape
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    /// The width of the pattern (in pixels)
+    width: u16,
+    /// The height of the pattern (in pixels)
+    height: u16,
+    /// The bits of the pattern (row major, true where the forground color is drawn)
+    bits: Vec<bool>,
+}
+
+impl Pattern {
+    /// Creates a new pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the pattern
+    /// * `height` - The height of the pattern
+    /// * `bits` - The bits of the pattern (row major, true where the forground color is drawn)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is empty or the bit count does not match the size.
+    pub fn new(width: u16, height: u16, bits: Vec<bool>) -> Result<Self, Box<dyn Error>> {
+        if width == 0 || height == 0 {
+            Err("Pattern must not be empty")?;
+        }
+        if bits.len() != width as usize * height as usize {
+            Err("Bit count does not match the pattern size")?;
+        }
+
+        Ok(Self { width, height, bits })
+    }
+
+    /// Creates a pattern from its rows, `#` (or `1`) marks the bits that are set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rows are empty or do not have the same length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::shape::Pattern;
+    ///
+    /// let pattern = Pattern::from_rows(&["#..", ".#.", "..#"]).unwrap();
+    /// assert!(pattern.is_set(4, 1));
+    /// ```
+    pub fn from_rows(rows: &[&str]) -> Result<Self, Box<dyn Error>> {
+        let width = rows.first().map(|row| row.chars().count()).unwrap_or_default();
+        if rows.iter().any(|row| row.chars().count() != width) {
+            Err("Pattern rows must have the same length")?;
+        }
+
+        let bits = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|bit| bit == '#' || bit == '1'))
+            .collect();
+        Self::new(
+            u16::try_from(width).map_err(|_| "Pattern is too wide")?,
+            u16::try_from(rows.len()).map_err(|_| "Pattern is too high")?,
+            bits,
+        )
+    }
+
+    /// Creates a checkerboard pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - The size of the squares (in pixels, at least 1)
+    pub fn checkerboard(cell: u16) -> Self {
+        let cell = cell.max(1);
+        let size = cell.saturating_mul(2);
+        Self::generate(size, size, |x, y| (x / cell + y / cell).is_multiple_of(2))
+    }
+
+    /// Creates a hatching pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `hatch` - The direction of the lines
+    /// * `spacing` - The distance between two lines (in pixels, at least 2)
+    /// * `thickness` - The thickness of the lines (in pixels, at least 1)
+    pub fn hatch(hatch: Hatch, spacing: u16, thickness: u16) -> Self {
+        let spacing = spacing.max(2);
+        let thickness = thickness.clamp(1, spacing - 1);
+        let line = |value: u16| value % spacing < thickness;
+
+        Self::generate(spacing, spacing, |x, y| match hatch {
+            Hatch::Horizontal => line(y),
+            Hatch::Vertical => line(x),
+            Hatch::Forward => line((x + y) % spacing),
+            Hatch::Backward => line((x + spacing - y) % spacing),
+            Hatch::Cross => line(x) || line(y),
+            Hatch::DiagonalCross => line((x + y) % spacing) || line((x + spacing - y) % spacing),
+        })
+    }
+
+    /// Creates a pattern of square dots
+    ///
+    /// # Arguments
+    ///
+    /// * `spacing` - The distance between two dots (in pixels, at least 2)
+    /// * `size` - The size of the dots (in pixels, at least 1)
+    pub fn dots(spacing: u16, size: u16) -> Self {
+        let spacing = spacing.max(2);
+        let size = size.clamp(1, spacing - 1);
+        Self::generate(spacing, spacing, |x, y| x < size && y < size)
+    }
+
+    /// Creates an ordered dithering pattern approximating a coverage
+    ///
+    /// The bits of the 8x8 pattern are spread evenly, a coverage of 128 sets every other pixel (checkerboard).
+    ///
+    /// # Arguments
+    ///
+    /// * `alpha` - The coverage, from 0 (no bit set) to 255 (every bit set)
+    pub fn dither(alpha: u8) -> Self {
+        // Number of bits set out of 64
+        let level = (alpha as u32 * 64 + 127) / 255;
+        Self::generate(8, 8, |x, y| (BAYER[y as usize][x as usize] as u32) < level)
+    }
+
+    /// Creates a pattern from a function returning its bits
+    fn generate(width: u16, height: u16, bit: impl Fn(u16, u16) -> bool) -> Self {
+        let bits = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| bit(x, y)).collect();
+        Self { width, height, bits }
+    }
+
+    /// Returns the width of the pattern
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height of the pattern
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Returns the bits of the pattern (row major)
+    pub fn bits(&self) -> &[bool] {
+        &self.bits
+    }
+
+    /// Returns true if the bit of a pixel is set (the pattern is repeated)
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column, relative to the origin of the pattern
+    /// * `y` - The row, relative to the origin of the pattern
+    pub fn is_set(&self, x: i32, y: i32) -> bool {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        let y = y.rem_euclid(self.height as i32) as usize;
+        self.bits[y * self.width as usize + x]
+    }
+}
+
+/// Describe how the inside of a shape is painted
+///
+/// - Solid: With the forground color of the shape
+/// - Gradient: With a gradient (relative to the box of the shape)
+/// - Stipple: With the forground color where the bits of the pattern are set,
+///   and the background color elsewhere if `opaque` (otherwise the gaps are see through)
+/// - Tile: With an image, repeated over the shape (its transparent pixels are removed from the window shape)
+///
+/// Except for the stipples, the forground color of the shape is used for the shape mask.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Fill {
+    #[default]
+    Solid,
+    Gradient(Gradient),
+    Stipple { pattern: Pattern, opaque: bool },
+    Tile(Bitmap),
+}
+
+impl Fill {
+    /// Creates a see through stipple fill
+    pub fn stipple(pattern: Pattern) -> Self {
+        Self::Stipple { pattern, opaque: false }
+    }
+
+    /// Creates an opaque stipple fill (the background color of the shape fills the gaps)
+    pub fn opaque_stipple(pattern: Pattern) -> Self {
+        Self::Stipple { pattern, opaque: true }
+    }
+
+    /// Returns how a pixel of the shape is painted on a canvas
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column, relative to the top left corner of the box of the shape
+    /// * `y` - The row, relative to the top left corner of the box of the shape
+    /// * `rect` - The box of the shape (in pixels)
+    /// * `forground` - The forground color of the shape
+    /// * `background` - The background color of the shape
+    ///
+    /// # Returns
+    ///
+    /// The `[r, g, b, a]` components of the pixel (`None` keeps the previous color) and its mask bit,
+    /// `None` if the pixel is left untouched
+    pub(crate) fn pixel(&self, x: i32, y: i32, rect: Rect, forground: &Color, background: &Color) -> Option<(Option<[u8; 4]>, bool)> {
+        // Transparent parts keep the previous color and are removed from the mask
+        let plain = |color: &Color| ((color != &Color::TRANSPARENT).then(|| color.rgba()), color.value(&Depth::D1) != 0);
+        let masked = forground.value(&Depth::D1) != 0;
+
+        match self {
+            Fill::Solid => Some(plain(forground)),
+            Fill::Gradient(gradient) => {
+                let (width, height) = (rect.width.max(1) as f32, rect.height.max(1) as f32);
+                let point = ((x as f32 + 0.5) / width, (y as f32 + 0.5) / height).into();
+                Some((Some(gradient.color_at(point)), masked))
+            }
+            Fill::Stipple { pattern, opaque } => match (pattern.is_set(x, y), opaque) {
+                (true, _) => Some(plain(forground)),
+                (false, true) => Some(plain(background)),
+                (false, false) => None,
+            },
+            Fill::Tile(bitmap) => {
+                if bitmap.width() == 0 || bitmap.height() == 0 {
+                    return None;
+                }
+                let column = x.rem_euclid(bitmap.width() as i32) as usize;
+                let row = y.rem_euclid(bitmap.height() as i32) as usize;
+                let pixel = bitmap.pixels()[row * bitmap.width() as usize + column];
+                (pixel[3] > DEFAULT_ALPHA_THRESHOLD).then_some((Some(pixel), masked))
+            }
+        }
+    }
+
+    /// Draws primitives with the fill (core protocol)
+    ///
+    /// Gradients and images are uploaded in a tile pixmap at the depth of the drawable,
+    /// patterns in a stipple pixmap. On the shape mask (depth 1), gradients are drawn with the forground color,
+    /// and images are stippled with their opaque pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection
+    /// * `gc` - The graphics context used for drawing (its colors are set, its fill style is restored)
+    /// * `drawable` - The drawable to draw on
+    /// * `rect` - The box of the shape (in pixels)
+    /// * `primitives` - The primitives to fill
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pixmap could not be created or the primitives could not be drawn.
+    pub(crate) fn draw<C: Connection>(
+        &self,
+        conn: &C,
+        gc: &mut GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        rect: Rect,
+        primitives: &[Primitive],
+    ) -> Result<(), Box<dyn Error>> {
+        let depth = drawable.depth();
+        let origin = (rect.x as i16, rect.y as i16);
+
+        match self {
+            Fill::Solid => Batch::draw(conn, gc, drawable, primitives),
+            Fill::Gradient(_) if depth == Depth::D1 => Batch::draw(conn, gc, drawable, primitives),
+            Fill::Gradient(gradient) => {
+                // The strokes may go past the box, the tile is never used outside of the drawable
+                let area = Rect::new(0, 0, drawable.width() as i32, drawable.height() as i32);
+                let Some(area) = rect.inflate(gc.line_style().margin()).intersection(&area).filter(|area| !area.is_empty()) else {
+                    return Ok(());
+                };
+
+                let values = colors(gradient.sample(rect, area)?.pixels(), depth);
+                let tile = Pixels { depth, width: area.width as u16, height: area.height as u16, values };
+                tile.draw(conn, gc, drawable, primitives, |tile| GcFill::Tiled { tile, origin: (area.x as i16, area.y as i16) })
+            }
+            Fill::Stipple { pattern, opaque } => {
+                let values = pattern.bits().iter().map(|bit| *bit as u32).collect();
+                let stipple = Pixels { depth: Depth::D1, width: pattern.width(), height: pattern.height(), values };
+                let opaque = *opaque;
+                stipple.draw(conn, gc, drawable, primitives, |stipple| GcFill::Stippled { stipple, origin, opaque })
+            }
+            Fill::Tile(bitmap) if bitmap.width() == 0 || bitmap.height() == 0 => Ok(()),
+            Fill::Tile(bitmap) if depth == Depth::D1 => {
+                let values = bitmap.pixels().iter().map(|pixel| (pixel[3] > DEFAULT_ALPHA_THRESHOLD) as u32).collect();
+                let stipple = Pixels { depth, width: bitmap.width(), height: bitmap.height(), values };
+                stipple.draw(conn, gc, drawable, primitives, |stipple| GcFill::Stippled { stipple, origin, opaque: false })
+            }
+            Fill::Tile(bitmap) => {
+                let values = colors(bitmap.pixels(), depth);
+                let tile = Pixels { depth, width: bitmap.width(), height: bitmap.height(), values };
+                tile.draw(conn, gc, drawable, primitives, |tile| GcFill::Tiled { tile, origin })
+            }
+        }
+    }
+}
+
+impl From<Gradient> for Fill {
+    fn from(gradient: Gradient) -> Self {
+        Fill::Gradient(gradient)
+    }
+}
+
+/// Converts RGBA pixels to pixel values of the given depth
+fn colors(pixels: &[[u8; 4]], depth: Depth) -> Vec<u32> {
+    pixels
+        .iter()
+        .map(|[r, g, b, a]| Color::RGBA(*r, *g, *b, *a).value(&depth))
+        .collect()
+}
+
+/// The content of a tile or stipple pixmap
+struct Pixels {
+    depth: Depth,
+    width: u16,
+    height: u16,
+    values: Vec<u32>,
+}
+
+impl Pixels {
+    /// Uploads the pixels in a pixmap and draws the primitives with it
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection
+    /// * `gc` - The graphics context used for drawing (its fill style is restored)
+    /// * `drawable` - The drawable to draw on
+    /// * `primitives` - The primitives to fill
+    /// * `fill` - Returns the fill style of the graphics context using the pixmap
+    fn draw<C: Connection>(
+        &self,
+        conn: &C,
+        gc: &mut GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        primitives: &[Primitive],
+        fill: impl Fn(u32) -> GcFill,
+    ) -> Result<(), Box<dyn Error>> {
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(self.depth.value(), pixmap, drawable.id(), self.width, self.height)?;
+
+        // The graphics context of the drawable may be clipped (and has another depth for the stipples)
+        let pixmap_gc = conn.generate_id()?;
+        conn.create_gc(pixmap_gc, pixmap, &CreateGCAux::new())?;
+        let uploaded = ximage::put_image(conn, pixmap, pixmap_gc, self.depth.value(), (0, 0), self.width, &self.values);
+        conn.free_gc(pixmap_gc)?;
+
+        let drawn = uploaded.and_then(|_| {
+            gc.set_fill(conn, fill(pixmap))?;
+            Batch::draw(conn, gc, drawable, primitives)
+        });
+        gc.set_fill(conn, GcFill::Solid)?;
+        conn.free_pixmap(pixmap)?;
+
+        drawn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkerboard() {
+        let pattern = Pattern::checkerboard(2);
+        assert_eq!((pattern.width(), pattern.height()), (4, 4));
+        assert!(pattern.is_set(0, 0));
+        assert!(pattern.is_set(1, 1));
+        assert!(!pattern.is_set(2, 0));
+        assert!(pattern.is_set(2, 2));
+        assert!(!pattern.is_set(-1, 0));
+    }
+
+    #[test]
+    fn test_hatch() {
+        let pattern = Pattern::hatch(Hatch::Forward, 4, 1);
+        assert!(pattern.is_set(0, 0));
+        assert!(pattern.is_set(3, 1));
+        assert!(!pattern.is_set(1, 1));
+
+        let pattern = Pattern::hatch(Hatch::Cross, 4, 1);
+        assert_eq!(pattern.bits().iter().filter(|bit| **bit).count(), 7);
+
+        let pattern = Pattern::hatch(Hatch::Horizontal, 3, 10);
+        assert_eq!(pattern.bits(), &[true, true, true, true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_dither() {
+        let count = |pattern: Pattern| pattern.bits().iter().filter(|bit| **bit).count();
+        assert_eq!(count(Pattern::dither(0)), 0);
+        assert_eq!(count(Pattern::dither(255)), 64);
+        assert_eq!(count(Pattern::dither(64)), 16);
+
+        // Half of the pixels, every other one
+        let half = Pattern::dither(128);
+        assert_eq!(count(half.clone()), 32);
+        let checkerboard = Pattern::checkerboard(1);
+        assert!((0..8).all(|y| (0..8).all(|x| half.is_set(x, y) == checkerboard.is_set(x, y))));
+
+        // Higher coverages keep the bits of the lower ones
+        let (low, high) = (Pattern::dither(50), Pattern::dither(200));
+        assert!(low.bits().iter().zip(high.bits()).all(|(low, high)| !low || *high));
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let pattern = Pattern::from_rows(&["#.", ".#"]).unwrap();
+        assert_eq!(pattern, Pattern::checkerboard(1));
+
+        assert!(Pattern::from_rows(&["#.", "#"]).is_err());
+        assert!(Pattern::from_rows(&[]).is_err());
+    }
+
+    #[test]
+    fn test_pixel() {
+        let rect = Rect::new(0, 0, 10, 10);
+        let stipple = Fill::stipple(Pattern::checkerboard(1));
+        assert_eq!(stipple.pixel(0, 0, rect, &Color::RED, &Color::BLUE), Some((Some(Color::RED.rgba()), true)));
+        assert_eq!(stipple.pixel(1, 0, rect, &Color::RED, &Color::BLUE), None);
+
+        let opaque = Fill::opaque_stipple(Pattern::checkerboard(1));
+        assert_eq!(opaque.pixel(1, 0, rect, &Color::RED, &Color::BLUE), Some((Some(Color::BLUE.rgba()), true)));
+        assert_eq!(opaque.pixel(1, 0, rect, &Color::RED, &Color::TRANSPARENT), Some((None, false)));
+
+        // Transparent pixels of the tiles are left untouched
+        let bitmap = Bitmap::new(2, 1, vec![[1, 2, 3, 0xFF], [4, 5, 6, 0]]).unwrap();
+        let tile = Fill::Tile(bitmap);
+        assert_eq!(tile.pixel(2, 3, rect, &Color::RED, &Color::BLUE), Some((Some([1, 2, 3, 0xFF]), true)));
+        assert_eq!(tile.pixel(-1, 0, rect, &Color::RED, &Color::BLUE), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Gradient module
+//!
+//! This module defines the linear and radial gradients used to fill the shapes.
+//!
+//! The core protocol has no gradients: the gradient is rasterized in a tile pixmap at the depth of
+//! the window, and the shape is drawn with the tiled fill style of the graphics context (see `Fill`).
+//! With the anti-aliased renderer, the gradient pictures of the XRender extension are used instead.
+//!
+//! The points of a gradient are relative to the box of the shape (`(0, 0)` is its top left corner,
+//! `(1, 1)` its bottom right corner), so the gradient follows the shape when it moves or is resized.
+//! Before the first stop and after the last one, the color of the nearest stop is used.
+
+use std::error::Error;
+
+use crate::{codec::Bitmap, math::rect::Rect, Color};
+
+use super::coord::Coord;
+
+/// Describe the geometry of a gradient (relative to the box of the shape)
+///
+/// - Linear: The color changes along the line from `start` to `end`
+/// - Radial: The color changes with the distance to `center` (`radius` is relative to the box size)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientKind {
+    Linear { start: Coord, end: Coord },
+    Radial { center: Coord, radius: f32 },
+}
+
+/// Represents a linear or radial gradient
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    /// The geometry of the gradient
+    kind: GradientKind,
+    /// The color stops, as offsets between 0 and 1 (sorted)
+    stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    /// Creates a linear gradient
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The point of the offset 0 (relative to the box of the shape)
+    /// * `end` - The point of the offset 1 (relative to the box of the shape)
+    /// * `stops` - The colors and their offsets (between 0 and 1, in increasing order)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the points are equal or the stops are invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::Coord, Gradient}, Color};
+    ///
+    /// // Red on the left, yellow in the middle, green on the right
+    /// let gradient = Gradient::linear(
+    ///     Coord::new(0.0, 0.0),
+    ///     Coord::new(1.0, 0.0),
+    ///     &[(0.0, Color::RED), (0.5, Color::YELLOW), (1.0, Color::GREEN)],
+    /// ).unwrap();
+    /// assert_eq!(gradient.color_at(Coord::new(0.5, 0.5)), Color::YELLOW.rgba());
+    /// ```
+    pub fn linear(start: Coord, end: Coord, stops: &[(f32, Color)]) -> Result<Self, Box<dyn Error>> {
+        if start == end {
+            Err("Gradient start and end must differ")?;
+        }
+        Self::create(GradientKind::Linear { start, end }, stops)
+    }
+
+    /// Creates a radial gradient
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - The center of the gradient, the point of the offset 0 (relative to the box of the shape)
+    /// * `radius` - The distance of the offset 1 (relative to the box of the shape, an ellipse for non square boxes)
+    /// * `stops` - The colors and their offsets (between 0 and 1, in increasing order)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the radius is not positive or the stops are invalid.
+    pub fn radial(center: Coord, radius: f32, stops: &[(f32, Color)]) -> Result<Self, Box<dyn Error>> {
+        if radius <= 0.0 || !radius.is_finite() {
+            Err("Gradient radius must be positive")?;
+        }
+        Self::create(GradientKind::Radial { center, radius }, stops)
+    }
+
+    /// Creates a left to right gradient between two colors
+    pub fn horizontal(from: Color, to: Color) -> Self {
+        Self {
+            kind: GradientKind::Linear { start: Coord::new(0.0, 0.0), end: Coord::new(1.0, 0.0) },
+            stops: vec![(0.0, from), (1.0, to)],
+        }
+    }
+
+    /// Creates a top to bottom gradient between two colors
+    pub fn vertical(from: Color, to: Color) -> Self {
+        Self {
+            kind: GradientKind::Linear { start: Coord::new(0.0, 0.0), end: Coord::new(0.0, 1.0) },
+            stops: vec![(0.0, from), (1.0, to)],
+        }
+    }
+
+    fn create(kind: GradientKind, stops: &[(f32, Color)]) -> Result<Self, Box<dyn Error>> {
+        if stops.is_empty() {
+            Err("Gradient needs at least one stop")?;
+        }
+        if stops.iter().any(|(offset, _)| !(0.0..=1.0).contains(offset)) {
+            Err("Gradient stop offsets must be between 0 and 1")?;
+        }
+        if stops.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            Err("Gradient stops must be sorted by offset")?;
+        }
+
+        Ok(Self { kind, stops: stops.to_vec() })
+    }
+
+    /// Returns the geometry of the gradient
+    pub fn kind(&self) -> &GradientKind {
+        &self.kind
+    }
+
+    /// Returns the color stops of the gradient
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+
+    /// Returns the offset of a point of the box (clamped between 0 and 1)
+    fn offset(&self, point: Coord) -> f32 {
+        let offset = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let direction = end - start;
+                (point - start).dot(direction) / direction.dot(direction)
+            }
+            GradientKind::Radial { center, radius } => (point - center).length() / radius,
+        };
+        offset.clamp(0.0, 1.0)
+    }
+
+    /// Returns the color of a point of the box
+    ///
+    /// The colors are interpolated between the stops (not premultiplied).
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The point, relative to the box of the shape
+    ///
+    /// # Returns
+    ///
+    /// The `[r, g, b, a]` components of the color
+    pub fn color_at(&self, point: Coord) -> [u8; 4] {
+        let offset = self.offset(point);
+
+        // Index of the first stop after the offset
+        let next = self.stops.partition_point(|(stop, _)| *stop <= offset);
+        if next == 0 {
+            return self.stops[0].1.rgba();
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1.rgba();
+        }
+
+        let (start, from) = self.stops[next - 1];
+        let (end, to) = self.stops[next];
+        let t = (offset - start) / (end - start);
+        let (from, to) = (from.rgba(), to.rgba());
+
+        let mut color = [0; 4];
+        for channel in 0..4 {
+            color[channel] = (from[channel] as f32 + (to[channel] as f32 - from[channel] as f32) * t).round() as u8;
+        }
+        color
+    }
+
+    /// Rasterizes the gradient on a box of the given size (in pixels)
+    ///
+    /// Every pixel is sampled at its center.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bitmap could not be created.
+    pub fn rasterize(&self, width: u16, height: u16) -> Result<Bitmap, Box<dyn Error>> {
+        let rect = Rect::new(0, 0, width as i32, height as i32);
+        self.sample(rect, rect)
+    }
+
+    /// Rasterizes an area of the gradient
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` - The box of the gradient (in pixels)
+    /// * `area` - The rasterized area (in pixels, usually inside of the box)
+    pub(crate) fn sample(&self, rect: Rect, area: Rect) -> Result<Bitmap, Box<dyn Error>> {
+        let (width, height) = (rect.width.max(1) as f32, rect.height.max(1) as f32);
+
+        let mut pixels = Vec::with_capacity(area.width as usize * area.height as usize);
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                let point = Coord::new(
+                    (x - rect.x) as f32 / width + 0.5 / width,
+                    (y - rect.y) as f32 / height + 0.5 / height,
+                );
+                pixels.push(self.color_at(point));
+            }
+        }
+
+        Bitmap::new(area.width as u16, area.height as u16, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear() {
+        let gradient = Gradient::horizontal(Color::BLACK, Color::WHITE);
+        assert_eq!(gradient.color_at(Coord::new(0.0, 0.3)), [0, 0, 0, 0xFF]);
+        assert_eq!(gradient.color_at(Coord::new(0.5, 0.3)), [0x80, 0x80, 0x80, 0xFF]);
+
+        // Padded outside of the stops
+        assert_eq!(gradient.color_at(Coord::new(-1.0, 0.0)), [0, 0, 0, 0xFF]);
+        assert_eq!(gradient.color_at(Coord::new(2.0, 0.0)), [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let diagonal = Gradient::linear(Coord::new(0.0, 0.0), Coord::new(1.0, 1.0), &[(0.0, Color::BLACK), (1.0, Color::WHITE)]).unwrap();
+        assert_eq!(diagonal.color_at(Coord::new(1.0, 0.0)), diagonal.color_at(Coord::new(0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_stops() {
+        let stops = [(0.25, Color::RED), (0.75, Color::BLUE)];
+        let gradient = Gradient::linear(Coord::new(0.0, 0.0), Coord::new(0.0, 1.0), &stops).unwrap();
+        assert_eq!(gradient.color_at(Coord::new(0.0, 0.1)), Color::RED.rgba());
+        assert_eq!(gradient.color_at(Coord::new(0.0, 0.5)), [0x80, 0, 0x80, 0xFF]);
+        assert_eq!(gradient.color_at(Coord::new(0.0, 0.9)), Color::BLUE.rgba());
+
+        assert!(Gradient::linear(Coord::new(0.0, 0.0), Coord::new(0.0, 1.0), &[]).is_err());
+        assert!(Gradient::linear(Coord::new(0.0, 0.0), Coord::new(0.0, 1.0), &[(0.5, Color::RED), (0.2, Color::BLUE)]).is_err());
+        assert!(Gradient::linear(Coord::new(0.0, 0.0), Coord::new(0.0, 1.0), &[(1.5, Color::RED)]).is_err());
+        assert!(Gradient::linear(Coord::new(0.5, 0.5), Coord::new(0.5, 0.5), &stops).is_err());
+    }
+
+    #[test]
+    fn test_radial() {
+        let gradient = Gradient::radial(Coord::new(0.5, 0.5), 0.5, &[(0.0, Color::WHITE), (1.0, Color::TRANSPARENT)]).unwrap();
+        assert_eq!(gradient.color_at(Coord::new(0.5, 0.5)), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(gradient.color_at(Coord::new(0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(gradient.color_at(Coord::new(0.75, 0.5)), gradient.color_at(Coord::new(0.5, 0.25)));
+
+        assert!(Gradient::radial(Coord::new(0.5, 0.5), 0.0, &[(0.0, Color::WHITE)]).is_err());
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let bitmap = Gradient::vertical(Color::BLACK, Color::WHITE).rasterize(2, 4).unwrap();
+        assert_eq!(bitmap.pixels().len(), 8);
+        assert_eq!(bitmap.pixels()[0], bitmap.pixels()[1]);
+        assert!(bitmap.pixels()[0][0] < bitmap.pixels()[2][0]);
+        assert!(bitmap.pixels()[4][0] < bitmap.pixels()[6][0]);
+    }
+}
@@ -0,0 +1,358 @@
+//! Image shape module
+//!
+//! This module is used to define the image shape object used by the overlay library
+//!
+//! The image is scaled to its box and uploaded with `put_image` at the depth of the window.
+//! Only the pixels whose alpha is above the alpha threshold are drawn, so the transparent areas
+//! of the image are removed from the shape of the window (see and click through).
+
+use std::{cell::RefCell, error::Error, path::Path, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::Rectangle as XRectangle,
+};
+
+use crate::{
+    codec::{ximage, Bitmap},
+    color::{Color, Depth},
+    drawable::{canvas::Canvas, Drawable},
+    math::vec::Vec2,
+};
+
+use super::{
+    coord::{Anchor, Coord, CoordExt, Size, SizeExt}, Batch, GcontextWrapperExt, Primitive, Shape
+};
+
+/// Default alpha threshold (pixels more than half opaque are drawn)
+pub const DEFAULT_ALPHA_THRESHOLD: u8 = 127;
+
+/// Represents an image shape object used by the overlay library.
+pub struct Image {
+    anchor: Anchor, // Describes where the coordinate is relative to the shape
+    position: Coord,
+    size: Size,
+    bitmap: Bitmap,
+    alpha_threshold: u8,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
+}
+
+impl Image {
+    /// Creates a new image shape object.
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the image.
+    /// * `position` - The position of the image.
+    /// * `size` - The size of the image (the bitmap is scaled to it).
+    /// * `bitmap` - The content of the image.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Image` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Image}, Bitmap};
+    ///
+    /// let bitmap = Bitmap::open("icon.bmp").unwrap();
+    /// let image = Image::new(Anchor::NorthWest, Coord::new(0.1, 0.1), Size::new(0.1, 0.1), bitmap).unwrap();
+    /// ```
+    pub fn new(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        bitmap: Bitmap,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        if bitmap.width() == 0 || bitmap.height() == 0 {
+            Err("Image must not be empty")?;
+        }
+
+        Ok(Rc::new(RefCell::new(Self {
+            anchor,
+            position,
+            size,
+            bitmap,
+            alpha_threshold: DEFAULT_ALPHA_THRESHOLD,
+            visible: true,
+            interactive: false,
+            dirty: true,
+        })))
+    }
+
+    /// Creates a new image shape object from an image file.
+    ///
+    /// Supported formats are binary PPM and PGM, BMP, and PNG (with the `png` feature).
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the image.
+    /// * `position` - The position of the image.
+    /// * `size` - The size of the image (the file is scaled to it).
+    /// * `path` - The path of the image file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Image` object, or a `Box` containing an error if the file could not be read or decoded.
+    pub fn open<P: AsRef<Path>>(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        path: P,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::new(anchor, position, size, Bitmap::open(path)?)
+    }
+
+    /// Returns the content of the image.
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.bitmap
+    }
+
+    /// Replaces the content of the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bitmap is empty.
+    pub fn set_bitmap(&mut self, bitmap: Bitmap) -> Result<(), Box<dyn Error>> {
+        if bitmap.width() == 0 || bitmap.height() == 0 {
+            Err("Image must not be empty")?;
+        }
+        self.bitmap = bitmap;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the alpha threshold of the image.
+    pub fn alpha_threshold(&self) -> u8 {
+        self.alpha_threshold
+    }
+
+    /// Sets the alpha threshold of the image.
+    ///
+    /// Pixels whose alpha is above the threshold are drawn and part of the window shape,
+    /// the others are see and click through.
+    /// With an ARGB visual, every pixel that is not fully transparent is drawn.
+    pub fn set_alpha_threshold(&mut self, threshold: u8) {
+        self.alpha_threshold = threshold;
+        self.dirty = true;
+    }
+
+    /// Returns the image scaled to its box on a drawable of the given size, with its top left corner.
+    fn scaled(&self, size: Vec2<u16>) -> (i16, i16, Bitmap) {
+        let coord = self
+            .position
+            .top_left(&self.anchor, &self.size)
+            .to_real_coord(size);
+        let size = self.size.to_real_size(size);
+
+        (coord.x as i16, coord.y as i16, self.bitmap.scale(size.x as u16, size.y as u16))
+    }
+}
+
+/// Returns the horizontal runs of pixels whose alpha is above the threshold
+///
+/// # Returns
+///
+/// The runs as `(x, y, length)`, in row major order
+pub(crate) fn opaque_runs(bitmap: &Bitmap, threshold: u8) -> Vec<(u16, u16, u16)> {
+    let mut runs = Vec::new();
+    if bitmap.width() == 0 {
+        return runs;
+    }
+
+    for (y, row) in bitmap.pixels().chunks(bitmap.width() as usize).enumerate() {
+        let mut start = None;
+        for (x, pixel) in row.iter().chain(std::iter::once(&[0; 4])).enumerate() {
+            match (start, pixel[3] > threshold && x < row.len()) {
+                (None, true) => start = Some(x),
+                (Some(first), false) => {
+                    runs.push((first as u16, y as u16, (x - first) as u16));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    runs
+}
+
+impl<C: Connection> Shape<C> for Image {
+    /// Draws the image on the specified drawable using the given graphics context.
+    ///
+    /// On the shape mask (depth 1), the pixels above the alpha threshold are filled with the forground color.
+    /// Otherwise they are uploaded at the depth of the drawable.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `gc` - The graphics context.
+    /// * `drawable` - The drawable to draw on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the drawing is successful, or a `Box` containing an error if the drawing fails.
+    fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let (x, y, bitmap) = self.scaled(drawable.size());
+        if bitmap.width() == 0 || bitmap.height() == 0 {
+            return Ok(());
+        }
+        let depth = drawable.depth();
+
+        // ARGB visuals handle the transparency, only fully transparent pixels are skipped
+        let threshold = match depth {
+            Depth::D32 => 0,
+            _ => self.alpha_threshold,
+        };
+        let runs = opaque_runs(&bitmap, threshold);
+
+        if depth == Depth::D1 {
+            let primitives: Vec<Primitive> = runs
+                .iter()
+                .map(|(run_x, run_y, length)| {
+                    Primitive::FillRectangle(XRectangle {
+                        x: x + *run_x as i16,
+                        y: y + *run_y as i16,
+                        width: *length,
+                        height: 1,
+                    })
+                })
+                .collect();
+            return Batch::draw(conn, gc, drawable, &primitives);
+        }
+
+        let values: Vec<u32> = bitmap
+            .pixels()
+            .iter()
+            .map(|[r, g, b, a]| Color::RGBA(*r, *g, *b, *a).value(&depth))
+            .collect();
+
+        let width = bitmap.width();
+        if runs.len() == bitmap.height() as usize && runs.iter().all(|(_, _, length)| *length == width) {
+            // Fully opaque, the image is uploaded at once
+            return ximage::put_image(conn, drawable.id(), gc.gcontext(), depth.value(), (x, y), width, &values);
+        }
+
+        // Transparent pixels keep the content below them
+        for (run_x, run_y, length) in runs {
+            let start = run_y as usize * width as usize + run_x as usize;
+            let run = &values[start..start + length as usize];
+            ximage::put_image(conn, drawable.id(), gc.gcontext(), depth.value(), (x + run_x as i16, y + run_y as i16), length, run)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws the pixels above the alpha threshold on the canvas.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        let (x, y, bitmap) = self.scaled(canvas.size());
+        canvas.draw_image(x as i32, y as i32, &bitmap, self.alpha_threshold);
+        Ok(())
+    }
+
+    /// Returns the mask color of the image (the pixels above the alpha threshold).
+    fn forground(&self) -> &Color {
+        &Color::WHITE
+    }
+
+    /// Returns the background color of the image (not used).
+    fn background(&self) -> &Color {
+        &Color::TRANSPARENT
+    }
+
+    /// Returns the shape size.
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Resizes the shape to the specified size.
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    fn position(&self) -> Coord {
+        self.position
+    }
+
+    fn set_position(&mut self, position: Coord) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    /// A 2x2 image: opaque red, half transparent green, transparent, opaque black
+    fn bitmap() -> Bitmap {
+        Bitmap::new(2, 2, vec![[0xFF, 0, 0, 0xFF], [0, 0xFF, 0, 0x80], [0, 0, 0, 0], [0, 0, 0, 0xFF]]).unwrap()
+    }
+
+    #[test]
+    fn test_opaque_runs() {
+        assert_eq!(opaque_runs(&bitmap(), DEFAULT_ALPHA_THRESHOLD), vec![(0, 0, 2), (1, 1, 1)]);
+        assert_eq!(opaque_runs(&bitmap(), 0x80), vec![(0, 0, 1), (1, 1, 1)]);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let image = Image::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), bitmap()).unwrap();
+        image.borrow_mut().set_alpha_threshold(0x80);
+
+        // Each image pixel covers 2x2 canvas pixels
+        let mut canvas = Canvas::new(Vec2::new(4, 4));
+        Shape::<RustConnection>::rasterize(&*image.borrow(), &mut canvas).unwrap();
+
+        assert!(canvas.is_masked(1, 1));
+        assert_eq!(canvas.pixel(1, 1), Some([0xFF, 0, 0, 0xFF]));
+        assert!(!canvas.is_masked(2, 0));
+        assert!(!canvas.is_masked(0, 2));
+
+        // Opaque black is part of the shape (unlike black shapes)
+        assert!(canvas.is_masked(3, 3));
+    }
+
+    #[test]
+    fn test_empty() {
+        let empty = Bitmap::new(0, 0, Vec::new()).unwrap();
+        assert!(Image::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), empty).is_err());
+    }
+}
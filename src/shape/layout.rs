@@ -0,0 +1,304 @@
+//! Text layout module
+//!
+//! This module breaks the texts in lines: at the newlines, and between the words when a line is wider
+//! than the maximum width of the text (words wider than the line are broken between characters).
+//! The lines are then aligned in the box of the text, which is as wide as its widest line.
+//!
+//! The layout only needs the advance of every character, so each character is measured once
+//! (with `query_text_extents` for the server fonts) and the lines are composed without another request.
+
+/// Describe how the lines of a text are aligned in its box
+///
+/// - Justify: The spaces of a line are stretched to fill the box, the last line of a paragraph is aligned left
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+/// Describe how a text is broken in lines (the widths are in pixels)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LayoutOptions<'a> {
+    /// The maximum width of the lines (None for no limit)
+    pub max_width: Option<i32>,
+    /// The maximum number of lines, the text is truncated with the ellipsis beyond (None for no limit)
+    pub max_lines: Option<usize>,
+    /// Break the lines between the words (otherwise the lines wider than the maximum width are truncated)
+    pub word_wrap: bool,
+    pub alignment: Alignment,
+    /// The text appended to the truncated lines
+    pub ellipsis: &'a str,
+}
+
+/// A line of a text
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TextLine {
+    pub text: String,
+    /// The width of the text (in pixels)
+    pub width: i32,
+    /// True for the last line of a paragraph (not justified)
+    pub last: bool,
+}
+
+/// A part of a line drawn at once
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Segment {
+    /// The offset from the left of the box (in pixels)
+    pub x: i32,
+    pub text: String,
+}
+
+/// Returns the width of a text (in pixels)
+fn width(text: &str, advance: &dyn Fn(char) -> i32) -> i32 {
+    text.chars().map(advance).sum()
+}
+
+/// Returns the byte ranges of the words of a text (separated by whitespace)
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(index),
+            (Some(first), true) => {
+                words.push((first, index));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(first) = start {
+        words.push((first, text.len()));
+    }
+    words
+}
+
+/// Breaks a paragraph between its words, so the lines are not wider than the maximum width
+///
+/// The lines are only broken where they overflow: they keep the spacing of the paragraph,
+/// except for the whitespace at the breaks.
+fn wrap(paragraph: &str, max_width: i32, advance: &dyn Fn(char) -> i32, lines: &mut Vec<TextLine>) {
+    let line = |text: &str, last| TextLine { text: text.to_string(), width: width(text, advance), last };
+    // The start of the current line, and the end of its last word
+    let (mut start, mut end) = (0, None);
+
+    for (word_start, word_end) in words(paragraph) {
+        if width(&paragraph[start..word_end], advance) <= max_width {
+            end = Some(word_end);
+            continue;
+        }
+
+        // The line overflows, it is broken before the word
+        if let Some(line_end) = end {
+            lines.push(line(&paragraph[start..line_end], false));
+            start = word_start;
+        }
+
+        // Words wider than the line are broken between characters (at least one per line)
+        let mut line_width = 0;
+        let chars: Vec<(usize, char)> = paragraph[start..word_end].char_indices().map(|(index, c)| (start + index, c)).collect();
+        for (index, c) in chars {
+            let char_width = advance(c);
+            if index > start && line_width + char_width > max_width {
+                lines.push(line(&paragraph[start..index], false));
+                (start, line_width) = (index, 0);
+            }
+            line_width += char_width;
+        }
+        end = Some(word_end);
+    }
+
+    // The last line keeps its trailing whitespace if it fits
+    let rest = &paragraph[start..];
+    match end {
+        Some(end) if width(rest, advance) > max_width => lines.push(line(&paragraph[start..end], true)),
+        _ => lines.push(line(rest, true)),
+    }
+}
+
+/// Appends the ellipsis to a text, removing its last characters so it fits the maximum width
+fn ellipsize(text: &str, max_width: Option<i32>, ellipsis: &str, advance: &dyn Fn(char) -> i32) -> TextLine {
+    let ellipsis_width = width(ellipsis, advance);
+    let mut text = text.trim_end().to_string();
+    if let Some(max_width) = max_width {
+        while !text.is_empty() && width(&text, advance) + ellipsis_width > max_width {
+            text.pop();
+        }
+        text.truncate(text.trim_end().len());
+    }
+    text.push_str(ellipsis);
+
+    TextLine { width: width(&text, advance), text, last: true }
+}
+
+/// Breaks a text in lines
+///
+/// # Arguments
+///
+/// * `text` - The text (`\n` starts a new paragraph)
+/// * `options` - How the text is broken
+/// * `advance` - Returns the advance of a character (in pixels)
+pub(crate) fn break_lines(text: &str, options: &LayoutOptions, advance: &dyn Fn(char) -> i32) -> Vec<TextLine> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+        match options.max_width {
+            Some(max_width) if options.word_wrap => wrap(paragraph, max_width, advance, &mut lines),
+            Some(max_width) if width(paragraph, advance) > max_width => {
+                lines.push(ellipsize(paragraph, Some(max_width), options.ellipsis, advance))
+            }
+            _ => lines.push(TextLine { text: paragraph.to_string(), width: width(paragraph, advance), last: true }),
+        }
+    }
+
+    // The last line kept ends with the ellipsis
+    if let Some(max_lines) = options.max_lines.map(|max_lines| max_lines.max(1)) {
+        if lines.len() > max_lines {
+            lines.truncate(max_lines);
+            if let Some(line) = lines.pop() {
+                lines.push(ellipsize(&line.text, options.max_width, options.ellipsis, advance));
+            }
+        }
+    }
+
+    lines
+}
+
+/// Aligns the lines of a text in its box
+///
+/// # Returns
+///
+/// The segments of every line, and the width of the box (the width of the widest line)
+pub(crate) fn align(lines: &[TextLine], alignment: Alignment, advance: &dyn Fn(char) -> i32) -> (Vec<Vec<Segment>>, i32) {
+    let box_width = lines.iter().map(|line| line.width).max().unwrap_or(0);
+
+    let segments = lines
+        .iter()
+        .map(|line| {
+            let words = words(&line.text);
+            if alignment == Alignment::Justify && !line.last && words.len() > 1 {
+                // The gaps keep their width, the extra pixels are spread between them (the remainder to the first gaps)
+                let gaps = words.len() as i32 - 1;
+                let spread = box_width - line.width;
+                return words
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (start, end))| {
+                        let index = index as i32;
+                        let extra = spread / gaps * index + index.min(spread % gaps);
+                        Segment { x: width(&line.text[..*start], advance) + extra, text: line.text[*start..*end].to_string() }
+                    })
+                    .collect();
+            }
+
+            let x = match alignment {
+                Alignment::Left | Alignment::Justify => 0,
+                Alignment::Center => (box_width - line.width) / 2,
+                Alignment::Right => box_width - line.width,
+            };
+            vec![Segment { x, text: line.text.clone() }]
+        })
+        .collect();
+
+    (segments, box_width)
+}
+
+/// Breaks a text in lines and aligns them (see `break_lines` and `align`)
+pub(crate) fn layout(text: &str, options: &LayoutOptions, advance: &dyn Fn(char) -> i32) -> (Vec<Vec<Segment>>, i32) {
+    align(&break_lines(text, options, advance), options.alignment, advance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is one pixel wide, but 'W' is three
+    fn advance(c: char) -> i32 {
+        if c == 'W' { 3 } else { 1 }
+    }
+
+    fn options(max_width: Option<i32>) -> LayoutOptions<'static> {
+        LayoutOptions { max_width, max_lines: None, word_wrap: true, alignment: Alignment::Left, ellipsis: "~" }
+    }
+
+    fn texts(lines: &[TextLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_newlines() {
+        let lines = break_lines("ab\r\n\ncWd", &options(None), &advance);
+        assert_eq!(texts(&lines), vec!["ab", "", "cWd"]);
+        assert_eq!(lines[2].width, 5);
+        assert!(lines.iter().all(|line| line.last));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let lines = break_lines("the quick  brown fox", &options(Some(9)), &advance);
+        assert_eq!(texts(&lines), vec!["the quick", "brown fox"]);
+        assert!(!lines[0].last && lines[1].last);
+
+        // Long words are broken between characters
+        let lines = break_lines("a abcdefgh", &options(Some(3)), &advance);
+        assert_eq!(texts(&lines), vec!["a", "abc", "def", "gh"]);
+        let lines = break_lines("WWa", &options(Some(2)), &advance);
+        assert_eq!(texts(&lines), vec!["W", "W", "a"]);
+    }
+
+    #[test]
+    fn test_spacing() {
+        // Lines that fit are not rewritten
+        let lines = break_lines("a  b", &options(Some(100)), &advance);
+        assert_eq!(texts(&lines), vec!["a  b"]);
+        let lines = break_lines("  a\tb ", &options(Some(100)), &advance);
+        assert_eq!(texts(&lines), vec!["  a\tb "]);
+
+        // Only the whitespace at the breaks is dropped
+        let lines = break_lines("  ab  cd   ef", &options(Some(8)), &advance);
+        assert_eq!(texts(&lines), vec!["  ab  cd", "ef"]);
+
+        // Justified lines keep their gaps, stretched
+        let lines = break_lines("a  b c xxxxxxx", &options(Some(7)), &advance);
+        let (segments, _) = align(&lines, Alignment::Justify, &advance);
+        let offsets: Vec<i32> = segments[0].iter().map(|segment| segment.x).collect();
+        assert_eq!(offsets, vec![0, 4, 6]);
+    }
+
+    #[test]
+    fn test_ellipsis() {
+        // Lines are truncated instead of wrapped
+        let options = LayoutOptions { word_wrap: false, ..options(Some(5)) };
+        let lines = break_lines("short\nthe quick brown fox", &options, &advance);
+        assert_eq!(texts(&lines), vec!["short", "the~"]);
+        assert_eq!(lines[1].width, 4);
+
+        // Lines beyond the maximum are dropped
+        let options = LayoutOptions { max_lines: Some(2), ..self::options(Some(9)) };
+        let lines = break_lines("the quick brown fox jumps", &options, &advance);
+        assert_eq!(texts(&lines), vec!["the quick", "brown fo~"]);
+        assert!(lines[1].last);
+    }
+
+    #[test]
+    fn test_align() {
+        let lines = break_lines("ab cd ef\nx", &options(Some(6)), &advance);
+        let line = |alignment| align(&lines, alignment, &advance).0;
+
+        assert_eq!(align(&lines, Alignment::Left, &advance).1, 5);
+        assert_eq!(line(Alignment::Left)[1], vec![Segment { x: 0, text: "ef".to_string() }]);
+        assert_eq!(line(Alignment::Center)[2], vec![Segment { x: 2, text: "x".to_string() }]);
+        assert_eq!(line(Alignment::Right)[1], vec![Segment { x: 3, text: "ef".to_string() }]);
+
+        // The last line of a paragraph is not justified
+        let lines = break_lines("a b c dddd e", &options(Some(6)), &advance);
+        let (segments, width) = align(&lines, Alignment::Justify, &advance);
+        assert_eq!(width, 6);
+        let offsets: Vec<i32> = segments[0].iter().map(|segment| segment.x).collect();
+        assert_eq!(offsets, vec![0, 3, 5]);
+        assert_eq!(segments[1], vec![Segment { x: 0, text: "dddd e".to_string() }]);
+    }
+}
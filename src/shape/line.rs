@@ -0,0 +1,385 @@
+//! Line shape module
+//!
+//! This module is used to define the line shape object used by the overlay library.
+//!
+//! A line goes through a list of points (two for a single line, more for a polyline).
+//! It is stroked with a `LineStyle` (width, dashes, caps and joins).
+
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Point as XPoint, Segment as XSegment},
+};
+
+use crate::{
+    color::Color,
+    drawable::{canvas::Canvas, Drawable},
+    font::FontSet,
+    math::{rect::Rect, vec::Vec2},
+};
+
+use super::{
+    coord::{Anchor, Coord, CoordExt, Size},
+    style::{Cap, Join, LineStyle},
+    Batch, GcontextWrapperExt, Primitive, Shape,
+};
+
+/// Represents a line or a polyline shape.
+pub struct Line {
+    anchor: Anchor, // Always the top left corner of the bounding box of the points
+    points: Vec<Coord>,
+    forground: Color,
+    style: LineStyle,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
+}
+
+impl Line {
+    /// Creates a new line between two points.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the line.
+    /// * `end` - The end of the line.
+    /// * `color` - The color of the line.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Line` object, or a `Box` containing an error if the creation fails.
+    pub fn new(start: Coord, end: Coord, color: Color) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::polyline(vec![start, end], color)
+    }
+
+    /// Creates a new polyline going through the given points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points of the polyline.
+    /// * `color` - The color of the polyline.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Line` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than two points.
+    pub fn polyline(points: Vec<Coord>, color: Color) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        if points.len() < 2 {
+            Err("A line needs at least two points")?;
+        }
+
+        Ok(Rc::new(RefCell::new(Self {
+            anchor: Anchor::NorthWest,
+            points,
+            forground: color,
+            style: LineStyle::default(),
+            visible: true,
+            interactive: false,
+            dirty: true,
+        })))
+    }
+
+    /// Returns the points of the line.
+    pub fn points(&self) -> &[Coord] {
+        &self.points
+    }
+
+    /// Sets the points of the line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than two points.
+    pub fn set_points(&mut self, points: Vec<Coord>) -> Result<(), Box<dyn Error>> {
+        if points.len() < 2 {
+            Err("A line needs at least two points")?;
+        }
+        self.points = points;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Sets the color of the line.
+    pub fn set_forground_color(&mut self, color: Color) {
+        self.forground = color;
+        self.dirty = true;
+    }
+
+    /// Returns the line style.
+    pub fn style(&self) -> &LineStyle {
+        &self.style
+    }
+
+    /// Sets the line style (width, dashes, caps and joins).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dash pattern contains a zero length.
+    pub fn set_style(&mut self, style: LineStyle) -> Result<(), Box<dyn Error>> {
+        if style.dashes.contains(&0) {
+            Err("Dash lengths must not be zero")?;
+        }
+        self.style = style;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Sets the width of the line (in pixels, 0 draws the thinnest line).
+    pub fn set_width(&mut self, width: u16) {
+        self.style.width = width;
+        self.dirty = true;
+    }
+
+    /// Sets the dash pattern of the line.
+    ///
+    /// # Arguments
+    ///
+    /// * `dashes` - Alternating drawn and skipped lengths in pixels (empty for a solid line).
+    /// * `offset` - The offset of the pattern at the start of the line (in pixels).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern contains a zero length.
+    pub fn set_dashes(&mut self, dashes: Vec<u8>, offset: u16) -> Result<(), Box<dyn Error>> {
+        if dashes.contains(&0) {
+            Err("Dash lengths must not be zero")?;
+        }
+        self.style.dashes = dashes;
+        self.style.dash_offset = offset;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Sets the style of the ends of the line.
+    pub fn set_cap(&mut self, cap: Cap) {
+        self.style.cap = cap;
+        self.dirty = true;
+    }
+
+    /// Sets the style of the corners of the polyline.
+    pub fn set_join(&mut self, join: Join) {
+        self.style.join = join;
+        self.dirty = true;
+    }
+
+    /// Returns true if the line is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the line.
+    ///
+    /// A hidden line is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the line captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the line captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// Returns the top left and bottom right corners of the bounding box of the points.
+    fn extent(&self) -> (Coord, Coord) {
+        self.points.iter().fold(
+            (Coord::new(f32::MAX, f32::MAX), Coord::new(f32::MIN, f32::MIN)),
+            |(min, max), point| {
+                (
+                    Coord::new(min.x.min(point.x), min.y.min(point.y)),
+                    Coord::new(max.x.max(point.x), max.y.max(point.y)),
+                )
+            },
+        )
+    }
+
+    /// Returns the points of the line on a drawable of the given size (in pixels).
+    fn real_points(&self, size: Vec2<u16>) -> Vec<Coord> {
+        self.points.iter().map(|point| point.to_real_coord(size)).collect()
+    }
+}
+
+impl<C: Connection> Shape<C> for Line {
+    /// Draws the line on the specified drawable using the given graphics context.
+    ///
+    /// The line style of the graphics context is set by the overlay (see `Shape::line_style`).
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `gc` - The graphics context.
+    /// * `drawable` - The drawable to draw on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the drawing is successful, or a `Box` containing an error if the drawing fails.
+    fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let primitives = <Self as Shape<C>>::primitives(self, drawable.size()).unwrap_or_default();
+        Batch::draw(conn, gc, drawable, &primitives)
+    }
+
+    /// Returns the line as a `poly_segment` element, or the polyline as a `poly_line` request.
+    fn primitives(&self, size: Vec2<u16>) -> Option<Vec<Primitive>> {
+        let points: Vec<XPoint> = self
+            .real_points(size)
+            .iter()
+            .map(|point| XPoint {
+                x: point.x as i16,
+                y: point.y as i16,
+            })
+            .collect();
+
+        Some(vec![match points.as_slice() {
+            [start, end] => Primitive::Segment(XSegment {
+                x1: start.x,
+                y1: start.y,
+                x2: end.x,
+                y2: end.y,
+            }),
+            _ => Primitive::Line(points),
+        }])
+    }
+
+    /// Strokes the line on the canvas with its line style.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        let points: Vec<Coord> = self
+            .real_points(canvas.size())
+            .iter()
+            .map(|point| Coord::new(point.x.trunc(), point.y.trunc()))
+            .collect();
+
+        canvas.stroke(&points, &self.style, &self.forground);
+        Ok(())
+    }
+
+    fn line_style(&self) -> Option<&LineStyle> {
+        Some(&self.style)
+    }
+
+    /// Returns the color of the line.
+    fn forground(&self) -> &Color {
+        &self.forground
+    }
+
+    /// Returns the color of the line (lines have no background).
+    fn background(&self) -> &Color {
+        &self.forground
+    }
+
+    /// Returns the size of the bounding box of the points.
+    fn size(&self) -> Size {
+        let (min, max) = self.extent();
+        max - min
+    }
+
+    /// Scales the points so that their bounding box has the specified size.
+    fn set_size(&mut self, size: Size) {
+        let (min, max) = self.extent();
+        let previous = max - min;
+
+        // A flat side can't be scaled
+        let scale = |size: f32, previous: f32| if previous > 0.0 { size / previous } else { 1.0 };
+        let scale = Size::new(scale(size.x, previous.x), scale(size.y, previous.y));
+
+        for point in self.points.iter_mut() {
+            *point = min + (*point - min).hammard(scale);
+        }
+        self.dirty = true;
+    }
+
+    fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    /// Returns the top left corner of the bounding box of the points.
+    fn position(&self) -> Coord {
+        self.extent().0
+    }
+
+    /// Moves the points so that their bounding box starts at the specified position.
+    fn set_position(&mut self, position: Coord) {
+        let delta = position - self.extent().0;
+        for point in self.points.iter_mut() {
+            *point = *point + delta;
+        }
+        self.dirty = true;
+    }
+
+    /// Returns the area covered by the stroke (including the caps and joins).
+    fn bounds(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        let (min, max) = self.extent();
+        let (min, max) = (min.to_real_coord(size), max.to_real_coord(size));
+
+        Ok(Rect::from_real(min, max - min).inflate(self.style.margin()))
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    #[test]
+    fn test_polyline_needs_two_points() {
+        assert!(Line::polyline(vec![Coord::new(0.0, 0.0)], Color::RED).is_err());
+    }
+
+    #[test]
+    fn test_resize() {
+        let line = Line::polyline(
+            vec![Coord::new(0.2, 0.2), Coord::new(0.6, 0.2), Coord::new(0.6, 0.4)],
+            Color::RED,
+        )
+        .unwrap();
+        let mut line = line.borrow_mut();
+
+        assert_eq!(Shape::<RustConnection>::position(&*line), Coord::new(0.2, 0.2));
+
+        Shape::<RustConnection>::set_size(&mut *line, Size::new(0.2, 0.1));
+        Shape::<RustConnection>::set_position(&mut *line, Coord::new(0.0, 0.5));
+
+        let expected = [Coord::new(0.0, 0.5), Coord::new(0.2, 0.5), Coord::new(0.2, 0.6)];
+        for (point, expected) in line.points().iter().zip(expected) {
+            assert!((*point - expected).length() < 1e-6);
+        }
+    }
+}
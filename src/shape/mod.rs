@@ -4,56 +4,103 @@
 
 #![allow(dead_code)]
 
-use std::error::Error;
-use coord::{Anchor, Coord, Size};
-use x11rb::{connection::Connection, protocol::xproto::{ChangeGCAux, ConnectionExt, CreateGCAux, Drawable as XDrawable, Fontable, Gcontext, GcontextWrapper}};
+use std::{error::Error, rc::Rc};
+use coord::{Anchor, Coord, CoordExt, Size, SizeExt};
+use x11rb::{connection::Connection, protocol::xproto::{ChangeGCAux, ClipOrdering, ConnectionExt, CreateGCAux, Drawable as XDrawable, FillStyle, Fontable, Gcontext, GcontextWrapper, LineStyle as XLineStyle, Pixmap as XPixmap, Rectangle as XRectangle}};
 
-use crate::{drawable::Drawable, Color};
+use crate::{drawable::{canvas::Canvas, Drawable}, font::{FontSet, FontSpec}, math::{rect::Rect, vec::Vec2}, Color, ResizePolicy};
 
 pub type XColor = u32;
 
 mod arc;
+mod batch;
 pub mod coord;
+mod fill;
+mod gradient;
+mod image;
+mod layout;
+mod line;
+mod path;
+mod polygon;
 mod rectangle;
+pub mod style;
 mod text;
 
-pub use arc::Arc;
-pub use rectangle::Rectangle;
+pub use arc::{Angle, Arc, CircleRadius};
+pub use batch::{Batch, Primitive};
+pub(crate) use batch::BatchState;
+pub use fill::{Fill, Hatch, Pattern};
+pub use gradient::{Gradient, GradientKind};
+pub use image::{Image, DEFAULT_ALPHA_THRESHOLD};
+pub use layout::Alignment;
+pub use line::Line;
+pub use path::{Path, PathCommand};
+pub use polygon::Polygon;
+pub use rectangle::{Radius, Rectangle};
+pub use style::{ArcMode, Cap, FillRule, Join, LineStyle};
 pub use text::Text;
 
-pub struct GcontextWrapperExt<'c, C: Connection> {
-    gc: GcontextWrapper<&'c C>,
-    font: Option<Fontable>,
+/// Describe how the graphics context fills the drawing operations
+///
+/// - Solid: With the foreground color
+/// - Tiled: With a pixmap at the depth of the drawable, repeated from the origin
+/// - Stippled: With the foreground color where the bits of a depth 1 pixmap are set (repeated from the origin),
+///   and the background color elsewhere if opaque
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GcFill {
+    #[default]
+    Solid,
+    Tiled { tile: XPixmap, origin: (i16, i16) },
+    Stippled { stipple: XPixmap, origin: (i16, i16), opaque: bool },
+}
+
+pub struct GcontextWrapperExt<C: Connection> {
+    gc: GcontextWrapper<Rc<C>>,
+    font: Option<FontSet>,
     fg: Option<XColor>,
     bg: Option<XColor>,
+    line_style: LineStyle,
+    fill_rule: FillRule,
+    arc_mode: ArcMode,
+    fill: GcFill,
 }
 
-impl<'c, C: Connection> GcontextWrapperExt<'c, C> {
+impl<C: Connection> GcontextWrapperExt<C> {
 
-    pub fn init(conn: &'c C, drawable: XDrawable , fg: Option<XColor>, bg: Option<XColor>, font: Option<Fontable>) -> Result<Self, Box<dyn Error>> {
+    pub fn init(conn: &Rc<C>, drawable: XDrawable , fg: Option<XColor>, bg: Option<XColor>, font: Option<FontSet>) -> Result<Self, Box<dyn Error>> {
         
         let value_list = CreateGCAux {
             foreground: fg,
             background: bg,
-            font,
+            font: font.as_ref().and_then(FontSet::primary),
             ..CreateGCAux::new()
         };
 
 
-        let gc = GcontextWrapper::create_gc(conn, drawable, &value_list)?;
+        let gc = GcontextWrapper::create_gc(conn.clone(), drawable, &value_list)?;
         Ok(Self {
             gc,
             font,
             fg,
             bg,
+            line_style: LineStyle::default(),
+            fill_rule: FillRule::default(),
+            arc_mode: ArcMode::default(),
+            fill: GcFill::default(),
         })
     }
 
+    /// Sets the foreground color
+    ///
+    /// `None` keeps the current foreground color: no request is sent and `foreground` still returns it
+    /// (like when the color is already set).
     pub fn set_foreground(&mut self, conn: &C, fg: Option<XColor>) -> Result<(), Box<dyn Error>> {
-        self.fg = fg;
+        let Some(fg) = update_color(&mut self.fg, fg) else {
+            return Ok(());
+        };
 
         let value_list = ChangeGCAux {
-            foreground: fg,
+            foreground: Some(fg),
             ..ChangeGCAux::new()
         };
 
@@ -62,11 +109,17 @@ impl<'c, C: Connection> GcontextWrapperExt<'c, C> {
         Ok(())
     }
 
+    /// Sets the background color
+    ///
+    /// `None` keeps the current background color: no request is sent and `background` still returns it
+    /// (like when the color is already set).
     pub fn set_background(&mut self, conn: &C, bg: Option<XColor>) -> Result<(), Box<dyn Error>> {
-        self.bg = bg;
+        let Some(bg) = update_color(&mut self.bg, bg) else {
+            return Ok(());
+        };
 
         let value_list = ChangeGCAux {
-            background: bg,
+            background: Some(bg),
             ..ChangeGCAux::new()
         };
 
@@ -75,11 +128,18 @@ impl<'c, C: Connection> GcontextWrapperExt<'c, C> {
         Ok(())
     }
 
-    pub fn set_font(&mut self, conn: &C, font: Option<Fontable>) -> Result<(), Box<dyn Error>> {
+    /// Sets the fonts used by the texts (the primary font is selected in the graphics context)
+    pub fn set_font(&mut self, conn: &C, font: Option<FontSet>) -> Result<(), Box<dyn Error>> {
+        let primary = font.as_ref().and_then(FontSet::primary);
+        let previous = self.font.as_ref().and_then(FontSet::primary);
         self.font = font;
 
+        if primary.is_none() || primary == previous {
+            return Ok(());
+        }
+
         let value_list = ChangeGCAux {
-            font,
+            font: primary,
             ..ChangeGCAux::new()
         };
 
@@ -88,13 +148,169 @@ impl<'c, C: Connection> GcontextWrapperExt<'c, C> {
         Ok(())
     }
 
+    /// Sets the line width, dash pattern, cap style and join style (skipped if already set)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dash pattern contains a zero length or the request could not be sent.
+    pub fn set_line_style(&mut self, conn: &C, style: &LineStyle) -> Result<(), Box<dyn Error>> {
+        if style == &self.line_style {
+            return Ok(());
+        }
+        if style.dashes.contains(&0) {
+            Err("Dash lengths must not be zero")?;
+        }
+
+        let line_style = match style.is_dashed() {
+            true => XLineStyle::ON_OFF_DASH,
+            false => XLineStyle::SOLID,
+        };
+
+        let value_list = ChangeGCAux {
+            line_width: Some(style.width as u32),
+            line_style: Some(line_style),
+            cap_style: Some(style.cap.into()),
+            join_style: Some(style.join.into()),
+            ..ChangeGCAux::new()
+        };
+
+        conn.change_gc(self.gc.gcontext(), &value_list)?;
+        if style.is_dashed() {
+            conn.set_dashes(self.gc.gcontext(), style.dash_offset, &style.dashes)?;
+        }
+        self.line_style = style.clone();
+
+        Ok(())
+    }
+
+    /// Sets the rule deciding which parts of self-intersecting polygons are filled (skipped if already set)
+    pub fn set_fill_rule(&mut self, conn: &C, rule: FillRule) -> Result<(), Box<dyn Error>> {
+        if rule == self.fill_rule {
+            return Ok(());
+        }
+        self.fill_rule = rule;
+
+        let value_list = ChangeGCAux {
+            fill_rule: Some(rule.into()),
+            ..ChangeGCAux::new()
+        };
+
+        conn.change_gc(self.gc.gcontext(), &value_list)?;
+
+        Ok(())
+    }
+
+    /// Sets how filled arcs are closed (skipped if already set)
+    pub fn set_arc_mode(&mut self, conn: &C, mode: ArcMode) -> Result<(), Box<dyn Error>> {
+        if mode == self.arc_mode {
+            return Ok(());
+        }
+        self.arc_mode = mode;
+
+        let value_list = ChangeGCAux {
+            arc_mode: Some(mode.into()),
+            ..ChangeGCAux::new()
+        };
+
+        conn.change_gc(self.gc.gcontext(), &value_list)?;
+
+        Ok(())
+    }
+
+    /// Sets how the drawing operations are filled (skipped if already set)
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection
+    /// * `fill` - The fill style, with its tile or stipple pixmap
+    pub fn set_fill(&mut self, conn: &C, fill: GcFill) -> Result<(), Box<dyn Error>> {
+        if fill == self.fill {
+            return Ok(());
+        }
+        self.fill = fill;
+
+        let value_list = match fill {
+            GcFill::Solid => ChangeGCAux {
+                fill_style: Some(FillStyle::SOLID),
+                ..ChangeGCAux::new()
+            },
+            GcFill::Tiled { tile, origin } => ChangeGCAux {
+                fill_style: Some(FillStyle::TILED),
+                tile: Some(tile),
+                tile_stipple_x_origin: Some(origin.0 as i32),
+                tile_stipple_y_origin: Some(origin.1 as i32),
+                ..ChangeGCAux::new()
+            },
+            GcFill::Stippled { stipple, origin, opaque } => ChangeGCAux {
+                fill_style: Some(match opaque {
+                    true => FillStyle::OPAQUE_STIPPLED,
+                    false => FillStyle::STIPPLED,
+                }),
+                stipple: Some(stipple),
+                tile_stipple_x_origin: Some(origin.0 as i32),
+                tile_stipple_y_origin: Some(origin.1 as i32),
+                ..ChangeGCAux::new()
+            },
+        };
+
+        conn.change_gc(self.gc.gcontext(), &value_list)?;
+
+        Ok(())
+    }
+
+    /// Restricts the drawing operations to the given regions
+    ///
+    /// An empty slice removes the restriction.
+    pub fn set_clip_rectangles(&mut self, conn: &C, rects: &[Rect]) -> Result<(), Box<dyn Error>> {
+        if rects.is_empty() {
+            let value_list = ChangeGCAux {
+                clip_mask: Some(x11rb::NONE),
+                ..ChangeGCAux::new()
+            };
+            conn.change_gc(self.gc.gcontext(), &value_list)?;
+        } else {
+            let rects: Vec<XRectangle> = rects.iter().map(|rect| (*rect).into()).collect();
+            conn.set_clip_rectangles(ClipOrdering::UNSORTED, self.gc.gcontext(), 0, 0, &rects)?;
+        }
+
+        Ok(())
+    }
 
     pub fn gcontext(&self) -> Gcontext {
         self.gc.gcontext()
     }
 
     pub fn font(&self) -> Option<Fontable> {
-        self.font
+        self.font.as_ref().and_then(FontSet::primary)
+    }
+
+    /// Returns the fonts used by the texts (primary font first)
+    pub fn fonts(&self) -> Option<&FontSet> {
+        self.font.as_ref()
+    }
+
+    pub fn foreground(&self) -> Option<XColor> {
+        self.fg
+    }
+
+    pub fn background(&self) -> Option<XColor> {
+        self.bg
+    }
+
+    pub fn line_style(&self) -> &LineStyle {
+        &self.line_style
+    }
+
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    pub fn arc_mode(&self) -> ArcMode {
+        self.arc_mode
+    }
+
+    pub fn fill(&self) -> GcFill {
+        self.fill
     }
 }
 
@@ -119,6 +335,88 @@ where
     /// Returns an error if there was a problem drawing the shape.
     fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>>;
 
+    /// Returns the primitives drawn by the shape on a drawable of the given size.
+    ///
+    /// Shapes returning primitives are batched with their neighbours sharing the same
+    /// graphics context state (one request for many shapes).
+    /// Shapes returning `None` are drawn individually with `draw`.
+    fn primitives(&self, _size: Vec2<u16>) -> Option<Vec<Primitive>> {
+        None
+    }
+
+    /// Draws the shape on a software canvas.
+    ///
+    /// The default implementation draws the primitives of the shape with its forground color (or its fill style).
+    /// Shapes without primitives must override it to appear on the canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - The canvas to draw on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape could not be rasterized.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        let primitives = self.primitives(canvas.size()).unwrap_or_default();
+        match self.fill_style().filter(|fill| fill != &&Fill::Solid) {
+            Some(fill) => {
+                // Draw the coverage of the shape, then paint it with the fill
+                let mut coverage = Canvas::new(canvas.size());
+                for primitive in primitives {
+                    coverage.draw_primitive(&primitive, &Color::WHITE);
+                }
+                canvas.paint(&coverage, real_box(self, canvas.size()), fill, self.forground(), self.background());
+            }
+            None => {
+                for primitive in primitives {
+                    canvas.draw_primitive(&primitive, self.forground());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the line style used to draw the shape.
+    ///
+    /// The overlay sets it on the graphics context before drawing the shape,
+    /// shapes returning `None` are drawn with the default line style.
+    fn line_style(&self) -> Option<&LineStyle> {
+        None
+    }
+
+    /// Returns the fill rule used to draw the shape.
+    ///
+    /// Like the line style, the overlay sets it on the graphics context before drawing the shape,
+    /// shapes returning `None` are drawn with the default fill rule (even-odd).
+    fn fill_rule(&self) -> Option<FillRule> {
+        None
+    }
+
+    /// Returns how the filled arcs of the shape are closed.
+    ///
+    /// Like the line style, the overlay sets it on the graphics context before drawing the shape,
+    /// shapes returning `None` are drawn with the default arc mode (pie slice).
+    fn arc_mode(&self) -> Option<ArcMode> {
+        None
+    }
+
+    /// Returns how the inside of the shape is painted.
+    ///
+    /// The overlay draws the primitives of the shape with the fill (gradient, stipple or tile) instead of the forground color.
+    /// Shapes returning `None` or `Fill::Solid` are drawn with their forground color.
+    fn fill_style(&self) -> Option<&Fill> {
+        None
+    }
+
+    /// Returns the font used to draw the shape on a drawable of the given size.
+    ///
+    /// The overlay selects the matching font (followed by its default fonts) in the graphics context before drawing the shape,
+    /// the closest available pixel size is used.
+    /// Shapes returning `None` are drawn with the default fonts.
+    fn font(&self, _size: Vec2<u16>) -> Option<FontSpec> {
+        None
+    }
+
     /// Returns the forground color of the shape.
     fn forground(&self) -> &Color;
 
@@ -139,4 +437,128 @@ where
 
     /// Sets the shape's position.
     fn set_position(&mut self, position: Coord);
+
+    /// Adapts the shape to a new window size, following the resize policy of the overlay.
+    ///
+    /// The default implementation scales the position and the size of the shape by the factors of the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The resize policy of the overlay.
+    /// * `previous` - The previous size of the window.
+    /// * `size` - The new size of the window.
+    fn resize(&mut self, policy: ResizePolicy, previous: Vec2<u16>, size: Vec2<u16>) {
+        let scale = policy.scale(previous, size);
+        if scale == Vec2::new(1.0, 1.0) {
+            // Nothing to do
+            return;
+        }
+
+        let (position, shape_size) = (self.position(), self.size());
+        self.set_size(shape_size.hammard(scale));
+        self.set_position(position.hammard(scale));
+    }
+
+    /// Measures the shape on a drawable of the given size, for the shapes whose size depends on the server (like texts).
+    ///
+    /// The overlay measures the shapes when they are added and when they change, so `size` is up to date
+    /// before the next frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `font` - The fonts used to draw the shape (if any).
+    /// * `size` - The size of the drawable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape could not be measured.
+    fn measure(&self, _conn: &C, _font: Option<&FontSet>, _size: Vec2<u16>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Returns the area covered by the shape on a drawable of the given size (in pixels).
+    ///
+    /// The overlay uses it to know which regions must be repainted when the shape changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `font` - The fonts used to draw the shape (if any).
+    /// * `size` - The size of the drawable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bounds could not be computed.
+    fn bounds(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        Ok(real_box(self, size))
+    }
+
+    /// Returns true if the shape is drawn.
+    fn is_visible(&self) -> bool;
+
+    /// Shows or hides the shape (hidden shapes are neither drawn nor part of the shape mask).
+    fn set_visible(&mut self, visible: bool);
+
+    /// Returns true if the shape captures the mouse (its area is part of the input region).
+    fn is_interactive(&self) -> bool;
+
+    /// Sets whether the shape captures the mouse (the overlay is click-through elsewhere).
+    fn set_interactive(&mut self, interactive: bool);
+
+    /// Returns true if the shape changed since it was last drawn.
+    fn is_dirty(&self) -> bool;
+
+    /// Marks the shape as changed (or drawn when `dirty` is false).
+    fn set_dirty(&mut self, dirty: bool);
+}
+
+/// Records a new color of the graphics context
+///
+/// # Returns
+///
+/// The color to send to the server (None if the color is `None`, which keeps the current one, or is already set)
+fn update_color(current: &mut Option<XColor>, color: Option<XColor>) -> Option<XColor> {
+    if color.is_none() || color == *current {
+        return None;
+    }
+    *current = color;
+    color
+}
+
+/// Returns the box of a shape on a drawable of the given size (in pixels)
+pub(crate) fn real_box<C, S>(shape: &S, size: Vec2<u16>) -> Rect
+where
+    C: Connection,
+    S: Shape<C> + ?Sized,
+{
+    let coord = shape
+        .position()
+        .top_left(shape.anchor(), &shape.size())
+        .to_real_coord(size);
+    let real_size = shape.size().to_real_size(size);
+
+    Rect::from_real(coord, real_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_color() {
+        let mut current = None;
+        assert_eq!(update_color(&mut current, None), None);
+        assert_eq!(update_color(&mut current, Some(0xFF0000)), Some(0xFF0000));
+
+        // Already set
+        assert_eq!(update_color(&mut current, Some(0xFF0000)), None);
+
+        // None keeps the current color
+        assert_eq!(update_color(&mut current, None), None);
+        assert_eq!(current, Some(0xFF0000));
+
+        assert_eq!(update_color(&mut current, Some(0x00FF00)), Some(0x00FF00));
+        assert_eq!(current, Some(0x00FF00));
+    }
 }
@@ -0,0 +1,199 @@
+//! Curve flattening
+//!
+//! The curves of a path are approximated by polylines before drawing:
+//! - quadratic curves are converted to cubic curves,
+//! - elliptic arcs are converted to cubic curves (one per quarter turn at most),
+//! - cubic curves are split into segments short enough to stay within `TOLERANCE` of the curve.
+
+use std::f32::consts::PI;
+
+use crate::math::vec::Vec2f;
+
+/// Maximum distance between a curve and its polyline (in pixels)
+pub const TOLERANCE: f32 = 0.25;
+
+/// Maximum number of segments approximating a curve
+const MAX_SEGMENTS: usize = 1024;
+
+/// Returns the control points of the cubic curve drawing a quadratic curve
+///
+/// # Arguments
+///
+/// * `start` - The start of the curve
+/// * `control` - The control point of the quadratic curve
+/// * `end` - The end of the curve
+pub fn quad_to_cubic(start: Vec2f, control: Vec2f, end: Vec2f) -> (Vec2f, Vec2f) {
+    (
+        start + (control - start) * (2.0 / 3.0),
+        end + (control - end) * (2.0 / 3.0),
+    )
+}
+
+/// Appends the points of a flattened cubic curve (the start point excluded)
+///
+/// # Arguments
+///
+/// * `start` - The start of the curve
+/// * `first` - The first control point
+/// * `second` - The second control point
+/// * `end` - The end of the curve
+/// * `points` - The polyline to extend
+pub fn cubic(start: Vec2f, first: Vec2f, second: Vec2f, end: Vec2f, points: &mut Vec<Vec2f>) {
+    // Wang's formula: number of segments keeping the error below the tolerance
+    let deviation = (start - first * 2.0 + second)
+        .length()
+        .max((first - second * 2.0 + end).length());
+    let segments = ((0.75 * deviation / TOLERANCE).sqrt().ceil() as usize).clamp(1, MAX_SEGMENTS);
+
+    for step in 1..=segments {
+        let t = step as f32 / segments as f32;
+        let u = 1.0 - t;
+        points.push(start * (u * u * u) + first * (3.0 * u * u * t) + second * (3.0 * u * t * t) + end * (t * t * t));
+    }
+}
+
+/// Converts an elliptic arc (SVG endpoint parameterization) to cubic curves
+///
+/// # Arguments
+///
+/// * `start` - The start of the arc
+/// * `radius` - The radii of the ellipse (enlarged if too small to reach the end)
+/// * `rotation` - The rotation of the ellipse (degrees)
+/// * `large_arc` - True to draw the arc larger than 180 degrees
+/// * `sweep` - True to draw the arc in the positive angle direction
+/// * `end` - The end of the arc
+///
+/// # Returns
+///
+/// The control points and end of each cubic curve, None if the arc is a straight line (a radius is zero)
+pub fn arc_to_cubics(
+    start: Vec2f,
+    radius: Vec2f,
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    end: Vec2f,
+) -> Option<Vec<[Vec2f; 3]>> {
+    if start == end {
+        return Some(Vec::new());
+    }
+    let (mut rx, mut ry) = (radius.x.abs(), radius.y.abs());
+    if rx == 0.0 || ry == 0.0 {
+        return None;
+    }
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+
+    // Start point in the coordinate system of the ellipse, relative to the middle of the chord
+    let half = (start - end) * 0.5;
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+
+    // Enlarge the radii if the end can't be reached
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let (cx1, cy1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+
+    let middle = (start + end) * 0.5;
+    let center = Vec2f::new(cos * cx1 - sin * cy1 + middle.x, sin * cx1 + cos * cy1 + middle.y);
+
+    let angle = |x: f32, y: f32| y.atan2(x);
+    let first = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut extent = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry) - first;
+    if sweep && extent < 0.0 {
+        extent += 2.0 * PI;
+    } else if !sweep && extent > 0.0 {
+        extent -= 2.0 * PI;
+    }
+
+    // Maps a point of the unit circle onto the ellipse
+    let map = |x: f32, y: f32| Vec2f::new(center.x + rx * x * cos - ry * y * sin, center.y + rx * x * sin + ry * y * cos);
+
+    let segments = (extent.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+    let step = extent / segments as f32;
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+
+    let curves = (0..segments)
+        .map(|index| {
+            let (a, b) = (first + step * index as f32, first + step * (index + 1) as f32);
+            let (sin_a, cos_a) = a.sin_cos();
+            let (sin_b, cos_b) = b.sin_cos();
+            [
+                map(cos_a - handle * sin_a, sin_a + handle * cos_a),
+                map(cos_b + handle * sin_b, sin_b - handle * cos_b),
+                // The last curve ends exactly on the end point
+                if index == segments - 1 { end } else { map(cos_b, sin_b) },
+            ]
+        })
+        .collect();
+
+    Some(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cubic_tolerance() {
+        let (start, first, second, end) = (
+            Vec2f::new(0.0, 0.0),
+            Vec2f::new(0.0, 100.0),
+            Vec2f::new(100.0, 100.0),
+            Vec2f::new(100.0, 0.0),
+        );
+        let mut points = vec![start];
+        cubic(start, first, second, end, &mut points);
+        assert_eq!(points.last(), Some(&end));
+
+        // The curve between two points stays close to the segment joining them
+        let segments = points.len() - 1;
+        for (index, segment) in points.windows(2).enumerate() {
+            let t = (index as f32 + 0.5) / segments as f32;
+            let u = 1.0 - t;
+            let curve = start * (u * u * u) + first * (3.0 * u * u * t) + second * (3.0 * u * t * t) + end * (t * t * t);
+
+            let direction = (segment[1] - segment[0]).normalize();
+            let offset = curve - segment[0];
+            let distance = (offset.x * direction.y - offset.y * direction.x).abs();
+            assert!(distance <= TOLERANCE + 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_arc_half_circle() {
+        let (start, end) = (Vec2f::new(0.0, 0.0), Vec2f::new(20.0, 0.0));
+        let curves = arc_to_cubics(start, Vec2f::new(10.0, 10.0), 0.0, false, true, end).unwrap();
+        assert_eq!(curves.len(), 2);
+
+        let mut points = vec![start];
+        let mut previous = start;
+        for [first, second, end] in curves {
+            cubic(previous, first, second, end, &mut points);
+            previous = end;
+        }
+
+        // Every point is on the circle, the positive sweep goes through the top (y down)
+        let center = Vec2f::new(10.0, 0.0);
+        assert!(points.iter().all(|point| ((*point - center).length() - 10.0).abs() < 0.1));
+        assert!(points.iter().any(|point| (point.y + 10.0).abs() < 0.1));
+    }
+
+    #[test]
+    fn test_arc_degenerate() {
+        let (start, end) = (Vec2f::new(0.0, 0.0), Vec2f::new(20.0, 0.0));
+        assert!(arc_to_cubics(start, Vec2f::new(0.0, 10.0), 0.0, false, true, end).is_none());
+
+        // Radii too small are enlarged to a half circle
+        let curves = arc_to_cubics(start, Vec2f::new(1.0, 1.0), 0.0, false, true, end).unwrap();
+        assert_eq!(curves.last().unwrap()[2], end);
+    }
+}
@@ -0,0 +1,710 @@
+//! Path shape module
+//!
+//! This module is used to define the path shape object used by the overlay library.
+//!
+//! A path is a list of commands (move, line, quadratic and cubic curves, elliptic arcs, close),
+//! which can be parsed from SVG path data. The commands use their own units: the view box
+//! (the bounding box of the points by default) is mapped onto the box of the shape,
+//! which is anchored and sized like a rectangle.
+//!
+//! The curves are flattened to polylines at draw time, at the resolution of the window,
+//! so they stay smooth when the overlay is resized.
+
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Point as XPoint},
+};
+
+use crate::{
+    color::Color,
+    drawable::{canvas::Canvas, Drawable},
+    font::FontSet,
+    math::{rect::Rect, vec::{Vec2, Vec2f}},
+};
+
+use super::{
+    coord::{Anchor, Coord, CoordExt, Size, SizeExt},
+    polygon::poly_shape,
+    style::{FillRule, LineStyle},
+    Batch, GcontextWrapperExt, Primitive, Shape,
+};
+
+mod flatten;
+mod svg;
+
+/// Represents a command of a path (coordinates in the units of the path)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand {
+    /// Starts a new subpath at the point
+    MoveTo(Coord),
+    /// Straight line to the point
+    LineTo(Coord),
+    /// Quadratic curve (control point, end)
+    QuadTo(Coord, Coord),
+    /// Cubic curve (first control point, second control point, end)
+    CubicTo(Coord, Coord, Coord),
+    /// Elliptic arc (SVG endpoint parameterization)
+    ArcTo {
+        /// The radii of the ellipse
+        radius: Vec2f,
+        /// The rotation of the ellipse (degrees)
+        rotation: f32,
+        /// True to draw the arc larger than 180 degrees
+        large_arc: bool,
+        /// True to draw the arc in the positive angle direction (clockwise on screen)
+        sweep: bool,
+        /// The end of the arc
+        end: Coord,
+    },
+    /// Closes the subpath with a line to its start
+    Close,
+}
+
+impl PathCommand {
+    /// Parses SVG path data (the `d` attribute of an SVG path)
+    ///
+    /// # Returns
+    ///
+    /// The path commands, with absolute coordinates
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path data is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::shape::PathCommand;
+    ///
+    /// let commands = PathCommand::parse("M 0 0 L 10 0 L 10 10 Z").unwrap();
+    /// assert_eq!(commands.len(), 4);
+    /// ```
+    pub fn parse(data: &str) -> Result<Vec<PathCommand>, Box<dyn Error>> {
+        svg::parse(data)
+    }
+}
+
+/// Represents a path shape object used by the overlay library.
+pub struct Path {
+    anchor: Anchor, // Describes where the coordinate is relative to the shape
+    position: Coord,
+    size: Size,
+    commands: Vec<PathCommand>,
+    view_box: (Vec2f, Vec2f), // Origin and size of the area of the path mapped onto the shape
+    forground: Color,
+    background: Color,
+    filled: bool,
+    fill_rule: FillRule,
+    style: LineStyle,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
+}
+
+impl Path {
+    /// Creates a new filled path shape object.
+    ///
+    /// Subpaths are closed automatically, overlapping parts follow the fill rule (winding by default, like SVG).
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the path box.
+    /// * `position` - The position of the path box.
+    /// * `size` - The size of the path box.
+    /// * `commands` - The commands of the path.
+    /// * `color` - The color of the path.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Path` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path has no command.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Path, PathCommand}, Color};
+    ///
+    /// let commands = PathCommand::parse("M12 2 L22 22 H2 Z").unwrap();
+    /// let path = Path::fill(Anchor::Center, Coord::new(0.5, 0.5), Size::new(0.1, 0.1), commands, Color::RED).unwrap();
+    /// ```
+    pub fn fill(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        commands: Vec<PathCommand>,
+        color: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::create(anchor, position, size, commands, color, color, true)
+    }
+
+    /// Creates a new stroked path shape object.
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the path box.
+    /// * `position` - The position of the path box.
+    /// * `size` - The size of the path box.
+    /// * `commands` - The commands of the path.
+    /// * `forground` - The color of the path.
+    /// * `background` - The background color of the path.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Path` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path has no command.
+    pub fn new(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        commands: Vec<PathCommand>,
+        forground: Color,
+        background: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::create(anchor, position, size, commands, forground, background, false)
+    }
+
+    /// Creates the path shape object.
+    fn create(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        commands: Vec<PathCommand>,
+        forground: Color,
+        background: Color,
+        filled: bool,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        if commands.is_empty() {
+            Err("A path needs at least one command")?;
+        }
+
+        Ok(Rc::new(RefCell::new(Self {
+            anchor,
+            position,
+            size,
+            view_box: bounding_box(&commands),
+            commands,
+            forground,
+            background,
+            filled,
+            fill_rule: FillRule::Winding,
+            style: LineStyle::default(),
+            visible: true,
+            interactive: false,
+            dirty: true,
+        })))
+    }
+
+    /// Returns the anchor point of the path.
+    pub fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    /// Sets the anchor point of the path.
+    pub fn set_anchor(&mut self, anchor: Anchor) {
+        self.anchor = anchor;
+        self.dirty = true;
+    }
+
+    /// Returns the position of the path.
+    pub fn position(&self) -> &Coord {
+        &self.position
+    }
+
+    /// Sets the position of the path.
+    pub fn set_position(&mut self, position: Coord) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    /// Returns the size of the path box.
+    pub fn size(&self) -> &Size {
+        &self.size
+    }
+
+    /// Sets the size of the path box.
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    /// Returns the commands of the path.
+    pub fn commands(&self) -> &[PathCommand] {
+        &self.commands
+    }
+
+    /// Sets the commands of the path (the view box is not changed).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path has no command.
+    pub fn set_commands(&mut self, commands: Vec<PathCommand>) -> Result<(), Box<dyn Error>> {
+        if commands.is_empty() {
+            Err("A path needs at least one command")?;
+        }
+        self.commands = commands;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the origin and size of the area of the path mapped onto the shape box.
+    pub fn view_box(&self) -> (Vec2f, Vec2f) {
+        self.view_box
+    }
+
+    /// Sets the area of the path mapped onto the shape box (like the `viewBox` of an SVG image).
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The top left corner of the area (in path units).
+    /// * `size` - The size of the area (in path units).
+    pub fn set_view_box(&mut self, origin: Vec2f, size: Vec2f) {
+        self.view_box = (origin, size);
+        self.dirty = true;
+    }
+
+    /// Returns the fill rule of the path.
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// Sets the rule deciding which overlapping parts of the path are filled.
+    pub fn set_fill_rule(&mut self, rule: FillRule) {
+        self.fill_rule = rule;
+        self.dirty = true;
+    }
+
+    /// Returns the line style of the path.
+    pub fn style(&self) -> &LineStyle {
+        &self.style
+    }
+
+    /// Sets the line style of the path (unused by filled paths).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dash pattern contains a zero length.
+    pub fn set_style(&mut self, style: LineStyle) -> Result<(), Box<dyn Error>> {
+        if style.dashes.contains(&0) {
+            Err("Dash lengths must not be zero")?;
+        }
+        self.style = style;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Sets the color of the path.
+    pub fn set_forground_color(&mut self, color: Color) {
+        self.forground = color;
+        self.dirty = true;
+    }
+
+    /// Sets the background color of the path.
+    pub fn set_background_color(&mut self, color: Color) {
+        self.background = color;
+        self.dirty = true;
+    }
+
+    /// Returns true if the path is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the path.
+    ///
+    /// A hidden path is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the path captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the path captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// Flattens the path on a drawable of the given size (points in pixels).
+    ///
+    /// Closed subpaths end on their start point.
+    fn flatten(&self, size: Vec2<u16>) -> Vec<Vec<Vec2f>> {
+        let origin = self
+            .position
+            .top_left(&self.anchor, &self.size)
+            .to_real_coord(size);
+        let real_size = self.size.to_real_size(size);
+
+        // Maps the path units to pixels (a flat view box keeps its side at the origin)
+        let (view_origin, view_size) = self.view_box;
+        let scale = |size: f32, view: f32| if view > 0.0 { size / view } else { 0.0 };
+        let scale = Vec2f::new(scale(real_size.x, view_size.x), scale(real_size.y, view_size.y));
+        let map = |point: Coord| origin + (point - view_origin).hammard(scale);
+
+        let mut subpaths = Vec::new();
+        let mut points: Vec<Vec2f> = Vec::new();
+        let (mut current, mut start) = (Coord::new(0.0, 0.0), Coord::new(0.0, 0.0));
+
+        for command in &self.commands {
+            // Drawing commands continue the current subpath, or start one at the current point
+            if points.is_empty() && !matches!(command, PathCommand::MoveTo(_) | PathCommand::Close) {
+                points.push(map(current));
+            }
+
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    finish(&mut subpaths, &mut points);
+                    points.push(map(point));
+                    (current, start) = (point, point);
+                }
+                PathCommand::LineTo(point) => {
+                    points.push(map(point));
+                    current = point;
+                }
+                PathCommand::QuadTo(control, end) => {
+                    let (first, second) = flatten::quad_to_cubic(current, control, end);
+                    flatten::cubic(map(current), map(first), map(second), map(end), &mut points);
+                    current = end;
+                }
+                PathCommand::CubicTo(first, second, end) => {
+                    flatten::cubic(map(current), map(first), map(second), map(end), &mut points);
+                    current = end;
+                }
+                PathCommand::ArcTo {
+                    radius,
+                    rotation,
+                    large_arc,
+                    sweep,
+                    end,
+                } => {
+                    // Arcs are converted in path units, as the mapping may stretch them
+                    match flatten::arc_to_cubics(current, radius, rotation, large_arc, sweep, end) {
+                        Some(curves) => {
+                            let mut previous = current;
+                            for [first, second, end] in curves {
+                                flatten::cubic(map(previous), map(first), map(second), map(end), &mut points);
+                                previous = end;
+                            }
+                        }
+                        None => points.push(map(end)),
+                    }
+                    current = end;
+                }
+                PathCommand::Close => {
+                    if !points.is_empty() {
+                        points.push(map(start));
+                    }
+                    finish(&mut subpaths, &mut points);
+                    current = start;
+                }
+            }
+        }
+        finish(&mut subpaths, &mut points);
+
+        subpaths
+    }
+
+    /// Returns the outline of the filled path as a single polygon (points in pixels).
+    ///
+    /// Every subpath is closed and followed by a line back to the start of the first one:
+    /// the connecting lines are drawn once in each direction, so they don't change the filled area.
+    fn fill_points(subpaths: &[Vec<Vec2f>]) -> Vec<Vec2f> {
+        let Some(anchor) = subpaths.first().map(|subpath| subpath[0]) else {
+            return Vec::new();
+        };
+
+        let mut points = Vec::new();
+        for subpath in subpaths {
+            points.extend_from_slice(subpath);
+            points.push(subpath[0]);
+            points.push(anchor);
+        }
+        points
+    }
+}
+
+impl<C: Connection> Shape<C> for Path {
+    /// Draws the path on the specified drawable using the given graphics context.
+    ///
+    /// The fill rule and line style of the graphics context are set by the overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `gc` - The graphics context.
+    /// * `drawable` - The drawable to draw on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the drawing is successful, or a `Box` containing an error if the drawing fails.
+    fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let primitives = <Self as Shape<C>>::primitives(self, drawable.size()).unwrap_or_default();
+        Batch::draw(conn, gc, drawable, &primitives)
+    }
+
+    /// Returns the path as a `fill_poly` request, or one `poly_line` request per subpath.
+    fn primitives(&self, size: Vec2<u16>) -> Option<Vec<Primitive>> {
+        let subpaths = self.flatten(size);
+        let to_points = |points: &[Vec2f]| -> Vec<XPoint> {
+            points
+                .iter()
+                .map(|point| XPoint {
+                    x: point.x as i16,
+                    y: point.y as i16,
+                })
+                .collect()
+        };
+
+        let primitives = match self.filled {
+            true => {
+                let points = to_points(&Self::fill_points(&subpaths));
+                if points.len() < 3 {
+                    return Some(Vec::new());
+                }
+                let shape = poly_shape(&points);
+                vec![Primitive::FillPolygon(points, shape)]
+            }
+            false => subpaths
+                .iter()
+                .map(|subpath| Primitive::Line(to_points(subpath)))
+                .collect(),
+        };
+
+        Some(primitives)
+    }
+
+    /// Draws the path on the canvas with its fill rule and line style.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        let subpaths = self.flatten(canvas.size());
+        let truncate = |points: &[Vec2f]| -> Vec<Vec2f> {
+            points
+                .iter()
+                .map(|point| Vec2f::new(point.x.trunc(), point.y.trunc()))
+                .collect()
+        };
+
+        match self.filled {
+            true => canvas.fill_polygon(&truncate(&Self::fill_points(&subpaths)), self.fill_rule, &self.forground),
+            false => {
+                for subpath in &subpaths {
+                    canvas.stroke(&truncate(subpath), &self.style, &self.forground);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn line_style(&self) -> Option<&LineStyle> {
+        (!self.filled).then_some(&self.style)
+    }
+
+    fn fill_rule(&self) -> Option<FillRule> {
+        self.filled.then_some(self.fill_rule)
+    }
+
+    /// Returns the color of the path.
+    fn forground(&self) -> &Color {
+        &self.forground
+    }
+
+    /// Returns the background color of the path.
+    fn background(&self) -> &Color {
+        &self.background
+    }
+
+    /// Returns the size of the path box.
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Resizes the path box to the specified size.
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    fn position(&self) -> Coord {
+        self.position
+    }
+
+    fn set_position(&mut self, position: Coord) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    /// Returns the area covered by the flattened path (it may extend outside of its box).
+    fn bounds(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        let subpaths = self.flatten(size);
+        let (min, max) = subpaths.iter().flatten().fold(
+            (Vec2f::new(f32::MAX, f32::MAX), Vec2f::new(f32::MIN, f32::MIN)),
+            |(min, max), point| {
+                (
+                    Vec2f::new(min.x.min(point.x), min.y.min(point.y)),
+                    Vec2f::new(max.x.max(point.x), max.y.max(point.y)),
+                )
+            },
+        );
+        if subpaths.is_empty() {
+            return Ok(Rect::new(0, 0, 0, 0));
+        }
+
+        let rect = Rect::from_real(min, max - min);
+        Ok(match self.filled {
+            true => rect,
+            false => rect.inflate(self.style.margin()),
+        })
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+/// Stores the current subpath if it has at least one segment
+fn finish(subpaths: &mut Vec<Vec<Vec2f>>, points: &mut Vec<Vec2f>) {
+    let points = std::mem::take(points);
+    if points.len() >= 2 {
+        subpaths.push(points);
+    }
+}
+
+/// Returns the origin and size of the bounding box of the points of the commands (control points included)
+fn bounding_box(commands: &[PathCommand]) -> (Vec2f, Vec2f) {
+    let points = commands.iter().flat_map(|command| match *command {
+        PathCommand::MoveTo(point) | PathCommand::LineTo(point) => vec![point],
+        PathCommand::QuadTo(control, end) => vec![control, end],
+        PathCommand::CubicTo(first, second, end) => vec![first, second, end],
+        PathCommand::ArcTo { end, .. } => vec![end],
+        PathCommand::Close => vec![],
+    });
+
+    let (min, max) = points.fold(
+        (Vec2f::new(f32::MAX, f32::MAX), Vec2f::new(f32::MIN, f32::MIN)),
+        |(min, max), point| {
+            (
+                Vec2f::new(min.x.min(point.x), min.y.min(point.y)),
+                Vec2f::new(max.x.max(point.x), max.y.max(point.y)),
+            )
+        },
+    );
+
+    match min.x <= max.x {
+        true => (min, max - min),
+        false => (Vec2f::new(0.0, 0.0), Vec2f::new(0.0, 0.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    fn filled(data: &str) -> Rc<RefCell<Path>> {
+        let commands = PathCommand::parse(data).unwrap();
+        Path::fill(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), commands, Color::WHITE).unwrap()
+    }
+
+    fn masked(canvas: &Canvas) -> usize {
+        canvas.mask().iter().filter(|bit| **bit).count()
+    }
+
+    #[test]
+    fn test_view_box() {
+        let path = filled("M10 10 H30 V20 Z");
+        assert_eq!(path.borrow().view_box(), (Vec2f::new(10.0, 10.0), Vec2f::new(20.0, 10.0)));
+
+        // The view box fills the shape box
+        let mut canvas = Canvas::new(Vec2::new(20, 10));
+        Shape::<RustConnection>::rasterize(&*path.borrow(), &mut canvas).unwrap();
+        assert!(canvas.is_masked(18, 8));
+        assert!(!canvas.is_masked(1, 8));
+    }
+
+    #[test]
+    fn test_circle() {
+        // Two half circles of radius 10, in a 40x40 view box
+        let path = filled("M10 20 A10 10 0 0 1 30 20 A10 10 0 0 1 10 20 Z");
+        path.borrow_mut().set_view_box(Vec2f::new(0.0, 0.0), Vec2f::new(40.0, 40.0));
+
+        let mut canvas = Canvas::new(Vec2::new(40, 40));
+        Shape::<RustConnection>::rasterize(&*path.borrow(), &mut canvas).unwrap();
+        let area = std::f32::consts::PI * 100.0;
+        assert!((masked(&canvas) as f32 - area).abs() < area * 0.03);
+
+        // Twice the size on a twice larger canvas: the curve is flattened again
+        let mut canvas = Canvas::new(Vec2::new(80, 80));
+        Shape::<RustConnection>::rasterize(&*path.borrow(), &mut canvas).unwrap();
+        assert!((masked(&canvas) as f32 - area * 4.0).abs() < area * 4.0 * 0.03);
+    }
+
+    #[test]
+    fn test_holes() {
+        // Outer square and inner square in the opposite direction: the inner one is a hole
+        let path = filled("M0 0 H10 V10 H0 Z M3 3 V7 H7 V3 Z");
+
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        Shape::<RustConnection>::rasterize(&*path.borrow(), &mut canvas).unwrap();
+        assert_eq!(masked(&canvas), 100 - 16);
+        assert!(!canvas.is_masked(5, 5));
+    }
+
+    #[test]
+    fn test_stroke_subpaths() {
+        let commands = PathCommand::parse("M0 0 L10 0 M0 5 Q5 10 10 5").unwrap();
+        let path = Path::new(
+            Anchor::NorthWest,
+            Coord::new(0.0, 0.0),
+            Size::new(1.0, 1.0),
+            commands,
+            Color::WHITE,
+            Color::WHITE,
+        )
+        .unwrap();
+
+        let primitives = Shape::<RustConnection>::primitives(&*path.borrow(), Vec2::new(100, 100)).unwrap();
+        assert_eq!(primitives.len(), 2);
+        assert!(matches!(&primitives[1], Primitive::Line(points) if points.len() > 2));
+    }
+}
@@ -0,0 +1,284 @@
+//! SVG path data parser
+//!
+//! Parses the `d` attribute of SVG `<path>` elements into absolute path commands:
+//! - every command is supported (`M L H V C S Q T A Z`, relative versions included),
+//! - repeated arguments repeat the command (extra pairs after a move are lines),
+//! - compact numbers are handled (`M0.5.5`, `1e-3`, flags without separator).
+
+use std::error::Error;
+
+use super::{Coord, PathCommand};
+
+/// Parses SVG path data
+///
+/// # Arguments
+///
+/// * `data` - The path data (the `d` attribute of an SVG path)
+///
+/// # Returns
+///
+/// The path commands, with absolute coordinates
+///
+/// # Errors
+///
+/// Returns an error if the path data is malformed.
+pub fn parse(data: &str) -> Result<Vec<PathCommand>, Box<dyn Error>> {
+    let mut parser = Parser {
+        data: data.as_bytes(),
+        index: 0,
+    };
+
+    let mut commands = Vec::new();
+    let (mut current, mut start) = (Coord::new(0.0, 0.0), Coord::new(0.0, 0.0));
+    let mut command: Option<u8> = None;
+
+    // Control points reflected by the smooth curves
+    let (mut cubic_control, mut quad_control): (Option<Coord>, Option<Coord>) = (None, None);
+
+    loop {
+        parser.skip_separators();
+        let Some(next) = parser.peek() else {
+            break;
+        };
+
+        if next.is_ascii_alphabetic() {
+            command = Some(next);
+            parser.index += 1;
+        }
+
+        let name = match command {
+            Some(b'Z' | b'z') if !next.is_ascii_alphabetic() => Err("Unexpected number after a close command")?,
+            Some(name) => name,
+            None => Err("Path data must start with a command")?,
+        };
+
+        let origin = match name.is_ascii_lowercase() {
+            true => current,
+            false => Coord::new(0.0, 0.0),
+        };
+        let (previous_cubic, previous_quad) = (cubic_control.take(), quad_control.take());
+
+        match name.to_ascii_uppercase() {
+            b'M' => {
+                current = origin + parser.point()?;
+                start = current;
+                commands.push(PathCommand::MoveTo(current));
+
+                // Following pairs are lines
+                command = Some(if name == b'm' { b'l' } else { b'L' });
+            }
+            b'L' => {
+                current = origin + parser.point()?;
+                commands.push(PathCommand::LineTo(current));
+            }
+            b'H' => {
+                current = Coord::new(origin.x + parser.number()?, current.y);
+                commands.push(PathCommand::LineTo(current));
+            }
+            b'V' => {
+                current = Coord::new(current.x, origin.y + parser.number()?);
+                commands.push(PathCommand::LineTo(current));
+            }
+            b'C' | b'S' => {
+                let first = match name.to_ascii_uppercase() {
+                    b'C' => origin + parser.point()?,
+                    _ => previous_cubic.map(|control| current * 2.0 - control).unwrap_or(current),
+                };
+                let second = origin + parser.point()?;
+                current = origin + parser.point()?;
+
+                commands.push(PathCommand::CubicTo(first, second, current));
+                cubic_control = Some(second);
+            }
+            b'Q' | b'T' => {
+                let control = match name.to_ascii_uppercase() {
+                    b'Q' => origin + parser.point()?,
+                    _ => previous_quad.map(|control| current * 2.0 - control).unwrap_or(current),
+                };
+                current = origin + parser.point()?;
+
+                commands.push(PathCommand::QuadTo(control, current));
+                quad_control = Some(control);
+            }
+            b'A' => {
+                let radius = Coord::new(parser.number()?, parser.number()?);
+                let rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                current = origin + parser.point()?;
+
+                commands.push(PathCommand::ArcTo {
+                    radius,
+                    rotation,
+                    large_arc,
+                    sweep,
+                    end: current,
+                });
+            }
+            b'Z' => {
+                commands.push(PathCommand::Close);
+                current = start;
+            }
+            _ => Err(format!("Unknown path command '{}'", name as char))?,
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Reads the numbers and flags of path data
+struct Parser<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl Parser<'_> {
+    /// Returns the next byte (None at the end of the data)
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.index).copied()
+    }
+
+    /// Skips the whitespaces and commas
+    fn skip_separators(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0C' | b',')) {
+            self.index += 1;
+        }
+    }
+
+    /// Reads a coordinate pair
+    fn point(&mut self) -> Result<Coord, Box<dyn Error>> {
+        Ok(Coord::new(self.number()?, self.number()?))
+    }
+
+    /// Reads a number (sign, decimals and exponent are optional)
+    fn number(&mut self) -> Result<f32, Box<dyn Error>> {
+        self.skip_separators();
+        let start = self.index;
+
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.index += 1;
+        }
+        let digits = self.skip_digits();
+        let decimals = match self.peek() {
+            Some(b'.') => {
+                self.index += 1;
+                self.skip_digits()
+            }
+            _ => 0,
+        };
+        if digits + decimals == 0 {
+            Err(format!("Expected a number at offset {}", start))?;
+        }
+
+        // The exponent is only read if digits follow (`e` is not a command)
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            let mark = self.index;
+            self.index += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.index += 1;
+            }
+            if self.skip_digits() == 0 {
+                self.index = mark;
+            }
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.index])?;
+        Ok(text.parse()?)
+    }
+
+    /// Reads an arc flag (a single `0` or `1`)
+    fn flag(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.skip_separators();
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => Err(format!("Expected an arc flag at offset {}", self.index))?,
+        };
+        self.index += 1;
+        Ok(flag)
+    }
+
+    /// Skips the digits and returns their count
+    fn skip_digits(&mut self) -> usize {
+        let start = self.index;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.index += 1;
+        }
+        self.index - start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_absolute() {
+        let commands = parse("M 10,20 L 30 40 H 50 V 60 Z").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                PathCommand::MoveTo(Coord::new(10.0, 20.0)),
+                PathCommand::LineTo(Coord::new(30.0, 40.0)),
+                PathCommand::LineTo(Coord::new(50.0, 40.0)),
+                PathCommand::LineTo(Coord::new(50.0, 60.0)),
+                PathCommand::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_relative() {
+        // Extra pairs after a move are lines, a move after a close starts from the subpath start
+        let commands = parse("m10 10 5 0 0 5z m2 2 h-4").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                PathCommand::MoveTo(Coord::new(10.0, 10.0)),
+                PathCommand::LineTo(Coord::new(15.0, 10.0)),
+                PathCommand::LineTo(Coord::new(15.0, 15.0)),
+                PathCommand::Close,
+                PathCommand::MoveTo(Coord::new(12.0, 12.0)),
+                PathCommand::LineTo(Coord::new(8.0, 12.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_compact() {
+        let commands = parse("M0.5.5l-1e1-2.5E-1a1 1 0 01.5.5").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                PathCommand::MoveTo(Coord::new(0.5, 0.5)),
+                PathCommand::LineTo(Coord::new(-9.5, 0.25)),
+                PathCommand::ArcTo {
+                    radius: Coord::new(1.0, 1.0),
+                    rotation: 0.0,
+                    large_arc: false,
+                    sweep: true,
+                    end: Coord::new(-9.0, 0.75),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_smooth_curves() {
+        let commands = parse("M0 0 C0 10 10 10 10 0 S20 -10 20 0 Q25 10 30 0 T40 0").unwrap();
+        assert_eq!(
+            commands[2],
+            PathCommand::CubicTo(Coord::new(10.0, -10.0), Coord::new(20.0, -10.0), Coord::new(20.0, 0.0))
+        );
+        assert_eq!(commands[4], PathCommand::QuadTo(Coord::new(35.0, -10.0), Coord::new(40.0, 0.0)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("10 10").is_err());
+        assert!(parse("M 10").is_err());
+        assert!(parse("M 0 0 Z 10").is_err());
+        assert!(parse("M 0 0 X 10").is_err());
+        assert!(parse("M 0 0 A 1 1 0 2 0 1 1").is_err());
+    }
+}
@@ -0,0 +1,628 @@
+//! Polygon shape module
+//!
+//! This module is used to define the polygon shape object used by the overlay library.
+//!
+//! The points of a polygon are relative to its box: (0.0, 0.0) is the top left corner of the box
+//! and (1.0, 1.0) the bottom right corner. The box is anchored and sized like a rectangle,
+//! so the polygon follows the resize policy of the overlay.
+//! Gradients, patterns and images filling a polygon are relative to its box too.
+
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Point as XPoint, PolyShape},
+};
+
+use crate::{
+    color::Color,
+    drawable::{canvas::Canvas, Drawable},
+    font::FontSet,
+    math::{rect::Rect, vec::Vec2},
+};
+
+use super::{
+    coord::{Anchor, Coord, CoordExt, Size, SizeExt},
+    style::{FillRule, LineStyle},
+    real_box, Batch, Fill, GcontextWrapperExt, Primitive, Shape,
+};
+
+/// Represents a polygon shape object used by the overlay library.
+pub struct Polygon {
+    anchor: Anchor, // Describes where the coordinate is relative to the shape
+    position: Coord,
+    size: Size,
+    points: Vec<Coord>, // Relative to the box of the polygon
+    forground: Color,
+    background: Color,
+    filled: bool,
+    fill_rule: FillRule,
+    style: LineStyle,
+    fill: Fill,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
+}
+
+impl Polygon {
+    /// Creates a new filled polygon shape object.
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the polygon box.
+    /// * `position` - The position of the polygon box.
+    /// * `size` - The size of the polygon box.
+    /// * `points` - The vertices of the polygon, relative to its box.
+    /// * `color` - The color of the polygon.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Polygon` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than three points.
+    pub fn fill(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        points: Vec<Coord>,
+        color: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::create(anchor, position, size, points, color, color, true)
+    }
+
+    /// Creates a new unfilled polygon shape object (closed outline).
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the polygon box.
+    /// * `position` - The position of the polygon box.
+    /// * `size` - The size of the polygon box.
+    /// * `points` - The vertices of the polygon, relative to its box.
+    /// * `forground` - The color of the edges of the polygon.
+    /// * `background` - The background color of the polygon.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Polygon` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than three points.
+    pub fn new(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        points: Vec<Coord>,
+        forground: Color,
+        background: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::create(anchor, position, size, points, forground, background, false)
+    }
+
+    /// Creates a new filled polygon from points in overlay coordinates.
+    ///
+    /// The box of the polygon is the bounding box of the points, anchored at its top left corner.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The vertices of the polygon.
+    /// * `color` - The color of the polygon.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Polygon` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than three points.
+    pub fn from_points(points: Vec<Coord>, color: Color) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let (min, max) = points.iter().fold(
+            (Coord::new(f32::MAX, f32::MAX), Coord::new(f32::MIN, f32::MIN)),
+            |(min, max), point| {
+                (
+                    Coord::new(min.x.min(point.x), min.y.min(point.y)),
+                    Coord::new(max.x.max(point.x), max.y.max(point.y)),
+                )
+            },
+        );
+        let size = max - min;
+
+        // A flat side keeps the points at 0.0
+        let relative = |value: f32, size: f32| if size > 0.0 { value / size } else { 0.0 };
+        let points = points
+            .iter()
+            .map(|point| {
+                let offset = *point - min;
+                Coord::new(relative(offset.x, size.x), relative(offset.y, size.y))
+            })
+            .collect();
+
+        Self::fill(Anchor::NorthWest, min, size, points, color)
+    }
+
+    /// Creates the polygon shape object.
+    fn create(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        points: Vec<Coord>,
+        forground: Color,
+        background: Color,
+        filled: bool,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        if points.len() < 3 {
+            Err("A polygon needs at least three points")?;
+        }
+
+        Ok(Rc::new(RefCell::new(Self {
+            anchor,
+            position,
+            size,
+            points,
+            forground,
+            background,
+            filled,
+            fill_rule: FillRule::default(),
+            style: LineStyle::default(),
+            fill: Fill::default(),
+            visible: true,
+            interactive: false,
+            dirty: true,
+        })))
+    }
+
+    /// Returns the anchor point of the polygon.
+    pub fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    /// Sets the anchor point of the polygon.
+    pub fn set_anchor(&mut self, anchor: Anchor) {
+        self.anchor = anchor;
+        self.dirty = true;
+    }
+
+    /// Returns the position of the polygon.
+    pub fn position(&self) -> &Coord {
+        &self.position
+    }
+
+    /// Sets the position of the polygon.
+    pub fn set_position(&mut self, position: Coord) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    /// Returns the size of the polygon box.
+    pub fn size(&self) -> &Size {
+        &self.size
+    }
+
+    /// Sets the size of the polygon box.
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    /// Returns the vertices of the polygon (relative to its box).
+    pub fn points(&self) -> &[Coord] {
+        &self.points
+    }
+
+    /// Sets the vertices of the polygon (relative to its box).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are less than three points.
+    pub fn set_points(&mut self, points: Vec<Coord>) -> Result<(), Box<dyn Error>> {
+        if points.len() < 3 {
+            Err("A polygon needs at least three points")?;
+        }
+        self.points = points;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the fill rule of the polygon.
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// Sets the rule deciding which parts of a self-intersecting polygon are filled.
+    pub fn set_fill_rule(&mut self, rule: FillRule) {
+        self.fill_rule = rule;
+        self.dirty = true;
+    }
+
+    /// Returns the line style of the outline.
+    pub fn style(&self) -> &LineStyle {
+        &self.style
+    }
+
+    /// Sets the line style of the outline (unused by filled polygons).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dash pattern contains a zero length.
+    pub fn set_style(&mut self, style: LineStyle) -> Result<(), Box<dyn Error>> {
+        if style.dashes.contains(&0) {
+            Err("Dash lengths must not be zero")?;
+        }
+        self.style = style;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns how the inside of the polygon is painted.
+    pub fn fill_style(&self) -> &Fill {
+        &self.fill
+    }
+
+    /// Sets how the inside of the polygon is painted (gradient, stipple pattern or image tile).
+    ///
+    /// The fill is relative to the box of the polygon. Stipples use the color of the polygon where the bits of
+    /// the pattern are set, the other fills replace the color of the polygon (still used for the shape mask).
+    pub fn set_fill_style(&mut self, fill: Fill) {
+        self.fill = fill;
+        self.dirty = true;
+    }
+
+    /// Sets the color of the polygon.
+    pub fn set_forground_color(&mut self, color: Color) {
+        self.forground = color;
+        self.dirty = true;
+    }
+
+    /// Sets the background color of the polygon.
+    pub fn set_background_color(&mut self, color: Color) {
+        self.background = color;
+        self.dirty = true;
+    }
+
+    /// Returns true if the polygon is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the polygon.
+    ///
+    /// A hidden polygon is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the polygon captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the polygon captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// Returns the vertices of the polygon on a drawable of the given size (in pixels).
+    fn real_points(&self, size: Vec2<u16>) -> Vec<XPoint> {
+        let origin = self
+            .position
+            .top_left(&self.anchor, &self.size)
+            .to_real_coord(size);
+        let real_size = self.size.to_real_size(size);
+
+        self.points
+            .iter()
+            .map(|point| {
+                let point = origin + point.hammard(real_size);
+                XPoint {
+                    x: point.x as i16,
+                    y: point.y as i16,
+                }
+            })
+            .collect()
+    }
+}
+
+impl<C: Connection> Shape<C> for Polygon {
+    /// Draws the polygon on the specified drawable using the given graphics context.
+    ///
+    /// The fill rule and line style of the graphics context are set by the overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The X11 connection.
+    /// * `gc` - The graphics context.
+    /// * `drawable` - The drawable to draw on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the drawing is successful, or a `Box` containing an error if the drawing fails.
+    fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let primitives = <Self as Shape<C>>::primitives(self, drawable.size()).unwrap_or_default();
+        Batch::draw(conn, gc, drawable, &primitives)
+    }
+
+    /// Returns the polygon as a `fill_poly` request, or its outline as a closed `poly_line` request.
+    fn primitives(&self, size: Vec2<u16>) -> Option<Vec<Primitive>> {
+        let mut points = self.real_points(size);
+
+        Some(vec![match self.filled {
+            true => {
+                let shape = poly_shape(&points);
+                Primitive::FillPolygon(points, shape)
+            }
+            false => {
+                // The server joins the ends of a closed polyline
+                points.push(points[0]);
+                Primitive::Line(points)
+            }
+        }])
+    }
+
+    /// Draws the polygon on the canvas with its fill rule and line style.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        let mut points: Vec<Coord> = self
+            .real_points(canvas.size())
+            .iter()
+            .map(|point| Coord::new(point.x as f32, point.y as f32))
+            .collect();
+        if !self.filled {
+            points.push(points[0]);
+        }
+
+        let paint = |canvas: &mut Canvas, color: &Color| match self.filled {
+            true => canvas.fill_polygon(&points, self.fill_rule, color),
+            false => canvas.stroke(&points, &self.style, color),
+        };
+
+        match &self.fill {
+            Fill::Solid => paint(canvas, &self.forground),
+            fill => {
+                let mut coverage = Canvas::new(canvas.size());
+                paint(&mut coverage, &Color::WHITE);
+                canvas.paint(&coverage, real_box::<C, _>(self, canvas.size()), fill, &self.forground, &self.background);
+            }
+        }
+        Ok(())
+    }
+
+    fn line_style(&self) -> Option<&LineStyle> {
+        (!self.filled).then_some(&self.style)
+    }
+
+    fn fill_style(&self) -> Option<&Fill> {
+        Some(&self.fill)
+    }
+
+    fn fill_rule(&self) -> Option<FillRule> {
+        self.filled.then_some(self.fill_rule)
+    }
+
+    /// Returns the color of the polygon.
+    fn forground(&self) -> &Color {
+        &self.forground
+    }
+
+    /// Returns the background color of the polygon.
+    fn background(&self) -> &Color {
+        &self.background
+    }
+
+    /// Returns the size of the polygon box.
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Resizes the polygon box to the specified size.
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    fn position(&self) -> Coord {
+        self.position
+    }
+
+    fn set_position(&mut self, position: Coord) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    /// Returns the area covered by the polygon (vertices may lie outside of its box).
+    fn bounds(&self, _conn: &C, _font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        let points = self.real_points(size);
+        let (left, top, right, bottom) = points.iter().fold(
+            (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+            |(left, top, right, bottom), point| {
+                let (x, y) = (point.x as i32, point.y as i32);
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            },
+        );
+
+        let rect = Rect::new(left, top, right - left, bottom - top);
+        Ok(match self.filled {
+            true => rect,
+            false => rect.inflate(self.style.margin()),
+        })
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+/// Returns the shape hint of a polygon for the `fill_poly` request
+///
+/// - Convex: every turn goes the same way and the outline goes around once
+/// - Nonconvex: the edges never cross each other
+/// - Complex: the edges may cross each other
+pub(super) fn poly_shape(points: &[XPoint]) -> PolyShape {
+    // Repeated points have no edge
+    let mut path: Vec<(i64, i64)> = Vec::with_capacity(points.len());
+    for point in points {
+        let point = (point.x as i64, point.y as i64);
+        if path.last() != Some(&point) {
+            path.push(point);
+        }
+    }
+    while path.len() > 1 && path.first() == path.last() {
+        path.pop();
+    }
+    if path.len() < 4 {
+        return PolyShape::CONVEX;
+    }
+
+    let count = path.len();
+    let edge = |index: usize| (path[index % count], path[(index + 1) % count]);
+    let direction = |((x0, y0), (x1, y1)): ((i64, i64), (i64, i64))| (x1 - x0, y1 - y0);
+
+    // Same turn direction at every corner, and a total rotation of one turn
+    let (mut positive, mut negative, mut rotation) = (false, false, 0.0f64);
+    for index in 0..count {
+        let (ax, ay) = direction(edge(index));
+        let (bx, by) = direction(edge(index + 1));
+        let cross = ax * by - ay * bx;
+        positive |= cross > 0;
+        negative |= cross < 0;
+        rotation += (cross as f64).atan2((ax * bx + ay * by) as f64);
+    }
+    if !(positive && negative) && rotation.abs() < 3.0 * std::f64::consts::PI {
+        return PolyShape::CONVEX;
+    }
+
+    // Edges that are not neighbours must not touch
+    for first in 0..count {
+        for second in first + 2..count {
+            if first == 0 && second == count - 1 {
+                continue;
+            }
+            if segments_touch(edge(first), edge(second)) {
+                return PolyShape::COMPLEX;
+            }
+        }
+    }
+
+    PolyShape::NONCONVEX
+}
+
+/// Returns true if two segments share at least one point
+fn segments_touch(first: ((i64, i64), (i64, i64)), second: ((i64, i64), (i64, i64))) -> bool {
+    let orientation = |(ax, ay): (i64, i64), (bx, by): (i64, i64), (cx, cy): (i64, i64)| {
+        ((bx - ax) * (cy - ay) - (by - ay) * (cx - ax)).signum()
+    };
+    let on_segment = |(ax, ay): (i64, i64), (bx, by): (i64, i64), (cx, cy): (i64, i64)| {
+        cx >= ax.min(bx) && cx <= ax.max(bx) && cy >= ay.min(by) && cy <= ay.max(by)
+    };
+
+    let ((a, b), (c, d)) = (first, second);
+    let (o1, o2, o3, o4) = (orientation(a, b, c), orientation(a, b, d), orientation(c, d, a), orientation(c, d, b));
+
+    (o1 != o2 && o3 != o4)
+        || (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    fn points(points: &[(i16, i16)]) -> Vec<XPoint> {
+        points.iter().map(|(x, y)| XPoint { x: *x, y: *y }).collect()
+    }
+
+    #[test]
+    fn test_poly_shape() {
+        let square = points(&[(0, 0), (10, 0), (10, 10), (0, 10)]);
+        assert_eq!(poly_shape(&square), PolyShape::CONVEX);
+
+        let arrow = points(&[(0, 0), (10, 5), (0, 10), (3, 5)]);
+        assert_eq!(poly_shape(&arrow), PolyShape::NONCONVEX);
+
+        let bow_tie = points(&[(0, 0), (10, 10), (10, 0), (0, 10)]);
+        assert_eq!(poly_shape(&bow_tie), PolyShape::COMPLEX);
+
+        // Every turn goes the same way, but the outline goes around twice
+        let star = points(&[(50, 0), (79, 90), (2, 35), (98, 35), (21, 90)]);
+        assert_eq!(poly_shape(&star), PolyShape::COMPLEX);
+    }
+
+    #[test]
+    fn test_from_points() {
+        let polygon = Polygon::from_points(
+            vec![Coord::new(0.25, 0.25), Coord::new(0.75, 0.25), Coord::new(0.5, 0.75)],
+            Color::RED,
+        )
+        .unwrap();
+        let polygon = polygon.borrow();
+
+        assert_eq!(Shape::<RustConnection>::position(&*polygon), Coord::new(0.25, 0.25));
+        assert_eq!(polygon.points()[2], Coord::new(0.5, 1.0));
+
+        // The vertices follow the box
+        let mut canvas = Canvas::new(Vec2::new(100, 100));
+        Shape::<RustConnection>::rasterize(&*polygon, &mut canvas).unwrap();
+        assert!(canvas.is_masked(40, 30));
+        assert!(!canvas.is_masked(25, 55));
+    }
+
+    #[test]
+    fn test_gradient() {
+        use crate::shape::Gradient;
+
+        let polygon = Polygon::fill(
+            Anchor::NorthWest,
+            Coord::new(0.0, 0.0),
+            Size::new(1.0, 1.0),
+            vec![Coord::new(0.0, 0.0), Coord::new(1.0, 0.0), Coord::new(0.0, 1.0)],
+            Color::WHITE,
+        )
+        .unwrap();
+        let gradient = Gradient::radial(Coord::new(0.0, 0.0), 1.0, &[(0.0, Color::RED), (1.0, Color::BLUE)]).unwrap();
+        polygon.borrow_mut().set_fill_style(Fill::Gradient(gradient));
+
+        let mut canvas = Canvas::new(Vec2::new(20, 20));
+        Shape::<RustConnection>::rasterize(&*polygon.borrow(), &mut canvas).unwrap();
+
+        assert!(canvas.pixel(1, 1).unwrap()[0] > 0xE0);
+        assert!(canvas.pixel(15, 1).unwrap()[2] > 0xA0);
+        assert!(canvas.is_masked(1, 15));
+        assert!(!canvas.is_masked(15, 15));
+    }
+}
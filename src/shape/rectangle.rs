@@ -1,20 +1,53 @@
 //! Rectangle shape module
 //! 
 //! This module is used to define the rectangle shape object used by the overlay library
+//!
+//! Rectangles may have rounded corners (a capsule when the radius is half of the smallest side),
+//! and may be filled with a gradient, a pattern or an image (see `Fill`).
 
 use std::{cell::RefCell, error::Error, rc::Rc};
 
 use x11rb::{
     connection::Connection,
-    protocol::xproto::{ConnectionExt, Rectangle as XRectangle},
+    protocol::xproto::{Arc as XArc, Rectangle as XRectangle, Segment as XSegment},
 };
 
-use crate::{color::Color, drawable::Drawable};
+use crate::{color::Color, drawable::Drawable, math::vec::Vec2};
 
 use super::{
-    coord::{Anchor, Coord, CoordExt, Size, SizeExt}, GcontextWrapperExt, Shape
+    coord::{Anchor, Coord, CoordExt, Size, SizeExt}, Batch, Fill, GcontextWrapperExt, Gradient, Primitive, Shape
 };
 
+/// Describe the radius of the corners of a rectangle
+///
+/// - Pixels: A fixed radius in pixels
+/// - Fraction: A fraction of the smallest side of the rectangle (0.5 draws a capsule)
+///
+/// The radius is limited to half of the smallest side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Radius {
+    Pixels(u16),
+    Fraction(f32),
+}
+
+impl Default for Radius {
+    fn default() -> Self {
+        Radius::Pixels(0)
+    }
+}
+
+impl Radius {
+    /// Returns the radius in pixels for a rectangle of the given size (in pixels)
+    pub fn to_pixels(&self, width: u16, height: u16) -> u16 {
+        let smallest = width.min(height);
+        let radius = match *self {
+            Radius::Pixels(radius) => radius,
+            Radius::Fraction(fraction) => (fraction.max(0.0) * smallest as f32) as u16,
+        };
+        radius.min(smallest / 2)
+    }
+}
+
 /// Represents a rectangle shape object used by the overlay library.
 pub struct Rectangle {
     anchor: Anchor, // Describes where the coordinate is relative to the shape
@@ -23,6 +56,11 @@ pub struct Rectangle {
     forground: Color,
     background: Color,
     filled: bool,
+    radius: Radius,
+    fill: Fill,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
 }
 
 impl Rectangle {
@@ -51,6 +89,11 @@ impl Rectangle {
             forground: color,
             background: color, // Not used
             filled: true,
+            radius: Radius::default(),
+            fill: Fill::default(),
+            visible: true,
+            interactive: false,
+            dirty: true,
         })))
     }
 
@@ -81,9 +124,96 @@ impl Rectangle {
             forground,
             background,
             filled: false,
+            radius: Radius::default(),
+            fill: Fill::default(),
+            visible: true,
+            interactive: false,
+            dirty: true,
         })))
     }
 
+    /// Creates a new filled rectangle shape object with rounded corners.
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the rectangle.
+    /// * `position` - The position of the rectangle.
+    /// * `size` - The size of the rectangle.
+    /// * `radius` - The radius of the corners.
+    /// * `color` - The color of the rectangle.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Rectangle` object, or a `Box` containing an error if the creation fails.
+    pub fn rounded(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        radius: Radius,
+        color: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let rectangle = Self::fill(anchor, position, size, color)?;
+        rectangle.borrow_mut().radius = radius;
+        Ok(rectangle)
+    }
+
+    /// Creates a new filled capsule shape object (the short sides are half circles).
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the capsule.
+    /// * `position` - The position of the capsule.
+    /// * `size` - The size of the capsule.
+    /// * `color` - The color of the capsule.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Rectangle` object, or a `Box` containing an error if the creation fails.
+    pub fn capsule(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        color: Color,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        Self::rounded(anchor, position, size, Radius::Fraction(0.5), color)
+    }
+
+    /// Creates a new rectangle shape object filled with a gradient.
+    ///
+    /// The whole rectangle is part of the shape mask (see `set_forground_color` to change the mask color).
+    /// Other fills are set with `set_fill_style`.
+    ///
+    /// # Arguments
+    ///
+    /// * `anchor` - The anchor point of the rectangle.
+    /// * `position` - The position of the rectangle.
+    /// * `size` - The size of the rectangle.
+    /// * `gradient` - The gradient filling the rectangle (relative to the rectangle).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference-counted `RefCell` of the created `Rectangle` object, or a `Box` containing an error if the creation fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Gradient, Rectangle}, Color};
+    ///
+    /// // A health bar, from red to green
+    /// let gradient = Gradient::horizontal(Color::RED, Color::GREEN);
+    /// let bar = Rectangle::fill_gradient(Anchor::NorthWest, Coord::new(0.1, 0.1), Size::new(0.3, 0.02), gradient).unwrap();
+    /// ```
+    pub fn fill_gradient(
+        anchor: Anchor,
+        position: Coord,
+        size: Size,
+        gradient: Gradient,
+    ) -> Result<Rc<RefCell<Self>>, Box<dyn Error>> {
+        let rectangle = Self::fill(anchor, position, size, Color::WHITE)?;
+        rectangle.borrow_mut().fill = Fill::Gradient(gradient);
+        Ok(rectangle)
+    }
+
     /// Returns the anchor point of the rectangle.
     pub fn anchor(&self) -> &Anchor {
         &self.anchor
@@ -92,6 +222,7 @@ impl Rectangle {
     /// Sets the anchor point of the rectangle.
     pub fn set_anchor(&mut self, anchor: Anchor) {
         self.anchor = anchor;
+        self.dirty = true;
     }
 
     /// Returns the position of the rectangle.
@@ -102,6 +233,7 @@ impl Rectangle {
     /// Sets the position of the rectangle.
     pub fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
     }
 
     /// Returns the size of the rectangle.
@@ -112,16 +244,81 @@ impl Rectangle {
     /// Sets the size of the rectangle.
     pub fn set_size(&mut self, size: Size) {
         self.size = size;
+        self.dirty = true;
+    }
+
+    /// Returns the radius of the corners.
+    pub fn radius(&self) -> &Radius {
+        &self.radius
+    }
+
+    /// Sets the radius of the corners (applies to both filled and unfilled rectangles).
+    pub fn set_radius(&mut self, radius: Radius) {
+        self.radius = radius;
+        self.dirty = true;
+    }
+
+    /// Returns how the inside of the rectangle is painted.
+    pub fn fill_style(&self) -> &Fill {
+        &self.fill
+    }
+
+    /// Sets how the inside of the rectangle is painted (gradient, stipple pattern or image tile).
+    ///
+    /// Stipples use the color of the rectangle where the bits of the pattern are set (and its background color
+    /// elsewhere if opaque), the other fills replace the color of the rectangle (still used for the shape mask).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord, Size}, Fill, Hatch, Pattern, Rectangle}, Color};
+    ///
+    /// // A restricted area, the game is visible between the red lines
+    /// let area = Rectangle::fill(Anchor::NorthWest, Coord::new(0.1, 0.1), Size::new(0.3, 0.3), Color::RED).unwrap();
+    /// area.borrow_mut().set_fill_style(Fill::stipple(Pattern::hatch(Hatch::Forward, 8, 2)));
+    /// ```
+    pub fn set_fill_style(&mut self, fill: Fill) {
+        self.fill = fill;
+        self.dirty = true;
     }
 
     /// Sets the color of the rectangle.
     pub fn set_forground_color(&mut self, color: Color) {
         self.forground = color;
+        self.dirty = true;
     }
 
     /// Sets the background color of the rectangle.
     pub fn set_background_color(&mut self, color: Color) {
         self.background = color;
+        self.dirty = true;
+    }
+
+    /// Returns true if the rectangle is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the rectangle.
+    ///
+    /// A hidden rectangle is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the rectangle captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the rectangle captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
     }
 
 }
@@ -139,40 +336,44 @@ impl<C: Connection> Shape<C> for Rectangle {
     ///
     /// A `Result` containing `()` if the drawing is successful, or a `Box` containing an error if the drawing fails.
     fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn Drawable) -> Result<(), Box<dyn Error>> {
+        let primitives = <Self as Shape<C>>::primitives(self, drawable.size()).unwrap_or_default();
+        Batch::draw(conn, gc, drawable, &primitives)
+    }
+
+    /// Returns the rectangle as a single `poly_rectangle` or `poly_fill_rectangle` element.
+    ///
+    /// Rounded corners are drawn with arcs, the sides with rectangles (filled) or segments (unfilled).
+    fn primitives(&self, size: Vec2<u16>) -> Option<Vec<Primitive>> {
         // Calculate the position of the rectangle
         let coord = self
             .position
             .top_left(&self.anchor, &self.size)
-            .to_real_coord(drawable.size());
-        let size = self.size.to_real_size(drawable.size());
+            .to_real_coord(size);
+        let size = self.size.to_real_size(size);
 
         let (x, y) = (coord.x as i16, coord.y as i16);
         let (width, height) = (size.x as u16, size.y as u16);
 
-        match self.filled {
-            true => conn.poly_fill_rectangle(
-                drawable.id(),
-                gc.gcontext(),
-                &[XRectangle {
-                    x,
-                    y,
-                    width,
-                    height,
-                }],
-            )?,
-            false => conn.poly_rectangle(
-                drawable.id(),
-                gc.gcontext(),
-                &[XRectangle {
-                    x,
-                    y,
-                    width,
-                    height,
-                }],
-            )?,
+        let rectangle = XRectangle {
+            x,
+            y,
+            width,
+            height,
         };
 
-        Ok(())
+        let radius = self.radius.to_pixels(width, height);
+        if radius == 0 {
+            return Some(vec![match self.filled {
+                true => Primitive::FillRectangle(rectangle),
+                false => Primitive::Rectangle(rectangle),
+            }]);
+        }
+
+        Some(rounded_primitives(rectangle, radius, self.filled))
+    }
+
+    fn fill_style(&self) -> Option<&Fill> {
+        Some(&self.fill)
     }
 
     /// Returns the color of the rectangle.
@@ -193,6 +394,7 @@ impl<C: Connection> Shape<C> for Rectangle {
     /// Resizes the shape to the specified size.
     fn set_size(&mut self, size: Size) {
         self.size = size;
+        self.dirty = true;
     }
     
     fn anchor(&self) -> &Anchor {
@@ -205,5 +407,173 @@ impl<C: Connection> Shape<C> for Rectangle {
     
     fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+/// Returns the primitives of a rectangle with rounded corners
+///
+/// # Arguments
+///
+/// * `rectangle` - The rectangle (in pixels)
+/// * `radius` - The radius of the corners (at most half of the smallest side)
+/// * `filled` - True to fill the rectangle, false to draw its outline
+fn rounded_primitives(rectangle: XRectangle, radius: u16, filled: bool) -> Vec<Primitive> {
+    let XRectangle { x, y, width, height } = rectangle;
+    let (diameter, r) = (radius * 2, radius as i16);
+    let (right, bottom) = (x + width as i16, y + height as i16);
+
+    // Quarter arcs of the corners (angles in 1/64 degrees, counterclockwise from 3 o'clock)
+    let corner = |x: i16, y: i16, start: i16| XArc {
+        x,
+        y,
+        width: diameter,
+        height: diameter,
+        angle1: start * 64,
+        angle2: 90 * 64,
+    };
+    let corners = [
+        corner(right - diameter as i16, y, 0),
+        corner(x, y, 90),
+        corner(x, bottom - diameter as i16, 180),
+        corner(right - diameter as i16, bottom - diameter as i16, 270),
+    ];
+
+    match filled {
+        true => {
+            // Vertical band between the corners, and horizontal band between the top and bottom corners
+            let mut primitives = vec![
+                Primitive::FillRectangle(XRectangle { x: x + r, y, width: width - diameter, height }),
+                Primitive::FillRectangle(XRectangle { x, y: y + r, width, height: height - diameter }),
+            ];
+            primitives.extend(corners.map(Primitive::FillArc));
+            primitives
+        }
+        false => {
+            // Like the rectangle outline, the right and bottom sides are at `x + width` and `y + height`
+            let segment = |x1: i16, y1: i16, x2: i16, y2: i16| Primitive::Segment(XSegment { x1, y1, x2, y2 });
+            let mut primitives = vec![
+                segment(x + r, y, right - r, y),
+                segment(right, y + r, right, bottom - r),
+                segment(x + r, bottom, right - r, bottom),
+                segment(x, y + r, x, bottom - r),
+            ];
+            primitives.extend(corners.map(Primitive::Arc));
+            primitives
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use crate::{drawable::canvas::Canvas, shape::Pattern};
+
+    use super::*;
+
+    #[test]
+    fn test_radius() {
+        assert_eq!(Radius::Pixels(5).to_pixels(100, 40), 5);
+        assert_eq!(Radius::Pixels(50).to_pixels(100, 40), 20);
+        assert_eq!(Radius::Fraction(0.25).to_pixels(100, 40), 10);
+        assert_eq!(Radius::Fraction(0.5).to_pixels(100, 40), 20);
+    }
+
+    #[test]
+    fn test_rounded_mask() {
+        let rectangle = Rectangle::rounded(
+            Anchor::NorthWest,
+            Coord::new(0.0, 0.0),
+            Size::new(1.0, 1.0),
+            Radius::Pixels(10),
+            Color::WHITE,
+        )
+        .unwrap();
+
+        let mut canvas = Canvas::new(Vec2::new(40, 30));
+        Shape::<RustConnection>::rasterize(&*rectangle.borrow(), &mut canvas).unwrap();
+
+        // The corners are cut, the sides are not
+        assert!(!canvas.is_masked(0, 0));
+        assert!(!canvas.is_masked(39, 29));
+        assert!(canvas.is_masked(20, 0));
+        assert!(canvas.is_masked(0, 15));
+        assert!(canvas.is_masked(5, 5));
+    }
+
+    #[test]
+    fn test_capsule_primitives() {
+        let capsule = Rectangle::capsule(Anchor::NorthWest, Coord::new(0.0, 0.0), Size::new(1.0, 1.0), Color::WHITE).unwrap();
+        let primitives = Shape::<RustConnection>::primitives(&*capsule.borrow(), Vec2::new(100, 20)).unwrap();
+
+        // Two bands and four corners of radius 10
+        assert_eq!(primitives.len(), 6);
+        assert!(matches!(primitives[1], Primitive::FillRectangle(XRectangle { height: 0, .. })));
+        assert!(matches!(primitives[2], Primitive::FillArc(XArc { width: 20, .. })));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let gradient = Gradient::horizontal(Color::BLACK, Color::WHITE);
+        let rectangle = Rectangle::fill_gradient(Anchor::NorthWest, Coord::new(0.0, 0.5), Size::new(1.0, 0.5), gradient).unwrap();
+
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        Shape::<RustConnection>::rasterize(&*rectangle.borrow(), &mut canvas).unwrap();
+
+        // Black pixels of the gradient are still part of the mask
+        assert!(canvas.is_masked(0, 5));
+        assert_eq!(canvas.pixel(0, 5), Some([0x0D, 0x0D, 0x0D, 0xFF]));
+        assert_eq!(canvas.pixel(9, 9), Some([0xF2, 0xF2, 0xF2, 0xFF]));
+        assert!(!canvas.is_masked(5, 4));
+
+        // The mask color still decides the shape of the window
+        rectangle.borrow_mut().set_forground_color(Color::TRANSPARENT);
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        Shape::<RustConnection>::rasterize(&*rectangle.borrow(), &mut canvas).unwrap();
+        assert!(!canvas.is_masked(5, 5));
+    }
+
+    #[test]
+    fn test_stipple() {
+        let rectangle = Rectangle::fill(Anchor::NorthWest, Coord::new(0.1, 0.1), Size::new(0.5, 0.5), Color::RED).unwrap();
+        rectangle.borrow_mut().set_fill_style(Fill::stipple(Pattern::checkerboard(1)));
+
+        let mut canvas = Canvas::new(Vec2::new(10, 10));
+        Shape::<RustConnection>::rasterize(&*rectangle.borrow(), &mut canvas).unwrap();
+
+        // The pattern starts at the top left corner of the rectangle, the gaps are see through
+        assert_eq!(canvas.pixel(1, 1), Some(Color::RED.rgba()));
+        assert!(canvas.is_masked(1, 1));
+        assert!(!canvas.is_masked(2, 1));
+        assert!(canvas.is_masked(2, 2));
+        assert!(!canvas.is_masked(0, 0));
     }
 }
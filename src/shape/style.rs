@@ -0,0 +1,459 @@
+//! Style module
+//!
+//! This module defines how the shapes are stroked (width, dashes, caps and joins) and filled (fill rule, arc mode).
+//!
+//! The X server strokes the lines itself, the geometry helpers of this module reproduce it for the
+//! software canvas and the anti-aliased renderer:
+//! - `dash` splits a polyline into the dashes drawn by the server,
+//! - `outline` returns convex polygons covering the stroke of a polyline.
+
+use std::f32::consts::PI;
+
+use x11rb::protocol::xproto::{ArcMode as XArcMode, CapStyle, FillRule as XFillRule, JoinStyle};
+
+use crate::math::vec::Vec2f;
+
+/// Longest miter drawn, relative to the line width (the server uses a bevel join below 11 degrees)
+const MITER_LIMIT: f32 = 10.43;
+
+/// Maximum length of the segments approximating the round caps and joins (in pixels)
+const SEGMENT_LENGTH: f32 = 1.0;
+
+/// Describe how the ends of the lines are drawn
+///
+/// - Butt: The line stops at the end point
+/// - Round: The line ends with a half circle
+/// - Projecting: The line extends past the end point by half its width
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Cap {
+    #[default]
+    Butt,
+    Round,
+    Projecting,
+}
+
+/// Describe how the corners of the polylines are drawn
+///
+/// - Miter: The outer edges are extended until they meet (bevel for very sharp corners)
+/// - Round: The corner is rounded
+/// - Bevel: The corner is cut
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Join {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Describe which parts of a self-intersecting polygon are filled
+///
+/// - EvenOdd: A point is inside if a ray from it crosses the outline an odd number of times
+/// - Winding: A point is inside if the outline winds around it (non-zero winding number)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FillRule {
+    #[default]
+    EvenOdd,
+    Winding,
+}
+
+impl FillRule {
+    /// Returns true if a point with the given winding number is inside the polygon
+    pub fn is_inside(&self, winding: i32) -> bool {
+        match self {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::Winding => winding != 0,
+        }
+    }
+}
+
+impl From<FillRule> for XFillRule {
+    fn from(rule: FillRule) -> Self {
+        match rule {
+            FillRule::EvenOdd => XFillRule::EVEN_ODD,
+            FillRule::Winding => XFillRule::WINDING,
+        }
+    }
+}
+
+/// Describe how filled arcs are closed
+///
+/// - PieSlice: The arc is closed by two lines to the center of the ellipse
+/// - Chord: The arc is closed by a single line between its end points
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArcMode {
+    #[default]
+    PieSlice,
+    Chord,
+}
+
+impl From<ArcMode> for XArcMode {
+    fn from(mode: ArcMode) -> Self {
+        match mode {
+            ArcMode::PieSlice => XArcMode::PIE_SLICE,
+            ArcMode::Chord => XArcMode::CHORD,
+        }
+    }
+}
+
+impl From<Cap> for CapStyle {
+    fn from(cap: Cap) -> Self {
+        match cap {
+            Cap::Butt => CapStyle::BUTT,
+            Cap::Round => CapStyle::ROUND,
+            Cap::Projecting => CapStyle::PROJECTING,
+        }
+    }
+}
+
+impl From<Join> for JoinStyle {
+    fn from(join: Join) -> Self {
+        match join {
+            Join::Miter => JoinStyle::MITER,
+            Join::Round => JoinStyle::ROUND,
+            Join::Bevel => JoinStyle::BEVEL,
+        }
+    }
+}
+
+/// Represents the style of the lines drawn by a graphics context
+///
+/// The default style is the one of a new graphics context: thin solid lines (width 0),
+/// butt caps and miter joins.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineStyle {
+    /// The width of the lines in pixels (0 draws one pixel wide lines with the fastest algorithm)
+    pub width: u16,
+    /// The dash pattern, alternating drawn and skipped lengths in pixels (empty for solid lines)
+    pub dashes: Vec<u8>,
+    /// The offset of the dash pattern at the start of the lines (in pixels)
+    pub dash_offset: u16,
+    /// The style of the ends of the lines
+    pub cap: Cap,
+    /// The style of the corners of the polylines
+    pub join: Join,
+}
+
+impl LineStyle {
+    /// Creates a solid line style
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the lines (in pixels)
+    ///
+    pub fn new(width: u16) -> Self {
+        Self {
+            width,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if the lines are dashed
+    pub fn is_dashed(&self) -> bool {
+        !self.dashes.is_empty()
+    }
+
+    /// Returns how far the stroke may extend past the points of the line (in pixels)
+    ///
+    /// It accounts for the projecting caps and the longest miter joins.
+    pub fn margin(&self) -> i32 {
+        let half = self.width.max(1) as f32 / 2.0;
+        let extent = match self.join {
+            Join::Miter => half * MITER_LIMIT,
+            _ => half * std::f32::consts::SQRT_2,
+        };
+        extent.ceil() as i32 + 1
+    }
+
+    /// Splits a polyline into its dashes
+    ///
+    /// The pattern continues across the corners of the polyline, like the X server does.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points of the polyline (in pixels)
+    ///
+    /// # Returns
+    ///
+    /// The polylines of the drawn dashes (the whole polyline if the style is solid)
+    pub fn dash(&self, points: &[Vec2f]) -> Vec<Vec<Vec2f>> {
+        let period: u32 = self.dashes.iter().map(|dash| *dash as u32).sum();
+        if period == 0 || points.len() < 2 {
+            return vec![points.to_vec()];
+        }
+
+        // Find the dash at the start of the line
+        let (mut index, mut on) = (0, true);
+        let mut remaining = self.dashes[0] as f32;
+        let mut offset = (self.dash_offset as u32 % period) as f32;
+        while offset > 0.0 {
+            if offset < remaining {
+                remaining -= offset;
+                break;
+            }
+            offset -= remaining;
+            index = (index + 1) % self.dashes.len();
+            remaining = self.dashes[index] as f32;
+            on = !on;
+        }
+
+        let mut dashes = Vec::new();
+        let mut current = if on { vec![points[0]] } else { Vec::new() };
+
+        for segment in points.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            let length = (end - start).length();
+            let mut travelled = 0.0;
+
+            while length - travelled > remaining {
+                travelled += remaining;
+                let point = start + (end - start) * (travelled / length);
+
+                if on {
+                    // A dash may end right on a corner
+                    if current.last() != Some(&point) {
+                        current.push(point);
+                    }
+                    dashes.push(std::mem::take(&mut current));
+                } else {
+                    current = vec![point];
+                }
+
+                index = (index + 1) % self.dashes.len();
+                remaining = self.dashes[index] as f32;
+                on = !on;
+            }
+
+            remaining -= length - travelled;
+            if on {
+                current.push(end);
+            }
+        }
+
+        if on && current.len() >= 2 {
+            dashes.push(current);
+        }
+
+        dashes
+    }
+
+    /// Returns convex polygons covering the stroke of a polyline (dashes are not applied)
+    ///
+    /// Lines of width 0 are stroked as one pixel wide lines.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points of the polyline (in pixels)
+    ///
+    pub fn outline(&self, points: &[Vec2f]) -> Vec<Vec<Vec2f>> {
+        let half = self.width.max(1) as f32 / 2.0;
+
+        // Repeated points have no direction
+        let mut path: Vec<Vec2f> = Vec::with_capacity(points.len());
+        for point in points {
+            if path.last() != Some(point) {
+                path.push(*point);
+            }
+        }
+
+        let mut polygons = Vec::new();
+        let (Some(first), Some(last)) = (path.first().copied(), path.last().copied()) else {
+            return polygons;
+        };
+
+        if path.len() == 1 {
+            // A single point is only visible with round or projecting caps
+            match self.cap {
+                Cap::Butt => {}
+                Cap::Round => polygons.push(circle(first, half)),
+                Cap::Projecting => polygons.push(vec![
+                    first + Vec2f::new(-half, -half),
+                    first + Vec2f::new(half, -half),
+                    first + Vec2f::new(half, half),
+                    first + Vec2f::new(-half, half),
+                ]),
+            }
+            return polygons;
+        }
+
+        // A closed polyline is joined at its first point instead of having caps
+        let closed = path.len() > 3 && first == last;
+        let cap = if closed { Cap::Butt } else { self.cap };
+
+        let segments = path.len() - 1;
+        for (index, segment) in path.windows(2).enumerate() {
+            let (start, end) = (segment[0], segment[1]);
+            let direction = (end - start).normalize();
+            let normal = perpendicular(direction) * half;
+
+            let start = match (index, cap) {
+                (0, Cap::Projecting) => start - direction * half,
+                _ => start,
+            };
+            let end = match cap {
+                Cap::Projecting if index == segments - 1 => end + direction * half,
+                _ => end,
+            };
+
+            polygons.push(vec![start + normal, end + normal, end - normal, start - normal]);
+        }
+
+        if cap == Cap::Round {
+            polygons.push(circle(first, half));
+            polygons.push(circle(last, half));
+        }
+
+        for corner in path.windows(3) {
+            polygons.push(self.corner(corner[0], corner[1], corner[2], half));
+        }
+        if closed {
+            polygons.push(self.corner(path[path.len() - 2], first, path[1], half));
+        }
+
+        polygons
+    }
+
+    /// Returns the polygon filling the outer side of a corner
+    fn corner(&self, previous: Vec2f, vertex: Vec2f, next: Vec2f, half: f32) -> Vec<Vec2f> {
+        let (incoming, outgoing) = ((vertex - previous).normalize(), (next - vertex).normalize());
+
+        // The outer side is the one the polyline turns away from
+        let side = if perpendicular(incoming).dot(outgoing) < 0.0 { half } else { -half };
+        let (outer_in, outer_out) = (perpendicular(incoming) * side, perpendicular(outgoing) * side);
+        let bevel = vec![vertex, vertex + outer_in, vertex + outer_out];
+
+        match self.join {
+            Join::Round => circle(vertex, half),
+            Join::Bevel => bevel,
+            Join::Miter => {
+                let bisector = outer_in + outer_out;
+                if bisector.length() < f32::EPSILON {
+                    // The polyline goes back on itself
+                    return bevel;
+                }
+
+                let bisector = bisector.normalize();
+                let cosine = bisector.dot(outer_in.normalize());
+                if cosine * MITER_LIMIT < 1.0 {
+                    return bevel;
+                }
+
+                vec![vertex, vertex + outer_in, vertex + bisector * (half / cosine), vertex + outer_out]
+            }
+        }
+    }
+}
+
+/// Returns the vector rotated by 90 degrees
+fn perpendicular(vector: Vec2f) -> Vec2f {
+    Vec2f::new(-vector.y, vector.x)
+}
+
+/// Returns a polygon approximating a circle
+fn circle(center: Vec2f, radius: f32) -> Vec<Vec2f> {
+    let segments = ((2.0 * PI * radius / SEGMENT_LENGTH).ceil() as usize).clamp(8, 256);
+
+    (0..segments)
+        .map(|step| {
+            let angle = 2.0 * PI * step as f32 / segments as f32;
+            center + Vec2f::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sums the area of convex polygons
+    fn area(polygons: &[Vec<Vec2f>]) -> f32 {
+        polygons
+            .iter()
+            .map(|polygon| {
+                let doubled: f32 = polygon
+                    .iter()
+                    .zip(polygon.iter().cycle().skip(1))
+                    .map(|(a, b)| a.x * b.y - b.x * a.y)
+                    .sum();
+                doubled.abs() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_dash() {
+        let style = LineStyle {
+            dashes: vec![4, 2],
+            ..LineStyle::new(1)
+        };
+        let points = [Vec2f::new(0.0, 0.0), Vec2f::new(10.0, 0.0), Vec2f::new(10.0, 5.0)];
+
+        let dashes = style.dash(&points);
+        assert_eq!(dashes.len(), 3);
+        assert_eq!(dashes[0], vec![Vec2f::new(0.0, 0.0), Vec2f::new(4.0, 0.0)]);
+        assert_eq!(dashes[1], vec![Vec2f::new(6.0, 0.0), Vec2f::new(10.0, 0.0)]);
+        assert_eq!(dashes[2], vec![Vec2f::new(10.0, 2.0), Vec2f::new(10.0, 5.0)]);
+
+        // The offset shifts the pattern
+        let shifted = LineStyle { dash_offset: 4, ..style }.dash(&points);
+        assert_eq!(shifted[0], vec![Vec2f::new(2.0, 0.0), Vec2f::new(6.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_dash_solid() {
+        let points = [Vec2f::new(0.0, 0.0), Vec2f::new(10.0, 0.0)];
+        assert_eq!(LineStyle::new(2).dash(&points), vec![points.to_vec()]);
+    }
+
+    #[test]
+    fn test_outline_caps() {
+        let points = [Vec2f::new(0.0, 0.0), Vec2f::new(10.0, 0.0)];
+
+        let butt = LineStyle::new(2);
+        assert!((area(&butt.outline(&points)) - 20.0).abs() < 1e-3);
+
+        let projecting = LineStyle { cap: Cap::Projecting, ..butt };
+        assert!((area(&projecting.outline(&points)) - 24.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_outline_joins() {
+        let points = [Vec2f::new(0.0, 0.0), Vec2f::new(10.0, 0.0), Vec2f::new(10.0, 10.0)];
+        let segments = 40.0;
+
+        // The miter fills the whole outer corner square, the bevel half of it
+        let miter = LineStyle::new(2);
+        assert!((area(&miter.outline(&points)) - segments - 1.0).abs() < 1e-3);
+
+        let bevel = LineStyle { join: Join::Bevel, ..miter };
+        assert!((area(&bevel.outline(&points)) - segments - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_outline_closed() {
+        let square = [
+            Vec2f::new(0.0, 0.0),
+            Vec2f::new(10.0, 0.0),
+            Vec2f::new(10.0, 10.0),
+            Vec2f::new(0.0, 10.0),
+            Vec2f::new(0.0, 0.0),
+        ];
+
+        // Four sides and four mitered corners, no caps even if projecting
+        let style = LineStyle { cap: Cap::Projecting, ..LineStyle::new(2) };
+        assert!((area(&style.outline(&square)) - 84.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        assert!(FillRule::EvenOdd.is_inside(1));
+        assert!(!FillRule::EvenOdd.is_inside(-2));
+        assert!(FillRule::Winding.is_inside(-2));
+        assert!(!FillRule::Winding.is_inside(0));
+    }
+
+    #[test]
+    fn test_margin() {
+        assert_eq!(LineStyle::new(0).margin(), 7);
+        assert_eq!(LineStyle { join: Join::Bevel, ..LineStyle::new(4) }.margin(), 4);
+    }
+}
@@ -1,15 +1,55 @@
 //! Text shape module
 //! 
 //! This module is used to define the text shape object used by the overlay library
+//!
+//! The text is drawn with the fonts of the overlay (see `FontSet`): every character is drawn
+//! with the first font that has its glyph, so non-ASCII characters are not lost.
+//!
+//! A text may have several lines: `\n` starts a new line, and the lines wider than the maximum width of the text
+//! are broken between the words (or truncated with an ellipsis). The lines are aligned in the box of the text.
+//!
+//! The size of a text is the size measured with its font. Resizing a text changes the pixel size of its font
+//! so its lines fill the new height (the closest size available on the server is used),
+//! so the text follows the window like the other shapes.
+//!
+//! With the `truetype` feature, a text can use a TrueType or OpenType font instead (see `TrueTypeFont`):
+//! it is rasterized client-side and uploaded as an image, without any server font.
 
-use std::{cell::RefCell, error::Error, rc::Rc};
+use std::{cell::{Cell, RefCell}, collections::HashMap, error::Error, rc::Rc};
 
-use x11rb::{connection::Connection, protocol::xproto::{Char2b, ConnectionExt, Fontable}};
+use x11rb::{connection::Connection, protocol::xproto::{ChangeGCAux, ConnectionExt, Fontable}};
 
-use crate::{math::vec::Vec2, Color, Drawable, Overlay};
+use crate::{font::{FontSet, FontSpec, TextRun, DEFAULT_PIXEL_SIZE}, math::{rect::Rect, vec::Vec2}, Canvas, Color, Drawable, Overlay, ResizePolicy};
 
-use super::{coord::{Anchor, Coord, CoordExt, Size}, GcontextWrapperExt, Shape};
+use super::{coord::{Anchor, Coord, CoordExt, Size, SizeExt}, layout::{layout, Alignment, LayoutOptions}, GcontextWrapperExt, Shape};
 
+#[cfg(feature = "truetype")]
+use x11rb::protocol::xproto::Rectangle as XRectangle;
+
+#[cfg(feature = "truetype")]
+use crate::{codec::{ximage, Bitmap}, color::Depth, font::truetype::{blend, Coverage, TrueTypeFont}};
+
+#[cfg(feature = "truetype")]
+use super::{image::{opaque_runs, DEFAULT_ALPHA_THRESHOLD}, Batch, Primitive};
+
+
+/// The ellipsis of the truncated lines ("..." if the fonts do not have it)
+const ELLIPSIS: &str = "\u{2026}";
+
+/// Measured runs of a text (in pixels), valid for the fonts and the maximum width they were measured with
+struct Layout {
+    fonts: Vec<Fontable>,
+    max_width: Option<i32>,
+    /// The runs with the position of their baseline (relative to the top left corner of the text)
+    runs: Vec<(TextRun, i16, i16)>,
+    line_height: i32,
+    width: i32,
+    height: i32,
+}
+
+/// Last rasterized text, with its pixel size and the maximum width of its lines (in pixels)
+#[cfg(feature = "truetype")]
+type Rendered = (u16, Option<i32>, Rc<Coverage>);
 
 /// Represents a text shape object used by the overlay library.
 pub struct Text {
@@ -18,12 +58,25 @@ pub struct Text {
     forground: Color,
     background: Color,
     text: String,
-    content: Vec<Char2b>,
-    previous: Rc<RefCell<Option<(Size, Fontable)>>>
-}
-
-fn string_to_char2b(text: &str) -> Vec<Char2b> {
-    text.chars().filter(|c| c.is_ascii()).map(|c| Char2b { byte2: c as u8, byte1: 0x0 }).collect()
+    font: Option<FontSpec>,
+    height: Option<f32>, // Height of the font relative to the window (None keeps the pixel size of the font)
+    alignment: Alignment,
+    line_spacing: f32, // Distance between the baselines, relative to the height of a line
+    max_width: Option<f32>, // Maximum width of the lines relative to the window (None for no limit)
+    max_lines: Option<usize>,
+    word_wrap: bool,
+    previous: Rc<RefCell<Option<Layout>>>,
+    measured: Cell<Size>,
+    measured_lines: Cell<usize>, // Number of lines last measured (0 if never measured)
+    #[cfg(feature = "truetype")]
+    truetype: Option<TrueTypeFont>,
+    #[cfg(feature = "truetype")]
+    coverage_threshold: u8,
+    #[cfg(feature = "truetype")]
+    rendered: RefCell<Option<Rendered>>,
+    visible: bool,
+    interactive: bool,
+    dirty: bool,
 }
 
 impl Text {
@@ -36,7 +89,6 @@ impl Text {
         text: T,
     ) -> Rc<RefCell<Self>> {
         let text = text.to_string();
-        let content = string_to_char2b(&text);
         
         Rc::new(RefCell::new(Self {
             anchor,
@@ -44,8 +96,25 @@ impl Text {
             forground,
             background,
             text,
-            content,
-            previous: Rc::new(RefCell::new(None))
+            font: None,
+            height: None,
+            alignment: Alignment::Left,
+            line_spacing: 1.0,
+            max_width: None,
+            max_lines: None,
+            word_wrap: true,
+            previous: Rc::new(RefCell::new(None)),
+            measured: Cell::new(Size::new(0.0, 0.0)),
+            measured_lines: Cell::new(0),
+            #[cfg(feature = "truetype")]
+            truetype: None,
+            #[cfg(feature = "truetype")]
+            coverage_threshold: DEFAULT_ALPHA_THRESHOLD,
+            #[cfg(feature = "truetype")]
+            rendered: RefCell::new(None),
+            visible: true,
+            interactive: false,
+            dirty: true,
         }))
     }
 
@@ -59,13 +128,177 @@ impl Text {
             return // No need to update the content
         }
         self.text = text;
-        self.content = string_to_char2b(&self.text);
         // Force a recalculation of the size as the text has changed
-        *self.previous.borrow_mut() = None;
+        self.invalidate();
+    }
+
+    /// Returns the alignment of the lines.
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Sets the alignment of the lines in the box of the text (as wide as its widest line).
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        if alignment != self.alignment {
+            self.alignment = alignment;
+            self.invalidate();
+        }
+    }
+
+    /// Returns the distance between the baselines of the lines, relative to the height of a line.
+    /// Returns the height of the font relative to the window (None keeps the pixel size of the font).
+    pub fn font_height(&self) -> Option<f32> {
+        self.height
+    }
+
+    /// Sets the height of the font relative to the window (the pixel size of the font follows the window).
+    ///
+    /// Unlike `set_size`, the height is the height of a line, whatever the number of lines.
+    pub fn set_font_height(&mut self, height: f32) {
+        self.height = Some(height.max(0.0));
+        self.invalidate();
+    }
+
+    pub fn line_spacing(&self) -> f32 {
+        self.line_spacing
+    }
+
+    /// Sets the distance between the baselines of the lines, relative to the height of a line (1.0 by default).
+    pub fn set_line_spacing(&mut self, spacing: f32) {
+        self.line_spacing = spacing.max(0.0);
+        self.invalidate();
+    }
+
+    /// Returns the maximum width of the lines, relative to the window (None for no limit).
+    pub fn max_width(&self) -> Option<f32> {
+        self.max_width
+    }
+
+    /// Sets the maximum width of the lines, relative to the window like the width of a `Size`.
+    ///
+    /// The lines wider than the maximum width are broken between the words (words wider than the line are
+    /// broken between characters), or truncated with an ellipsis when word wrapping is disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::shape::{coord::{Anchor, Coord}, Alignment, Text};
+    /// use xoverlay::Color;
+    ///
+    /// let text = Text::new(Anchor::North, Coord::new(0.5, 0.1), Color::WHITE, Color::BLACK, "Quest log\nFind the three keys hidden in the castle");
+    /// let mut text = text.borrow_mut();
+    /// text.set_max_width(Some(0.3));
+    /// text.set_alignment(Alignment::Center);
+    /// text.set_max_lines(Some(3));
+    /// ```
+    pub fn set_max_width(&mut self, width: Option<f32>) {
+        if width != self.max_width {
+            self.max_width = width;
+            self.invalidate();
+        }
+    }
+
+    /// Returns the maximum number of lines (None for no limit).
+    pub fn max_lines(&self) -> Option<usize> {
+        self.max_lines
+    }
+
+    /// Sets the maximum number of lines, the last line kept ends with an ellipsis (at least one line is kept).
+    pub fn set_max_lines(&mut self, lines: Option<usize>) {
+        if lines != self.max_lines {
+            self.max_lines = lines;
+            self.invalidate();
+        }
+    }
+
+    /// Returns true if the lines are broken between the words.
+    pub fn word_wrap(&self) -> bool {
+        self.word_wrap
+    }
+
+    /// Sets whether the lines wider than the maximum width are broken between the words (default)
+    /// or truncated with an ellipsis.
+    pub fn set_word_wrap(&mut self, wrap: bool) {
+        if wrap != self.word_wrap {
+            self.word_wrap = wrap;
+            self.invalidate();
+        }
     }
 
     pub fn get_size<C: Connection>(&self, overlay: &Overlay<C>) -> Result<Size, Box<dyn Error>> {
-        self.get_size_raw(overlay.conn(), overlay.font().ok_or("No Font Selected")?, overlay.size())
+        let font = <Self as Shape<C>>::font(self, overlay.size());
+        self.get_size_raw(overlay.conn(), Some(&overlay.font_set(font.as_ref())), overlay.size())
+    }
+
+    /// Returns the font of the text (None for the default font of the overlay).
+    pub fn font(&self) -> Option<&FontSpec> {
+        self.font.as_ref()
+    }
+
+    /// Sets the font of the text.
+    ///
+    /// The font is opened by the overlay when the text is drawn. If no server font matches
+    /// the description, the default font of the overlay is used.
+    /// Once the text is resized, its height replaces the pixel size of the description.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xoverlay::{shape::{coord::{Anchor, Coord}, Text}, Color, FontSpec, FontWeight};
+    ///
+    /// let text = Text::new(Anchor::Center, Coord::new(0.5, 0.5), Color::WHITE, Color::BLACK, "Score: 42");
+    /// text.borrow_mut().set_font(Some(FontSpec::new("fixed", FontWeight::Bold, 18)));
+    /// ```
+    pub fn set_font(&mut self, font: Option<FontSpec>) {
+        if font != self.font {
+            self.font = font;
+            self.invalidate();
+        }
+    }
+
+    /// Returns the TrueType font of the text (None when the text is drawn with server fonts).
+    #[cfg(feature = "truetype")]
+    pub fn truetype_font(&self) -> Option<&TrueTypeFont> {
+        self.truetype.as_ref()
+    }
+
+    /// Sets the TrueType font of the text.
+    ///
+    /// The text is rasterized client-side with the font, at the pixel size of its font description
+    /// (the family and weight of the description are not used). Characters missing from the font
+    /// are drawn with its replacement glyph.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use xoverlay::{shape::{coord::{Anchor, Coord}, Text}, Color, TrueTypeFont};
+    ///
+    /// let font = TrueTypeFont::open("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf").unwrap();
+    /// let text = Text::new(Anchor::Center, Coord::new(0.5, 0.5), Color::WHITE, Color::TRANSPARENT, "Score: 42");
+    /// text.borrow_mut().set_truetype_font(Some(font));
+    /// ```
+    #[cfg(feature = "truetype")]
+    pub fn set_truetype_font(&mut self, font: Option<TrueTypeFont>) {
+        if font != self.truetype {
+            self.truetype = font;
+            self.invalidate();
+        }
+    }
+
+    /// Returns the coverage threshold of the TrueType text.
+    #[cfg(feature = "truetype")]
+    pub fn coverage_threshold(&self) -> u8 {
+        self.coverage_threshold
+    }
+
+    /// Sets the coverage threshold of the TrueType text.
+    ///
+    /// Pixels whose coverage is above the threshold are part of the window shape,
+    /// the edges of the glyphs are blended with what is below them.
+    #[cfg(feature = "truetype")]
+    pub fn set_coverage_threshold(&mut self, threshold: u8) {
+        self.coverage_threshold = threshold;
+        self.dirty = true;
     }
 
     /// Returns the position of the text.
@@ -76,78 +309,415 @@ impl Text {
     /// Sets the position of the text.
     pub fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
     }
 
     pub fn set_anchor(&mut self, anchor: Anchor) {
         self.anchor = anchor;
+        self.dirty = true;
     }
 
     /// Sets the color of the text.
     pub fn set_forground_color(&mut self, color: Color) {
         self.forground = color;
+        self.dirty = true;
     }
 
     /// Sets the background color of the text.
     pub fn set_background_color(&mut self, color: Color) {
         self.background = color;
+        self.dirty = true;
     }
 
-    fn get_size_raw<C: Connection>(&self, conn: &C, font: Fontable, size: Vec2<u16>) -> Result<Size, Box<dyn Error>> {
-        
-        let (size, previous) = match self.previous.as_ref().borrow().as_ref() {
-            Some(previous) if previous.1 == font => {
-                // The font has not changed, we can reuse the previous size
-                return Ok(previous.0)
-            },
-            _ => {
-                // First we need to compute the bounding box of the text
-                let extents = conn.query_text_extents(font, &self.content)?.reply()?;
-        
-                let raw_width = extents.overall_width;
-                let raw_height = extents.overall_ascent as i32 + extents.overall_descent as i32;
-                
-        
-                // Translate the size to portion of the screen
-                let width = raw_width as f32 / size.x() as f32;
-                let height = raw_height as f32 / size.y() as f32;
-                
-        
-                let size = Size::new(width, height); // Source: trust me bro
+    /// Returns true if the text is drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the text.
+    ///
+    /// A hidden text is skipped by the overlay (no draw call, not part of the shape mask).
+    pub fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns true if the text captures the mouse.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Sets whether the text captures the mouse.
+    ///
+    /// The overlay is click-through, except on the area of its visible interactive shapes.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
 
-                // Let's store the size for future use
-                (size, Some((size, font)))
+    /// Returns the font description of the text, its pixel size follows the height of the text.
+    fn font_spec(&self, size: Vec2<u16>) -> Option<FontSpec> {
+        match self.height {
+            Some(height) => {
+                let pixel_size = (height * size.y() as f32).round().clamp(1.0, u16::MAX as f32) as u16;
+                Some(self.font.clone().unwrap_or_default().with_pixel_size(pixel_size))
             }
+            None => self.font.clone(),
+        }
+    }
+
+    fn get_size_raw<C: Connection>(&self, conn: &C, fonts: Option<&FontSet>, size: Vec2<u16>) -> Result<Size, Box<dyn Error>> {
+        #[cfg(feature = "truetype")]
+        let rendered = self.coverage(size).map(|coverage| (coverage.width() as i32, coverage.height() as i32));
+        #[cfg(not(feature = "truetype"))]
+        let rendered = None;
+
+        let (raw_width, raw_height) = match rendered {
+            Some(rendered) => rendered,
+            None => self.measure(conn, fonts.ok_or("No font set")?, size)?,
         };
 
-        *self.previous.borrow_mut() = previous;
+        // Translate the size to portion of the screen
+        let width = raw_width as f32 / size.x() as f32;
+        let height = raw_height as f32 / size.y() as f32;
+
+        let size = Size::new(width, height); // Source: trust me bro
+        self.measured.set(size);
 
         Ok(size)
     }
+
+    /// Forgets the measured layout and size, the text is measured again by the overlay
+    fn invalidate(&mut self) {
+        *self.previous.borrow_mut() = None;
+        self.measured.set(Size::new(0.0, 0.0));
+        #[cfg(feature = "truetype")]
+        self.rendered.replace(None);
+        self.dirty = true;
+    }
+
+    /// Returns how the text is broken in lines on a drawable of the given size
+    fn layout_options<'a>(&self, size: Vec2<u16>, ellipsis: &'a str) -> LayoutOptions<'a> {
+        LayoutOptions {
+            max_width: self.max_width.map(|width| (width * size.x() as f32).round() as i32),
+            max_lines: self.max_lines,
+            word_wrap: self.word_wrap,
+            alignment: self.alignment,
+            ellipsis,
+        }
+    }
+
+    /// Returns the height of the text (in pixels), from the top of its first line to the baseline of the last one
+    fn text_height(&self, lines: usize, line_height: i32) -> i32 {
+        let line_advance = (line_height as f32 * self.line_spacing).round() as i32;
+        line_height + lines.saturating_sub(1) as i32 * line_advance
+    }
+
+    /// Returns the number of lines last measured (the number of paragraphs if the text was never measured)
+    fn line_count(&self) -> usize {
+        match self.measured_lines.get() {
+            0 => self.text.split('\n').count(),
+            lines => lines,
+        }
+    }
+
+    /// Breaks the text in lines of font runs and measures them
+    ///
+    /// The layout is kept until the text, the fonts or the maximum width change.
+    ///
+    /// # Returns
+    ///
+    /// The width and height of the text (in pixels)
+    fn measure<C: Connection>(&self, conn: &C, fonts: &FontSet, size: Vec2<u16>) -> Result<(i32, i32), Box<dyn Error>> {
+        let ellipsis = if ELLIPSIS.chars().all(|c| fonts.font_for(c).is_some()) { ELLIPSIS } else { "..." };
+        let options = self.layout_options(size, ellipsis);
+
+        if let Some(previous) = self.previous.borrow().as_ref() {
+            if previous.fonts.iter().copied().eq(fonts.fonts()) && previous.max_width == options.max_width {
+                // The fonts have not changed, we can reuse the previous layout
+                return Ok((previous.width, previous.height));
+            }
+        }
+
+        // First we need to measure every character once (the requests are sent before waiting for the replies)
+        let mut chars: Vec<char> = self.text.chars().chain(ellipsis.chars()).chain([' ']).filter(|c| !matches!(c, '\n' | '\r')).collect();
+        chars.sort_unstable();
+        chars.dedup();
+
+        let mut cookies = Vec::new();
+        for c in chars {
+            if let Some(run) = fonts.runs(&c.to_string()).pop() {
+                cookies.push((c, conn.query_text_extents(run.font, &run.chars)?));
+            }
+        }
+
+        let (mut advances, mut ascent, mut descent) = (HashMap::new(), 0, 0);
+        for (c, cookie) in cookies {
+            let extents = cookie.reply()?;
+            advances.insert(c, extents.overall_width);
+            // The height of the lines only depends on the characters of the text
+            if self.text.contains(c) {
+                ascent = ascent.max(extents.overall_ascent as i32);
+                descent = descent.max(extents.overall_descent as i32);
+            }
+        }
+        let advance = |c: char| advances.get(&c).copied().unwrap_or(0);
+        let (lines, width) = layout(&self.text, &options, &advance);
+        self.measured_lines.set(lines.len());
+
+        // Then the lines are split in font runs, each line below the previous one
+        let line_height = ascent + descent;
+        let mut runs = Vec::new();
+        for (index, segments) in lines.iter().enumerate() {
+            let baseline = self.text_height(index + 1, line_height);
+            for segment in segments {
+                let (mut x, mut chars) = (segment.x, segment.text.chars());
+                for run in fonts.runs(&segment.text) {
+                    let run_width: i32 = chars.by_ref().take(run.chars.len()).map(advance).sum();
+                    runs.push((run, x as i16, baseline as i16));
+                    x += run_width;
+                }
+            }
+        }
+
+        // Let's store the layout for future use
+        let height = self.text_height(lines.len(), line_height);
+        *self.previous.borrow_mut() = Some(Layout {
+            fonts: fonts.fonts().collect(),
+            max_width: options.max_width,
+            runs,
+            line_height,
+            width,
+            height,
+        });
+
+        Ok((width, height))
+    }
+
+    /// Returns the text rasterized with its TrueType font (None without TrueType font)
+    ///
+    /// The coverage is kept until the text, the font or its pixel size change.
+    #[cfg(feature = "truetype")]
+    fn coverage(&self, size: Vec2<u16>) -> Option<Rc<Coverage>> {
+        let font = self.truetype.as_ref()?;
+        let pixel_size = self.font_spec(size).map_or(DEFAULT_PIXEL_SIZE, |font| font.pixel_size);
+        let ellipsis = if ELLIPSIS.chars().all(|c| font.contains(c)) { ELLIPSIS } else { "..." };
+        let options = self.layout_options(size, ellipsis);
+
+        let mut rendered = self.rendered.borrow_mut();
+        if let Some((previous, max_width, coverage)) = rendered.as_ref() {
+            if *previous == pixel_size && *max_width == options.max_width {
+                return Some(coverage.clone());
+            }
+        }
+
+        let advance = |c: char| font.advance(c, pixel_size).round() as i32;
+        let (lines, width) = layout(&self.text, &options, &advance);
+        self.measured_lines.set(lines.len());
+
+        // The lines are rasterized below each other (with kerning, they may be a bit wider than measured)
+        let (ascent, line_height) = font.line_metrics(pixel_size);
+        let mut parts = Vec::new();
+        for (index, segments) in lines.iter().enumerate() {
+            let top = self.text_height(index + 1, line_height as i32) - line_height as i32;
+            for segment in segments {
+                parts.push((segment.x, top, font.rasterize(&segment.text, pixel_size)));
+            }
+        }
+        let width = parts.iter().map(|(x, _, part)| x + part.width() as i32).fold(width, i32::max);
+        let height = self.text_height(lines.len(), line_height as i32);
+
+        let mut coverage = Coverage::blank(width.clamp(0, u16::MAX as i32) as u16, height.clamp(0, u16::MAX as i32) as u16, ascent);
+        for (x, y, part) in &parts {
+            coverage.blit(*x, *y, part);
+        }
+
+        let coverage = Rc::new(coverage);
+        *rendered = Some((pixel_size, options.max_width, coverage.clone()));
+        Some(coverage)
+    }
+
+    /// Draws the text rasterized with its TrueType font
+    ///
+    /// On the shape mask (depth 1), the pixels above the coverage threshold are filled with the forground color.
+    /// Otherwise the forground color is blended with the coverage over the background color,
+    /// or over the content of the drawable when the background is transparent.
+    #[cfg(feature = "truetype")]
+    fn draw_coverage<C: Connection>(
+        &self,
+        conn: &C,
+        gc: &GcontextWrapperExt<C>,
+        drawable: &dyn Drawable,
+        coverage: &Coverage,
+    ) -> Result<(), Box<dyn Error>> {
+        let size = self.get_size_raw(conn, None, drawable.size())?;
+        let coord = self
+            .position
+            .top_left(&self.anchor, &size)
+            .to_real_coord(drawable.size());
+
+        // Only the part of the text inside of the drawable is uploaded
+        let text = Rect::new(coord.x as i32, coord.y as i32, coverage.width() as i32, coverage.height() as i32);
+        let Some(area) = text.intersection(&Rect::new(0, 0, drawable.width() as i32, drawable.height() as i32)) else {
+            return Ok(());
+        };
+        let (x, y, width, height) = (area.x as i16, area.y as i16, area.width as u16, area.height as u16);
+        let values: Vec<u8> = (area.y..area.bottom())
+            .flat_map(|row| {
+                let start = (row - text.y) as usize * coverage.width() as usize + (area.x - text.x) as usize;
+                coverage.values()[start..start + width as usize].iter().copied()
+            })
+            .collect();
+
+        let depth = drawable.depth();
+        let transparent = self.background == Color::TRANSPARENT;
+
+        if depth == Depth::D1 {
+            if !transparent {
+                let (forground, background) = (gc.foreground().unwrap_or(1), gc.background().unwrap_or(0));
+                let values: Vec<u32> = values
+                    .iter()
+                    .map(|value| if *value > self.coverage_threshold { forground } else { background })
+                    .collect();
+                return ximage::put_image(conn, drawable.id(), gc.gcontext(), depth.value(), (x, y), width, &values);
+            }
+
+            let bitmap = Bitmap::new(width, height, values.iter().map(|value| [0, 0, 0, *value]).collect())?;
+            let primitives: Vec<Primitive> = opaque_runs(&bitmap, self.coverage_threshold)
+                .iter()
+                .map(|(run_x, run_y, length)| {
+                    Primitive::FillRectangle(XRectangle {
+                        x: x + *run_x as i16,
+                        y: y + *run_y as i16,
+                        width: *length,
+                        height: 1,
+                    })
+                })
+                .collect();
+            return Batch::draw(conn, gc, drawable, &primitives);
+        }
+
+        // The colors below the text
+        let under: Vec<[u8; 4]> = if transparent {
+            ximage::get_area(conn, drawable.id(), (x, y), width, height)?
+                .iter()
+                .map(|value| Color::from_value(*value, &depth).rgba())
+                .collect()
+        } else {
+            vec![self.background.rgba(); values.len()]
+        };
+
+        let forground = self.forground.rgba();
+        let pixels: Vec<u32> = values
+            .iter()
+            .zip(under)
+            .map(|(value, under)| {
+                let [r, g, b, a] = blend(under, forground, *value);
+                Color::RGBA(r, g, b, a).value(&depth)
+            })
+            .collect();
+
+        ximage::put_image(conn, drawable.id(), gc.gcontext(), depth.value(), (x, y), width, &pixels)
+    }
 }
 
 impl<C: Connection> Shape<C> for Text {
     fn draw(&self, conn: &C, gc: &GcontextWrapperExt<C>, drawable: &dyn crate::Drawable) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(feature = "truetype")]
+        if let Some(coverage) = self.coverage(drawable.size()) {
+            return self.draw_coverage(conn, gc, drawable, &coverage);
+        }
+
+        let fonts = gc.fonts().ok_or("No font set")?;
         
-        // Build the content of the text
-        let font = gc.font.ok_or("No font set")?;
-        
-        // Then we need to compute the bounding box of the text
-        let size = self.get_size_raw(conn, font, drawable.size())?;
+        // First we need to compute the bounding box of the text
+        let size = self.get_size_raw(conn, Some(fonts), drawable.size())?;
 
         let coord = self
             .position
-            .bottom_left(&self.anchor, &size)
+            .top_left(&self.anchor, &size)
             .to_real_coord(drawable.size());
 
         let (x, y) = (coord.x as i16, coord.y as i16);
 
-        // Draw the text
-        conn.image_text16(
-            drawable.id(),
-            gc.gcontext(),
-            x, y,
-            &self.content
-        )?;
+        // Draw the runs, switching to the fallback fonts when needed
+        let primary = gc.font();
+        let mut current = primary;
+        let layout = self.previous.borrow();
+        for (run, run_x, baseline) in layout.iter().flat_map(|layout| &layout.runs) {
+            if current != Some(run.font) {
+                conn.change_gc(gc.gcontext(), &ChangeGCAux::new().font(run.font))?;
+                current = Some(run.font);
+            }
+            conn.image_text16(drawable.id(), gc.gcontext(), x.saturating_add(*run_x), y.saturating_add(*baseline), &run.chars)?;
+        }
+
+        // The graphics context keeps the primary font
+        if current != primary {
+            conn.change_gc(gc.gcontext(), &ChangeGCAux { font: primary, ..ChangeGCAux::new() })?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws the text on the canvas with the built-in font of the canvas (or its TrueType font).
+    ///
+    /// The characters missing from the built-in font are drawn with its replacement glyph.
+    /// The text is measured with the font of the canvas.
+    fn rasterize(&self, canvas: &mut Canvas) -> Result<(), Box<dyn Error>> {
+        #[cfg(feature = "truetype")]
+        if let Some(coverage) = self.coverage(canvas.size()) {
+            let size = Size::new(
+                coverage.width() as f32 / canvas.width() as f32,
+                coverage.height() as f32 / canvas.height() as f32,
+            );
+            self.measured.set(size);
+            let coord = self
+                .position
+                .top_left(&self.anchor, &size)
+                .to_real_coord(canvas.size());
+
+            // Like the overlay, only the pixels above the coverage threshold are part of the mask
+            let (forground, background) = (self.forground.rgba(), self.background.rgba());
+            let pixels = coverage
+                .values()
+                .iter()
+                .map(|value| match self.background {
+                    Color::TRANSPARENT => [forground[0], forground[1], forground[2], *value],
+                    _ => blend(background, forground, *value),
+                })
+                .collect();
+            let bitmap = Bitmap::new(coverage.width(), coverage.height(), pixels)?;
+            let threshold = if self.background == Color::TRANSPARENT { self.coverage_threshold } else { 0 };
+            canvas.draw_image(coord.x as i32, coord.y as i32, &bitmap, threshold);
+            return Ok(());
+        }
+
+        // The built-in font is monospaced and only has ASCII glyphs
+        let cell = Canvas::text_size(" ");
+        let line_height = cell.y as i32;
+        let (lines, width) = layout(&self.text, &self.layout_options(canvas.size(), "..."), &|_| cell.x as i32);
+        self.measured_lines.set(lines.len());
+
+        let size = Size::new(
+            width as f32 / canvas.width() as f32,
+            self.text_height(lines.len(), line_height) as f32 / canvas.height() as f32,
+        );
+        self.measured.set(size);
+
+        let coord = self
+            .position
+            .top_left(&self.anchor, &size)
+            .to_real_coord(canvas.size());
+
+        // Like image_text16, the glyph cells are filled with the background color
+        for (index, segments) in lines.iter().enumerate() {
+            let baseline = coord.y as i32 + self.text_height(index + 1, line_height);
+            for segment in segments {
+                canvas.draw_text(coord.x as i32 + segment.x, baseline, &segment.text, &self.forground, Some(&self.background));
+            }
+        }
 
         Ok(())
     }
@@ -160,19 +730,54 @@ impl<C: Connection> Shape<C> for Text {
         &self.background
     }
 
-    /// Returns the shape size.
+    /// Returns the measured size of the text.
+    ///
+    /// The overlay measures the text when it is added and when it changes (zero until then).
     fn size(&self) -> Size {
-        // We need to compute the size of the text
-        // I don't know how to do that yet
-        Size::new(0.0, 0.0)
+        self.measured.get()
+    }
+
+    /// Measures the text with its fonts (or its TrueType font).
+    fn measure(&self, conn: &C, font: Option<&FontSet>, size: Vec2<u16>) -> Result<(), Box<dyn Error>> {
+        self.get_size_raw(conn, font, size).map(|_| ())
+    }
+
+    /// Resizes the text to the specified height.
+    ///
+    /// The height is the height of the box of the text (like `size`): the height of the font is the height
+    /// of a line, derived from the number of lines last measured and the line spacing.
+    /// The width follows the font (the closest pixel size available on the server is used).
+    /// See `set_font_height` to set the height of a line directly.
+    fn set_size(&mut self, size: Size) {
+        let lines = self.line_count().max(1) as f32;
+        self.set_font_height(size.y / (1.0 + (lines - 1.0) * self.line_spacing));
+    }
+
+    /// Returns the server font of the text, its pixel size follows the height of the text (see `set_font_height`).
+    ///
+    /// TrueType texts do not use server fonts.
+    fn font(&self, size: Vec2<u16>) -> Option<FontSpec> {
+        #[cfg(feature = "truetype")]
+        if self.truetype.is_some() {
+            return None;
+        }
+        self.font_spec(size)
     }
 
-    /// Resizes the shape to the specified size.
-    fn set_size(&mut self, _size: Size) {
-        // We need to update the font size
-        // I don't know how to do that yet
+    /// Adapts the text to a new window size.
+    ///
+    /// The position follows the resize policy like the other shapes, and so does the height of the font:
+    /// the text grows with the window unless the policy keeps the heights in pixels.
+    fn resize(&mut self, policy: ResizePolicy, previous: Vec2<u16>, size: Vec2<u16>) {
+        let scale = policy.scale(previous, size);
+        let height = self.height.unwrap_or_else(|| {
+            let pixel_size = self.font.as_ref().map_or(DEFAULT_PIXEL_SIZE, |font| font.pixel_size);
+            pixel_size as f32 / previous.y().max(1) as f32
+        });
 
-        // TODO: Implement resizing of the text
+        self.height = Some(height * scale.y);
+        self.position = self.position.hammard(scale);
+        self.invalidate();
     }
     
     fn anchor(&self) -> &Anchor {
@@ -185,5 +790,173 @@ impl<C: Connection> Shape<C> for Text {
     
     fn set_position(&mut self, position: Coord) {
         self.position = position;
+        self.dirty = true;
     }
-}
\ No newline at end of file
+
+    /// Returns the area covered by the text.
+    ///
+    /// The last line is drawn on its baseline, so its descent may go below the computed size:
+    /// the area is extended downward by the height of a line to cover it.
+    fn bounds(&self, conn: &C, font: Option<&FontSet>, size: Vec2<u16>) -> Result<Rect, Box<dyn Error>> {
+        let text_size = self.get_size_raw(conn, font, size)?;
+
+        let coord = self
+            .position
+            .top_left(&self.anchor, &text_size)
+            .to_real_coord(size);
+        let real_size = text_size.to_real_size(size);
+
+        // TrueType texts are drawn in their box
+        #[cfg(feature = "truetype")]
+        if self.truetype.is_some() {
+            return Ok(Rect::from_real(coord, real_size));
+        }
+
+        let line_height = self.previous.borrow().as_ref().map_or(0, |layout| layout.line_height);
+        Ok(Rect::from_real(coord, Size::new(real_size.x, real_size.y + line_height as f32)))
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if self.visible != visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::rust_connection::RustConnection;
+
+    use super::*;
+
+    fn text() -> Rc<RefCell<Text>> {
+        Text::new(Anchor::NorthWest, Coord::new(0.1, 0.2), Color::WHITE, Color::BLACK, "Label")
+    }
+
+    #[test]
+    fn test_set_size() {
+        let text = text();
+        let size = Vec2::new(800, 600);
+        assert_eq!(Shape::<RustConnection>::font(&*text.borrow(), size), None);
+
+        // The pixel size follows the height of the window
+        Shape::<RustConnection>::set_size(&mut *text.borrow_mut(), Size::new(0.0, 0.05));
+        let font = Shape::<RustConnection>::font(&*text.borrow(), size).unwrap();
+        assert_eq!(font, FontSpec::default().with_pixel_size(30));
+        let font = Shape::<RustConnection>::font(&*text.borrow(), Vec2::new(800, 300)).unwrap();
+        assert_eq!(font.pixel_size, 15);
+    }
+
+    #[test]
+    fn test_lines() {
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "AA\nA");
+        text.borrow_mut().set_alignment(Alignment::Right);
+
+        // The built-in glyphs are 6x8, the second line is on the right of the box
+        let mut canvas = Canvas::new(Vec2::new(40, 40));
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        assert_eq!(canvas.pixel(0, 3), Some(Color::WHITE.rgba()));
+        assert_eq!(canvas.pixel(6, 11), Some(Color::WHITE.rgba()));
+        assert_eq!(canvas.pixel(0, 11), Some([0; 4]));
+
+        // The baselines are two lines apart
+        text.borrow_mut().set_line_spacing(2.0);
+        let mut canvas = Canvas::new(Vec2::new(40, 40));
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        assert_eq!(canvas.pixel(6, 11), Some([0; 4]));
+        assert_eq!(canvas.pixel(6, 19), Some(Color::WHITE.rgba()));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "ABCDEFGH");
+        text.borrow_mut().set_word_wrap(false);
+        text.borrow_mut().set_max_width(Some(0.5));
+
+        // "AB..." fits in 30 pixels
+        let mut canvas = Canvas::new(Vec2::new(60, 20));
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        assert!(canvas.is_masked(0, 3));
+        assert!(!canvas.is_masked(31, 3));
+        assert_eq!(canvas.pixel(31, 1), Some([0; 4]));
+    }
+
+    #[test]
+    fn test_measured_size() {
+        let text = text();
+        let mut canvas = Canvas::new(Vec2::new(100, 80));
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        let size = Shape::<RustConnection>::size(&*text.borrow());
+        assert_eq!(size, Size::new(0.3, 0.1));
+
+        // The size is measured again after a change
+        text.borrow_mut().set_string("Longer label");
+        assert_ne!(Shape::<RustConnection>::size(&*text.borrow()), size);
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        assert_eq!(Shape::<RustConnection>::size(&*text.borrow()), Size::new(0.72, 0.1));
+    }
+
+    #[test]
+    fn test_size_round_trip() {
+        let text = Text::new(Anchor::NorthWest, Coord::new(0.0, 0.0), Color::WHITE, Color::BLACK, "A\nB\nC");
+        text.borrow_mut().set_line_spacing(1.5);
+        let mut canvas = Canvas::new(Vec2::new(100, 80));
+        Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+
+        // Three lines of 8 pixels, 12 pixels apart
+        let size = Shape::<RustConnection>::size(&*text.borrow());
+        assert_eq!(size, Size::new(0.06, 0.4));
+
+        // The font keeps the height of a line instead of growing to the height of the box
+        for _ in 0..3 {
+            let size = Shape::<RustConnection>::size(&*text.borrow());
+            Shape::<RustConnection>::set_size(&mut *text.borrow_mut(), size);
+            let font = Shape::<RustConnection>::font(&*text.borrow(), canvas.size()).unwrap();
+            assert_eq!(font.pixel_size, 8);
+            Shape::<RustConnection>::rasterize(&*text.borrow(), &mut canvas).unwrap();
+        }
+        assert_eq!(Shape::<RustConnection>::size(&*text.borrow()), size);
+
+        text.borrow_mut().set_font_height(0.2);
+        assert_eq!(text.borrow().font_height(), Some(0.2));
+        assert_eq!(Shape::<RustConnection>::font(&*text.borrow(), canvas.size()).unwrap().pixel_size, 16);
+    }
+
+    #[test]
+    fn test_resize() {
+        let (previous, size) = (Vec2::new(400, 260), Vec2::new(800, 520));
+
+        // The text grows with the window
+        let text = text();
+        Shape::<RustConnection>::resize(&mut *text.borrow_mut(), ResizePolicy::KeepAspectRatio, previous, size);
+        assert_eq!(Shape::<RustConnection>::font(&*text.borrow(), size).unwrap().pixel_size, 2 * DEFAULT_PIXEL_SIZE);
+        assert_eq!(text.borrow().position(), &Coord::new(0.1, 0.2));
+
+        // Unless its height is kept in pixels
+        let text = self::text();
+        Shape::<RustConnection>::resize(&mut *text.borrow_mut(), ResizePolicy::KeepBoth, previous, size);
+        assert_eq!(Shape::<RustConnection>::font(&*text.borrow(), size).unwrap().pixel_size, DEFAULT_PIXEL_SIZE);
+        assert_eq!(text.borrow().position(), &Coord::new(0.05, 0.1));
+    }
+}
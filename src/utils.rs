@@ -58,6 +58,7 @@ fn compute_levensthein_distance_case_insensitive(a: &str, b: &str) -> usize {
     for i in 0..=a_len {
         dp[i][0] = i;
     }
+    #[allow(clippy::needless_range_loop)]
     for j in 0..=b_len {
         dp[0][j] = j;
     }